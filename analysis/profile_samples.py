@@ -24,6 +24,7 @@ class ProfileSamples:
             self.sample_count = read_u64(f)
             self.cluster_buffer_count = read_u64(f)
             self.basic_buffer_count = read_u64(f)
+            self.histogram_bin_count = read_u64(f)
             self.field_count = 4
 
             stamp_count = self.run_count * self.frame_count * self.sample_count * self.field_count
@@ -37,14 +38,14 @@ class ProfileSamples:
 
             self.deltas = np.subtract(self.stamps[:, :, :, [1, 3]], self.stamps[:, :, :, [0, 2]])
 
-            cluster_buffer_u32_size = 256*4
+            cluster_buffer_u32_size = 256 + 3*self.histogram_bin_count
 
             self.cluster_buffers = np.reshape(
                 np.fromfile(f, dtype='uint32', count = self.frame_count*self.cluster_buffer_count*cluster_buffer_u32_size),
                 (self.frame_count, self.cluster_buffer_count, cluster_buffer_u32_size)
             );
 
-            basic_buffer_u32_size = 2
+            basic_buffer_u32_size = 3
 
             self.basic_buffers = np.reshape(
                 np.fromfile(f, dtype='uint32', count = self.frame_count*self.basic_buffer_count*basic_buffer_u32_size),
@@ -4,6 +4,8 @@ use crate::tree::*;
 pub struct Material {
     pub id: i64,
     pub name: String,
+    /// Usually "phong" or "lambert", not every exporter writes it.
+    pub shading_model: Option<String>,
     pub properties: MaterialProperties,
 }
 
@@ -20,6 +22,8 @@ impl_properties70!(MaterialProperties {
     "SpecularFactor" => specular_factor: f64 = 1.0,
     "Shininess" | "ShininessExponent" => shininess: f64 = 20.0,
     "Opacity" => opacity: f64 = 1.0,
+    "Maya|metallic" | "3dsMax|Parameters|metalness" => metallic: f64 = 0.0,
+    "Maya|roughness" | "3dsMax|Parameters|roughness" => roughness: f64 = std::f64::NAN,
 });
 
 impl Material {
@@ -37,6 +41,7 @@ impl Material {
 
         assert_eq!("", node.properties[2].as_str());
 
+        let mut shading_model = None;
         let mut properties = None;
 
         for node in node.children.iter() {
@@ -46,7 +51,8 @@ impl Material {
                     // Don't care.
                 }
                 "ShadingModel" => {
-                    assert_eq!("phong", node.properties[0].as_str());
+                    assert!(shading_model.is_none());
+                    shading_model = Some(String::from(node.properties[0].as_str()));
                 }
                 "MultiLayer" => {
                     assert_eq!(0, node.properties[0].to_i32_exact());
@@ -67,6 +73,7 @@ impl Material {
         Self {
             id,
             name,
+            shading_model,
             properties: properties.unwrap(),
         }
     }
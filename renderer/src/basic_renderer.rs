@@ -4,6 +4,10 @@ pub struct Renderer {
     pub opaque_program: rendering::Program,
     pub masked_program: rendering::Program,
     pub transparent_program: rendering::Program,
+    pub transparent_weighted_blended_program: rendering::Program,
+    pub transparent_linked_list_program: rendering::Program,
+    pub weighted_blended_composite_program: rendering::Program,
+    pub linked_list_composite_program: rendering::Program,
 }
 
 pub struct Parameters {
@@ -27,6 +31,10 @@ glsl_defines!(fixed_header {
         LIGHT_INDICES_BUFFER_BINDING = 11;
         CLUSTER_SPACE_BUFFER_BINDING = 12;
         // PROFILING_CLUSTER_BUFFER_BINDING = 13;
+        // LIGHT_DIR_BUFFER_BINDING = 14;
+        OIT_HEAD_BUFFER_BINDING = 15;
+        OIT_NODE_BUFFER_BINDING = 16;
+        CAMERA_BUFFER_BINDING = 17;
 
         BASIC_ATOMIC_BINDING = 0;
 
@@ -38,11 +46,21 @@ glsl_defines!(fixed_header {
         SHADOW_SAMPLER_BINDING = 6;
         SHADOW_SAMPLER_BINDING_2 = 7;
         SHADOW_SAMPLER_BINDING_3 = 8;
+        SHADOW_ATLAS_SAMPLER_BINDING = 9;
+        OIT_ACCUM_SAMPLER_BINDING = 10;
+        OIT_REVEALAGE_SAMPLER_BINDING = 11;
+        METALLIC_ROUGHNESS_SAMPLER_BINDING = 12;
+        OCCLUSION_SAMPLER_BINDING = 13;
+        IBL_IRRADIANCE_SAMPLER_BINDING = 14;
+        IBL_PREFILTERED_SAMPLER_BINDING = 15;
+        IBL_BRDF_LUT_SAMPLER_BINDING = 16;
     },
     uniforms: {
-        CAM_POS_IN_LGT_LOC = 0;
         VIEWPORT_LOC = 1;
+        // One location per view.
         REN_CLP_TO_CLU_CAM_LOC = 2;
+        OIT_WIDTH_LOC = 4;
+        IBL_INTENSITY_LOC = 5;
     },
 });
 
@@ -54,13 +72,15 @@ impl Context<'_> {
             ..
         } = *self;
 
+        let transparency_mode = self.configuration.transparency.mode;
+        self.main_resources_pool[params.main_resources_index].reconcile_oit(gl, &self.configuration.transparency);
+
         let main_resources = &self.main_resources_pool[params.main_resources_index];
 
         let profiler_index = self
             .profiling_context
             .start(gl, main_resources.profilers.basic_profiler);
 
-        let cam_pos_in_lgt = main_resources.camera.cam_pos_in_wld();
         let cluster_resources_index = main_resources.cluster_resources_index;
 
         unsafe {
@@ -68,7 +88,31 @@ impl Context<'_> {
 
             basic_renderer.opaque_program.update(&mut rendering_context!(self));
             basic_renderer.masked_program.update(&mut rendering_context!(self));
-            basic_renderer.transparent_program.update(&mut rendering_context!(self));
+            let transparent_program = match transparency_mode {
+                configuration::TransparencyMode::Unsorted => &mut basic_renderer.transparent_program,
+                configuration::TransparencyMode::WeightedBlended => {
+                    &mut basic_renderer.transparent_weighted_blended_program
+                }
+                configuration::TransparencyMode::LinkedList => &mut basic_renderer.transparent_linked_list_program,
+            };
+            transparent_program.update(&mut rendering_context!(self));
+            let composite_program = match transparency_mode {
+                configuration::TransparencyMode::Unsorted => None,
+                configuration::TransparencyMode::WeightedBlended => {
+                    Some(&mut basic_renderer.weighted_blended_composite_program)
+                }
+                configuration::TransparencyMode::LinkedList => Some(&mut basic_renderer.linked_list_composite_program),
+            };
+            let composite_program = match composite_program {
+                Some(program) => {
+                    program.update(&mut rendering_context!(self));
+                    match program.name {
+                        ProgramName::Linked(name) => Some(name),
+                        ProgramName::Unlinked(_) => None,
+                    }
+                }
+                None => None,
+            };
             if let (
                 &ProgramName::Linked(opaque_program),
                 &ProgramName::Linked(masked_program),
@@ -76,7 +120,7 @@ impl Context<'_> {
             ) = (
                 &basic_renderer.opaque_program.name,
                 &basic_renderer.masked_program.name,
-                &basic_renderer.transparent_program.name,
+                &transparent_program.name,
             ) {
                 if let Some(cluster_resources_index) = cluster_resources_index {
                     let cluster_resources = &self.cluster_resources_pool[cluster_resources_index];
@@ -125,12 +169,16 @@ impl Context<'_> {
                     draw_resources.instance_matrices_buffer,
                 );
 
+                gl.bind_buffer_base(
+                    gl::UNIFORM_BUFFER,
+                    CAMERA_BUFFER_BINDING,
+                    main_resources.camera_buffer_name,
+                );
+
                 gl.bind_buffer(gl::DRAW_INDIRECT_BUFFER, draw_resources.draw_command_buffer);
 
                 gl.bind_vertex_array(self.resources.scene_vao);
-
-                let draw_counts = &draw_resources.draw_counts;
-                let draw_offsets = &draw_resources.draw_offsets;
+                main_resources.enable_view_clipping(gl);
 
                 for &(program, material_kind, profiler) in [
                     (
@@ -161,20 +209,36 @@ impl Context<'_> {
                         let dimensions = main_resources.framebuffer.dimensions.cast::<f32>().unwrap();
                         gl.uniform_4f(VIEWPORT_LOC, [0.0, 0.0, dimensions.x, dimensions.y]);
 
-                        let ren_clp_to_clu_cam =
-                            cluster_resources.computed.wld_to_clu_cam * main_resources.camera.clp_to_wld;
-                        gl.uniform_matrix4f(
+                        let wld_to_clu_cam = cluster_resources.computed.wld_to_clu_cam;
+                        let [ren_clp_to_wld_0, ren_clp_to_wld_1] = main_resources.ren_clp_to_wld();
+                        rendering::uniform_view_matrices(
+                            gl,
                             REN_CLP_TO_CLU_CAM_LOC,
-                            gl::MajorAxis::Column,
-                            ren_clp_to_clu_cam.cast::<f32>().unwrap().as_ref(),
+                            &[wld_to_clu_cam * ren_clp_to_wld_0, wld_to_clu_cam * ren_clp_to_wld_1],
                         );
                     }
 
-                    gl.uniform_3f(CAM_POS_IN_LGT_LOC, cam_pos_in_lgt.cast().unwrap().into());
-
                     gl.bind_texture_unit(SHADOW_SAMPLER_BINDING, self.light_resources.distance_texture);
                     gl.bind_texture_unit(SHADOW_SAMPLER_BINDING_2, self.light_resources.nor_texture);
                     gl.bind_texture_unit(SHADOW_SAMPLER_BINDING_3, self.light_resources.tint_texture);
+                    gl.bind_texture_unit(
+                        SHADOW_ATLAS_SAMPLER_BINDING,
+                        self.light_resources.shadow_atlas.distance_texture_name,
+                    );
+
+                    let ibl = &self.ibl_renderer;
+                    gl.bind_texture_unit(IBL_IRRADIANCE_SAMPLER_BINDING, ibl.irradiance_texture_name);
+                    gl.bind_texture_unit(IBL_PREFILTERED_SAMPLER_BINDING, ibl.prefiltered_texture_name);
+                    gl.bind_texture_unit(IBL_BRDF_LUT_SAMPLER_BINDING, ibl.brdf_lut_texture_name);
+                    // A zero intensity disables sampling the textures.
+                    gl.uniform_1f(
+                        IBL_INTENSITY_LOC,
+                        if self.configuration.ibl.enabled && ibl.generated.is_some() {
+                            self.configuration.ibl.intensity
+                        } else {
+                            0.0
+                        },
+                    );
 
                     self.gl.enable(gl::DEPTH_TEST);
                     match material_kind {
@@ -191,8 +255,6 @@ impl Context<'_> {
                         resources::MaterialKind::Transparent => {
                             self.gl.depth_func(gl::GREATER);
                             self.gl.depth_mask(gl::WriteMask::Disabled);
-                            self.gl.enable(gl::BLEND);
-                            self.gl.blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
                         }
                     }
                     self.gl.color_mask(
@@ -201,6 +263,53 @@ impl Context<'_> {
                         gl::WriteMask::Enabled,
                         gl::WriteMask::Enabled,
                     );
+                    if material_kind == resources::MaterialKind::Transparent {
+                        match transparency_mode {
+                            configuration::TransparencyMode::Unsorted => {
+                                self.gl.enable(gl::BLEND);
+                                self.gl.blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                            }
+                            configuration::TransparencyMode::WeightedBlended => {
+                                let oit = main_resources.weighted_blended_oit.as_ref().unwrap();
+                                self.gl.bind_framebuffer(gl::FRAMEBUFFER, oit.framebuffer_name);
+                                self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                                self.gl.clear(gl::ClearFlag::COLOR_BUFFER);
+                                self.gl.enable(gl::BLEND);
+                                self.gl.blend_func(gl::ONE, gl::ONE);
+                            }
+                            configuration::TransparencyMode::LinkedList => {
+                                let oit = main_resources.linked_list_oit.as_ref().unwrap();
+                                self.gl.clear_named_buffer_sub_data(
+                                    oit.head_buffer_name,
+                                    gl::R32UI,
+                                    0,
+                                    oit.head_buffer_bytes(),
+                                    gl::RED,
+                                    gl::UNSIGNED_INT,
+                                    None,
+                                );
+                                gl.bind_buffer_base(
+                                    gl::SHADER_STORAGE_BUFFER,
+                                    OIT_HEAD_BUFFER_BINDING,
+                                    oit.head_buffer_name,
+                                );
+                                gl.bind_buffer_base(
+                                    gl::SHADER_STORAGE_BUFFER,
+                                    OIT_NODE_BUFFER_BINDING,
+                                    oit.node_buffer_name,
+                                );
+                                gl.uniform_1ui(OIT_WIDTH_LOC, oit.dimensions.x as u32);
+                                gl.memory_barrier(gl::MemoryBarrierFlag::BUFFER_UPDATE);
+                                self.gl.disable(gl::BLEND);
+                                self.gl.color_mask(
+                                    gl::WriteMask::Disabled,
+                                    gl::WriteMask::Disabled,
+                                    gl::WriteMask::Disabled,
+                                    gl::WriteMask::Disabled,
+                                );
+                            }
+                        }
+                    }
 
                     for (material_index, material) in self
                         .resources
@@ -216,15 +325,29 @@ impl Context<'_> {
                         gl.bind_texture_unit(AMBIENT_SAMPLER_BINDING, textures[material.ambient_texture_index].name);
                         gl.bind_texture_unit(DIFFUSE_SAMPLER_BINDING, textures[material.diffuse_texture_index].name);
                         gl.bind_texture_unit(SPECULAR_SAMPLER_BINDING, textures[material.specular_texture_index].name);
+                        gl.bind_texture_unit(
+                            METALLIC_ROUGHNESS_SAMPLER_BINDING,
+                            textures[material.metallic_roughness_texture_index].name,
+                        );
+                        gl.bind_texture_unit(
+                            OCCLUSION_SAMPLER_BINDING,
+                            textures[material.occlusion_texture_index].name,
+                        );
 
                         // Execute draw.
-                        gl.multi_draw_elements_indirect(
-                            gl::TRIANGLES,
-                            gl::UNSIGNED_INT,
-                            draw_offsets[material_index] as usize * std::mem::size_of::<DrawCommand>(),
-                            draw_counts[material_index] as i32,
-                            std::mem::size_of::<DrawCommand>() as i32,
-                        );
+                        draw_resources.multi_draw(gl, material_index);
+                    }
+
+                    if material_kind == resources::MaterialKind::Transparent {
+                        if let Some(composite_program) = composite_program {
+                            composite_transparency(
+                                gl,
+                                &self.resources,
+                                main_resources,
+                                composite_program,
+                                transparency_mode,
+                            );
+                        }
                     }
 
                     self.profiling_context.stop(gl, profiler_index);
@@ -232,6 +355,7 @@ impl Context<'_> {
 
                 gl.unuse_program();
 
+                main_resources.disable_view_clipping(gl);
                 gl.unbind_vertex_array();
             }
         }
@@ -240,6 +364,59 @@ impl Context<'_> {
     }
 }
 
+/// Blends the transparent fragments gathered by the order independent
+/// transparency modes over the main framebuffer.
+unsafe fn composite_transparency(
+    gl: &gl::Gl,
+    resources: &Resources,
+    main_resources: &MainResources,
+    program: gl::ProgramName,
+    transparency_mode: configuration::TransparencyMode,
+) {
+    gl.bind_framebuffer(gl::FRAMEBUFFER, main_resources.framebuffer.framebuffer_name);
+    gl.use_program(program);
+
+    match transparency_mode {
+        configuration::TransparencyMode::Unsorted => unreachable!(),
+        configuration::TransparencyMode::WeightedBlended => {
+            let oit = main_resources.weighted_blended_oit.as_ref().unwrap();
+            gl.bind_texture_unit(OIT_ACCUM_SAMPLER_BINDING, oit.accum_texture_name);
+            gl.bind_texture_unit(OIT_REVEALAGE_SAMPLER_BINDING, oit.revealage_texture_name);
+            gl.blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+        configuration::TransparencyMode::LinkedList => {
+            let oit = main_resources.linked_list_oit.as_ref().unwrap();
+            gl.uniform_1ui(OIT_WIDTH_LOC, oit.dimensions.x as u32);
+            gl.memory_barrier(gl::MemoryBarrierFlag::SHADER_STORAGE);
+            // The composited color is premultiplied and alpha holds the transmittance.
+            gl.blend_func(gl::ONE, gl::SRC_ALPHA);
+        }
+    }
+
+    gl.enable(gl::BLEND);
+    gl.disable(gl::DEPTH_TEST);
+    gl.color_mask(
+        gl::WriteMask::Enabled,
+        gl::WriteMask::Enabled,
+        gl::WriteMask::Enabled,
+        gl::WriteMask::Enabled,
+    );
+
+    main_resources.disable_view_clipping(gl);
+    gl.bind_vertex_array(resources.full_screen_vao);
+    gl.draw_elements(
+        gl::TRIANGLES,
+        (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+        gl::UNSIGNED_INT,
+        0,
+    );
+    gl.bind_vertex_array(resources.scene_vao);
+    main_resources.enable_view_clipping(gl);
+
+    gl.enable(gl::DEPTH_TEST);
+    gl.disable(gl::BLEND);
+}
+
 impl Renderer {
     pub fn new(context: &mut RenderingContext) -> Self {
         fn basic_pass_header(kind: resources::MaterialKind) -> String {
@@ -258,19 +435,61 @@ impl Renderer {
             )
         };
 
-        let mut create_program = |kind: resources::MaterialKind| -> rendering::Program {
-            vs_fs_program(
-                context,
-                "basic_renderer.vert",
-                "basic_renderer.frag",
-                format!("{}{}", fixed_header(), basic_pass_header(kind)),
+        fn transparency_header(mode: configuration::TransparencyMode) -> String {
+            format!(
+                "\
+                #define TRANSPARENCY_MODE_UNSORTED 1\n\
+                #define TRANSPARENCY_MODE_WEIGHTED_BLENDED 2\n\
+                #define TRANSPARENCY_MODE_LINKED_LIST 3\n\
+                #define TRANSPARENCY_MODE {}\n\
+                ",
+                match mode {
+                    configuration::TransparencyMode::Unsorted => "TRANSPARENCY_MODE_UNSORTED",
+                    configuration::TransparencyMode::WeightedBlended => "TRANSPARENCY_MODE_WEIGHTED_BLENDED",
+                    configuration::TransparencyMode::LinkedList => "TRANSPARENCY_MODE_LINKED_LIST",
+                }
             )
-        };
+        }
+
+        let mut create_program =
+            |kind: resources::MaterialKind, mode: configuration::TransparencyMode| -> rendering::Program {
+                vs_fs_program(
+                    context,
+                    "basic_renderer.vert",
+                    "basic_renderer.frag",
+                    format!(
+                        "{}{}{}",
+                        fixed_header(),
+                        basic_pass_header(kind),
+                        transparency_header(mode)
+                    ),
+                )
+            };
+
+        use configuration::TransparencyMode;
+        use resources::MaterialKind;
 
         Renderer {
-            opaque_program: create_program(resources::MaterialKind::Opaque),
-            masked_program: create_program(resources::MaterialKind::Masked),
-            transparent_program: create_program(resources::MaterialKind::Transparent),
+            opaque_program: create_program(MaterialKind::Opaque, TransparencyMode::Unsorted),
+            masked_program: create_program(MaterialKind::Masked, TransparencyMode::Unsorted),
+            transparent_program: create_program(MaterialKind::Transparent, TransparencyMode::Unsorted),
+            transparent_weighted_blended_program: create_program(
+                MaterialKind::Transparent,
+                TransparencyMode::WeightedBlended,
+            ),
+            transparent_linked_list_program: create_program(MaterialKind::Transparent, TransparencyMode::LinkedList),
+            weighted_blended_composite_program: vs_fs_program(
+                context,
+                "overlay_renderer.vert",
+                "oit_weighted_blended.frag",
+                fixed_header(),
+            ),
+            linked_list_composite_program: vs_fs_program(
+                context,
+                "overlay_renderer.vert",
+                "oit_linked_list.frag",
+                fixed_header(),
+            ),
         }
     }
 }
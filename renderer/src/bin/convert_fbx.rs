@@ -1,4 +1,6 @@
 use cgmath::*;
+use renderer::mesh_simplification;
+use renderer::profiling::{ProfilingThread, ThreadProfiler};
 use renderer::scene_file::*;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
@@ -68,6 +70,12 @@ impl ModelExt for fbx::dom::Model {
 
 pub type Triangle = [u32; 3];
 
+/// Number of simplified levels of detail generated per mesh.
+const MAX_LOD_COUNT: usize = 4;
+
+/// Meshes are not simplified below this many triangles.
+const MIN_LOD_TRIANGLE_COUNT: usize = 64;
+
 #[derive(Default)]
 pub struct MeshBuilder {
     vertices: Vec<Vertex>,
@@ -93,6 +101,44 @@ impl MeshBuilder {
     }
 }
 
+/// How the parameters of an FBX material are interpreted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ShadingModel {
+    MetallicRoughness,
+    Phong,
+    Lambert,
+}
+
+impl ShadingModel {
+    fn of(material: &fbx::dom::Material) -> Self {
+        // Prefer the physically based parameters when the exporter wrote them.
+        if !material.properties.roughness.is_nan() {
+            return ShadingModel::MetallicRoughness;
+        }
+        match material.shading_model {
+            Some(ref model) if model.eq_ignore_ascii_case("lambert") => ShadingModel::Lambert,
+            _ => ShadingModel::Phong,
+        }
+    }
+}
+
+/// The specular color of `pbr.glsl` maps [0, 1] onto reflectances at normal
+/// incidence of [0, 0.08], so 0.5 gives the usual dielectric 0.04.
+const DIELECTRIC_SPECULAR_COLOR: [f32; 3] = [0.5; 3];
+
+/// Phong specular colors scale the highlight rather than give the reflectance
+/// at normal incidence and are often black. Keep their tint but clamp the
+/// reflectance to the range of common dielectrics so every material still
+/// reflects its surroundings.
+fn phong_specular_color(specular_color: [f64; 3], specular_factor: f64) -> [f32; 3] {
+    let f0 = |c: f64| (c * specular_factor).max(0.02).min(0.08);
+    [
+        (f0(specular_color[0]) / 0.08) as f32,
+        (f0(specular_color[1]) / 0.08) as f32,
+        (f0(specular_color[2]) / 0.08) as f32,
+    ]
+}
+
 fn read(path: impl AsRef<Path>) -> io::Result<fbx::tree::File> {
     let mut reader = io::BufReader::new(fs::File::open(path)?);
     fbx::tree::File::parse(&mut reader)
@@ -138,18 +184,24 @@ fn visit(node: &fbx::tree::Node, depth: usize) {
     }
 }
 
-fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
+fn convert(profiler: &ThreadProfiler, path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
     let path = path.as_ref();
     let file_dir = path.parent().unwrap();
+
+    let span = profiler.start_with(|| format!("read {}", path.display()));
     let file = read(path).unwrap();
+    profiler.stop(span);
     dbg!(&file.header, file.children.len());
 
+    let span = profiler.start("build dom");
     let root = fbx::dom::Root::from_fbx_file(&file);
+    profiler.stop(span);
 
     let glass_regex = regex::RegexBuilder::new(r"glass").case_insensitive(true).build().unwrap();
 
     let mut file = SceneFile {
         mesh_descriptions: Vec::new(),
+        mesh_lods: Vec::new(),
         pos_in_obj_buffer: Vec::new(),
         nor_in_obj_buffer: Vec::new(),
         bin_in_obj_buffer: Vec::new(),
@@ -200,6 +252,8 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
                     [a[0] as f32, a[1] as f32, a[2] as f32]
                 }
 
+                let shading_model = ShadingModel::of(material);
+
                 RawMaterial {
                     normal_texture_index: None,
                     emissive_color: cast_f64_3(material.properties.emissive_color),
@@ -208,13 +262,29 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
                     ambient_texture_index: None,
                     diffuse_color: cast_f64_3(material.properties.diffuse_color),
                     diffuse_texture_index: None,
-                    specular_color: cast_f64_3(material.properties.specular_color),
+                    specular_color: match shading_model {
+                        ShadingModel::Phong => phong_specular_color(
+                            material.properties.specular_color,
+                            material.properties.specular_factor,
+                        ),
+                        ShadingModel::MetallicRoughness | ShadingModel::Lambert => DIELECTRIC_SPECULAR_COLOR,
+                    },
                     specular_texture_index: None,
                     shininess: material.properties.shininess as f32,
                     opacity: material.properties.opacity as f32,
                     masked: false,
                     // NOTE: Not the nicest way of determining this.
                     transparent: material.properties.opacity < 1.0 || glass_regex.is_match(&material.name),
+                    metallic: material.properties.metallic as f32,
+                    roughness: match shading_model {
+                        ShadingModel::MetallicRoughness => material.properties.roughness as f32,
+                        // Map the phong exponent to the roughness with a similar lobe.
+                        ShadingModel::Phong => (2.0 / (material.properties.shininess + 2.0)).sqrt() as f32,
+                        // Lambert materials have no highlight.
+                        ShadingModel::Lambert => 1.0,
+                    },
+                    metallic_roughness_texture_index: None,
+                    occlusion_texture_index: None,
                 }
             })
             .collect(),
@@ -228,6 +298,8 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
             .collect(),
     };
 
+    let span = profiler.start("convert meshes");
+
     let mut geometry_index_to_mesh_indices: Vec<Vec<u32>> = Vec::new();
 
     for geometry in root.objects.geometries.iter() {
@@ -424,13 +496,37 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
                 }
             };
 
+            let lods = {
+                let positions: Vec<Point3<f32>> = mesh_builder
+                    .vertices
+                    .iter()
+                    .map(|v| Point3::from(v.pos_in_obj).map(FiniteF32::get))
+                    .collect();
+                mesh_simplification::generate_lods(
+                    &positions,
+                    &mesh_builder.triangles,
+                    MAX_LOD_COUNT,
+                    MIN_LOD_TRIANGLE_COUNT,
+                )
+            };
+
+            let lod_offset = file.mesh_lods.len() as u32;
+            let triangle_offset = file.triangle_buffer.len() as u32;
+
+            file.mesh_lods.push(MeshLod {
+                triangle_offset,
+                triangle_count: mesh_builder.triangles.len() as u32,
+            });
+
             file.mesh_descriptions.push(MeshDescription {
-                triangle_offset: file.triangle_buffer.len() as u32,
+                triangle_offset,
                 triangle_count: mesh_builder.triangles.len() as u32,
                 vertex_offset: file.pos_in_obj_buffer.len() as u32,
                 vertex_count: mesh_builder.vertices.len() as u32,
                 bounding_box,
                 bounding_sphere,
+                lod_offset,
+                lod_count: 1 + lods.len() as u32,
             });
 
             file.pos_in_obj_buffer
@@ -444,11 +540,23 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
             file.pos_in_tex_buffer
                 .extend(mesh_builder.vertices.iter().map(|v| v.pos_in_tex));
             file.triangle_buffer.extend(mesh_builder.triangles);
+
+            for lod in lods {
+                file.mesh_lods.push(MeshLod {
+                    triangle_offset: file.triangle_buffer.len() as u32,
+                    triangle_count: lod.len() as u32,
+                });
+                file.triangle_buffer.extend(lod);
+            }
         }
 
         geometry_index_to_mesh_indices.push(mesh_indices);
     }
 
+    profiler.stop(span);
+
+    let span = profiler.start("convert instances");
+
     use fbx::dom::TypedIndex;
 
     let mut model_index_to_incomplete_instances: Vec<(Vec<IncompleteInstance>, usize)> = Vec::new();
@@ -533,8 +641,20 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
                     "NormalMap" => {
                         material.normal_texture_index = some_texture_index;
                     }
-                    "SpecularColor" => {
-                        material.specular_texture_index = some_texture_index;
+                    "SpecularColor" => match ShadingModel::of(&root.objects.materials[material_index as usize]) {
+                        ShadingModel::MetallicRoughness => {
+                            // NOTE: The specular textures of our metallic roughness materials pack occlusion,
+                            // roughness and metalness. They do not carry a specular color.
+                            material.specular_color = DIELECTRIC_SPECULAR_COLOR;
+                            material.metallic_roughness_texture_index = some_texture_index;
+                            material.occlusion_texture_index = some_texture_index;
+                        }
+                        ShadingModel::Phong | ShadingModel::Lambert => {
+                            material.specular_texture_index = some_texture_index;
+                        }
+                    },
+                    "Maya|TEX_ao_map" => {
+                        material.occlusion_texture_index = some_texture_index;
                     }
                     "EmissiveColor" => {
                         material.emissive_texture_index = some_texture_index;
@@ -614,8 +734,12 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
             }),
     );
 
+    profiler.stop(span);
+
+    let span = profiler.start("write scene");
     file.write(&mut std::io::BufWriter::new(std::fs::File::create(&out_path).unwrap()))
         .unwrap();
+    profiler.stop(span);
 
     // let mut file = std::fs::File::open(&out_path).unwrap();
     // let scene_file = SceneFile::read(&mut file).unwrap();
@@ -629,6 +753,23 @@ fn convert(path: impl AsRef<Path>, out_path: impl AsRef<Path>) {
 
 fn main() {
     let resource_dir = std::path::PathBuf::from("resources");
+
+    // With --profile, CPU time spans are recorded so conversions can be
+    // inspected with the chrome trace exporter of the profiling reader.
+    let mut profiling_thread = if std::env::args().skip(1).any(|arg| arg == "--profile") {
+        let profiling_dir = std::path::PathBuf::from("profiling/convert_fbx");
+        fs::create_dir_all(&profiling_dir).unwrap();
+        ProfilingThread::spawn(
+            &profiling_dir.join("events.bin"),
+            std::time::Instant::now(),
+            String::new(),
+        )
+    } else {
+        ProfilingThread::disabled()
+    };
+    profiling_thread.write_header(&[]);
+    let profiler = profiling_thread.cpu_profiler().thread("main");
+
     for p in &[
         // "speedtree/Azalea/LowPoly/Azalea_LowPoly.fbx",
         // "speedtree/Azalea/HighPoly/Azalea.fbx",
@@ -659,6 +800,6 @@ fn main() {
         let i = resource_dir.join(p);
         let o = i.with_extension("bin");
         dbg!(&i, &o);
-        convert(i, o);
+        convert(&profiler, i, o);
     }
 }
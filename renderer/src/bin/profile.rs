@@ -1,216 +1,390 @@
-use cgmath::*;
+use clap::{App, Arg};
 pub(crate) use log::*;
 use renderer::configuration::{self, Configuration};
-use std::path::PathBuf;
+use renderer::profiling::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Mutex};
 
-pub fn run_with_configuration(configuration_path: &str) {
-    let _ = Command::new("cargo")
+/// A benchmark matrix, see `resources/profile_matrix.toml`.
+#[derive(serde::Deserialize, Debug)]
+struct MatrixFile {
+    base: PathBuf,
+    /// Every variant is a table with a `name` and the configuration values
+    /// it overrides.
+    axes: BTreeMap<String, Vec<toml::value::Table>>,
+    matrices: Vec<Matrix>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct Matrix {
+    /// Prefix of the combination names.
+    #[serde(default)]
+    name: String,
+    axes: Vec<String>,
+    /// Configuration values overridden by every combination.
+    #[serde(default)]
+    configuration: toml::value::Table,
+}
+
+struct Combination {
+    name: String,
+    /// Maps the axis names to variant names.
+    parameters: BTreeMap<String, String>,
+    configuration: Configuration,
+}
+
+impl Combination {
+    fn profiling_dir(&self) -> PathBuf {
+        PathBuf::from("profiling").join(&self.name)
+    }
+}
+
+/// Recursively overwrites the values in `target` with the values in `patch`.
+fn merge(target: &mut toml::Value, patch: &toml::Value) {
+    match (target, patch) {
+        (toml::Value::Table(target), toml::Value::Table(patch)) => {
+            for (key, value) in patch.iter() {
+                match target.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn combinations(matrix_file: &MatrixFile, base: &toml::Value) -> Vec<Combination> {
+    let mut combinations = Vec::new();
+
+    for matrix in matrix_file.matrices.iter() {
+        let axes: Vec<&[toml::value::Table]> = matrix
+            .axes
+            .iter()
+            .map(|axis| match matrix_file.axes.get(axis) {
+                Some(variants) => variants.as_slice(),
+                None => panic!("Matrix {:?} uses unknown axis {:?}.", matrix.name, axis),
+            })
+            .collect();
+
+        if axes.iter().any(|variants| variants.is_empty()) {
+            continue;
+        }
+
+        // Iterate over the product like an odometer, the last axis changes fastest.
+        let mut variant_indices = vec![0; axes.len()];
+        'combinations: loop {
+            let mut value = base.clone();
+            merge(&mut value, &toml::Value::Table(matrix.configuration.clone()));
+
+            let mut names: Vec<String> = Vec::new();
+            if !matrix.name.is_empty() {
+                names.push(matrix.name.clone());
+            }
+            let mut parameters = BTreeMap::new();
+
+            for ((axis, variants), &variant_index) in matrix.axes.iter().zip(axes.iter()).zip(variant_indices.iter()) {
+                let mut variant = variants[variant_index].clone();
+                let variant_name = match variant.remove("name") {
+                    Some(toml::Value::String(name)) => name,
+                    _ => panic!("Variant {} of axis {:?} has no name.", variant_index, axis),
+                };
+                merge(&mut value, &toml::Value::Table(variant));
+                names.push(variant_name.clone());
+                parameters.insert(axis.clone(), variant_name);
+            }
+
+            let name = names.join("_");
+            let mut cfg: Configuration = value
+                .try_into()
+                .unwrap_or_else(|error| panic!("Combination {} is not a valid configuration: {}", name, error));
+            cfg.global.mode = configuration::ApplicationMode::Replay;
+            cfg.profiling.name = Some(PathBuf::from(&name));
+
+            combinations.push(Combination {
+                name,
+                parameters,
+                configuration: cfg,
+            });
+
+            let mut axis_index = variant_indices.len();
+            loop {
+                if axis_index == 0 {
+                    break 'combinations;
+                }
+                axis_index -= 1;
+                variant_indices[axis_index] += 1;
+                if variant_indices[axis_index] < axes[axis_index].len() {
+                    break;
+                }
+                variant_indices[axis_index] = 0;
+            }
+        }
+    }
+
+    combinations
+}
+
+/// A result is complete when every run has been recorded and the samples
+/// have been extracted.
+fn is_complete(profiling_dir: &Path, run_count: usize) -> bool {
+    if !profiling_dir.join("samples.bin").exists() {
+        return false;
+    }
+
+    let reader = match EventReader::open(profiling_dir.join("events.bin")) {
+        Ok(reader) => reader,
+        Err(_) => return false,
+    };
+
+    let mut end_run_count = 0;
+    for event in reader {
+        match event {
+            Ok(MeasurementEvent::EndRun) => end_run_count += 1,
+            Ok(_) => {}
+            Err(_) => return false,
+        }
+    }
+    end_run_count == run_count
+}
+
+fn run(combination: &Combination) -> bool {
+    let profiling_dir = combination.profiling_dir();
+    let configuration_path = profiling_dir.join("configuration.toml");
+    combination.configuration.write(&configuration_path).unwrap();
+
+    let status = Command::new("cargo")
         .args(&[
             "run",
             "--bin",
             "renderer",
             "--",
             "--configuration-path",
-            configuration_path,
+            configuration_path.to_str().unwrap(),
         ])
         .status()
         .expect("failed to execute process");
 
-    let _ = Command::new("cargo")
-        .args(&["run", "--bin", "profiling_reader"])
+    if !status.success() {
+        return false;
+    }
+
+    let status = Command::new("cargo")
+        .args(&[
+            "run",
+            "--bin",
+            "profiling_reader",
+            "--",
+            "--dir",
+            profiling_dir.to_str().unwrap(),
+        ])
         .status()
         .expect("failed to execute process");
+
+    status.success() && is_complete(&profiling_dir, combination.configuration.replay.run_count)
 }
 
-struct Scene {
-    short_name: &'static str,
-    scene_path: PathBuf,
-    replay_path: PathBuf,
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
+enum Status {
+    Complete,
+    Failed,
 }
 
-enum Technique {
-    Ortho { size: f64 },
-    Persp { size: u32, displacement: f64 },
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct IndexEntry {
+    name: String,
+    dir: PathBuf,
+    status: Status,
+    parameters: BTreeMap<String, String>,
 }
 
-impl Technique {
-    pub fn name(&self) -> String {
-        match *self {
-            Self::Ortho { size } => format!("ortho_{:04.0}", size * 100.0),
-            Self::Persp { size, displacement } => {
-                if displacement == 0.0 {
-                    format!("persp_{:04}", size)
-                } else {
-                    format!("persp_{:04}_{:02.0}", size, displacement)
-                }
-            }
-        }
-    }
+/// Lists the result directories of all combinations ever run, so that
+/// analysis scripts don't have to parse directory names.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+struct Index {
+    runs: Vec<IndexEntry>,
+}
 
-    pub fn apply(&self, cfg: &mut Configuration) {
-        match *self {
-            Self::Ortho { size } => {
-                cfg.clustered_light_shading.projection = configuration::ClusteringProjection::Orthographic;
-                cfg.clustered_light_shading.orthographic_sides = Vector3 {
-                    x: size,
-                    y: size,
-                    z: size,
-                };
-            }
-            Self::Persp { size, displacement } => {
-                cfg.clustered_light_shading.projection = configuration::ClusteringProjection::Perspective;
-                cfg.clustered_light_shading.perspective_pixels = Vector2 { x: size, y: size };
-                cfg.clustered_light_shading.perspective_align = displacement == 0.0;
-                cfg.clustered_light_shading.perspective_displacement = displacement;
+const INDEX_PATH: &'static str = "profiling/index.toml";
+
+impl Index {
+    fn read() -> Self {
+        match std::fs::read_to_string(INDEX_PATH) {
+            Ok(contents) => {
+                toml::from_str(&contents).unwrap_or_else(|error| panic!("Failed to parse {}: {}", INDEX_PATH, error))
             }
+            Err(_) => Default::default(),
         }
     }
-}
 
-struct Lighting {
-    count: usize,
-    attenuation: configuration::Attenuation,
-}
+    fn update(&mut self, combination: &Combination, status: Status) {
+        let entry = IndexEntry {
+            name: combination.name.clone(),
+            dir: combination.profiling_dir(),
+            status,
+            parameters: combination.parameters.clone(),
+        };
 
-impl Lighting {
-    pub fn apply(&self, cfg: &mut Configuration) {
-        cfg.rain.max_count = self.count;
-        cfg.light.attenuation = self.attenuation;
+        match self.runs.iter_mut().find(|run| run.name == entry.name) {
+            Some(run) => *run = entry,
+            None => self.runs.push(entry),
+        }
+        self.runs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        std::fs::create_dir_all(Path::new(INDEX_PATH).parent().unwrap()).unwrap();
+        std::fs::write(INDEX_PATH, toml::Value::try_from(&*self).unwrap().to_string()).unwrap();
     }
 }
 
 pub fn main() {
     env_logger::init();
 
-    let scenes = [
-        Scene {
-            short_name: "bistro",
-            scene_path: PathBuf::from("bistro/Bistro_Exterior.bin"),
-            replay_path: PathBuf::from("replay_bistro.bin"),
-        },
-        Scene {
-            short_name: "suntem",
-            scene_path: PathBuf::from("sun_temple/SunTemple.bin"),
-            replay_path: PathBuf::from("replay_suntem.bin"),
-        },
-    ];
-
-    let base_cfg = Configuration::read("resources/profile_configuration.toml");
-
-    let lightings = [
-        Lighting {
-            count: 1000,
-            attenuation: configuration::Attenuation {
-                i: 100.0,
-                i0: 0.5,
-                r0: 0.1,
-            },
-        },
-        Lighting {
-            count: 10_000,
-            attenuation: configuration::Attenuation {
-                i: 20.0,
-                i0: 0.5,
-                r0: 0.1,
-            },
-        },
-        Lighting {
-            count: 100_000,
-            attenuation: configuration::Attenuation {
-                i: 1.0,
-                i0: 0.5,
-                r0: 0.1,
-            },
-        },
-    ];
-
-    let techniques: Vec<Technique> = [1.0, 2.0, 4.0, 8.0, 16.0]
-        .iter()
-        .map(|&n| Technique::Ortho { size: n })
-        .chain([16, 32, 64, 96, 128].iter().map(|&n| Technique::Persp {
-            size: n,
-            displacement: 0.0,
-        }))
-        .chain(
-            [1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0]
-                .iter()
-                .map(|&n| Technique::Persp {
-                    size: 64,
-                    displacement: n,
-                }),
+    let matches = App::new("Profile")
+        .version("1.0")
+        .author("Mick van Gelderen")
+        .about("Runs the renderer for every combination in a benchmark matrix.")
+        .arg(
+            Arg::with_name("matrix")
+                .help("Benchmark matrix file")
+                .default_value("resources/profile_matrix.toml")
+                .index(1),
         )
-        .collect();
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .value_name("REGEX")
+                .help("Only runs combinations whose name matches one of the patterns")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .value_name("COUNT")
+                .help("Number of combinations to run at the same time, timings are only reliable with 1")
+                .default_value("1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .value_name("COUNT")
+                .help("Number of times a failed run is retried")
+                .default_value("2")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("Also runs combinations that already have complete results"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .long("list")
+                .help("Lists the selected combinations and their status without running them"),
+        )
+        .get_matches();
 
-    for scene in scenes.iter() {
-        for lighting in lightings.iter() {
-            for technique in techniques.iter() {
-                let name = format!("{}_{:07}_{}", scene.short_name, lighting.count, technique.name());
-                info!("Profiling {}...", &name);
-                let mut cfg = base_cfg.clone();
-                cfg.global.mode = configuration::ApplicationMode::Replay;
-                cfg.global.scene_path = scene.scene_path.clone();
-                cfg.replay.path = scene.replay_path.clone();
-                cfg.profiling.name = Some(PathBuf::from(name.clone()));
-
-                lighting.apply(&mut cfg);
-                technique.apply(&mut cfg);
-
-                let profiling_dir = PathBuf::from("profiling").join(name);
-                let configuration_path = profiling_dir.join("configuration.toml");
-                cfg.write(&configuration_path).unwrap();
-                run_with_configuration(configuration_path.to_str().unwrap());
-            }
+    let matrix_path = matches.value_of("matrix").unwrap();
+    let matrix_file: MatrixFile = toml::from_str(
+        &std::fs::read_to_string(matrix_path)
+            .unwrap_or_else(|error| panic!("Failed to read {}: {}", matrix_path, error)),
+    )
+    .unwrap_or_else(|error| panic!("Failed to parse {}: {}", matrix_path, error));
+
+    let base: toml::Value = toml::from_str(
+        &std::fs::read_to_string(&matrix_file.base)
+            .unwrap_or_else(|error| panic!("Failed to read {}: {}", matrix_file.base.display(), error)),
+    )
+    .unwrap_or_else(|error| panic!("Failed to parse {}: {}", matrix_file.base.display(), error));
+
+    let filters: Vec<regex::Regex> = matches
+        .values_of("filter")
+        .map(|values| values.map(|value| regex::Regex::new(value).unwrap()).collect())
+        .unwrap_or_default();
+    let jobs: usize = matches.value_of("jobs").unwrap().parse().unwrap();
+    let retries: usize = matches.value_of("retries").unwrap().parse().unwrap();
+    let force = matches.is_present("force");
+
+    let mut index = Index::read();
+    let mut pending = VecDeque::new();
+
+    for combination in combinations(&matrix_file, &base) {
+        if !filters.is_empty() && !filters.iter().any(|filter| filter.is_match(&combination.name)) {
+            continue;
+        }
+
+        let complete = is_complete(&combination.profiling_dir(), combination.configuration.replay.run_count);
+
+        if matches.is_present("list") {
+            println!(
+                "{:60} {}",
+                combination.name,
+                if complete { "complete" } else { "pending" }
+            );
+        } else if complete && !force {
+            info!("Skipping {}, results are complete.", &combination.name);
+            index.update(&combination, Status::Complete);
+        } else {
+            pending.push_back(combination);
         }
     }
 
-    let tuned_techniques = vec![
-        Technique::Ortho { size: 4.0 },
-        Technique::Persp {
-            size: 64,
-            displacement: 0.0,
-        },
-        Technique::Persp {
-            size: 64,
-            displacement: 32.0,
-        },
-    ];
-
-    let groupings = vec![
-        ("indi", configuration::ClusteringGrouping::Individual),
-        ("encl", configuration::ClusteringGrouping::Enclosed),
-    ];
-
-    for scene in scenes.iter() {
-        for lighting in lightings.iter() {
-            for technique in tuned_techniques.iter() {
-                for &(grouping_name, grouping) in groupings.iter() {
-                    let name = format!(
-                        "stereo_{}_{:07}_{}_{}",
-                        scene.short_name,
-                        lighting.count,
-                        grouping_name,
-                        technique.name()
-                    );
-                    info!("Profiling {}...", &name);
-                    let mut cfg = base_cfg.clone();
-                    cfg.global.mode = configuration::ApplicationMode::Replay;
-                    cfg.global.scene_path = scene.scene_path.clone();
-                    cfg.replay.path = scene.replay_path.clone();
-                    cfg.profiling.name = Some(PathBuf::from(name.clone()));
-
-                    cfg.virtual_stereo.enabled = true;
-                    cfg.clustered_light_shading.grouping = grouping;
-
-                    lighting.apply(&mut cfg);
-                    technique.apply(&mut cfg);
-
-                    let profiling_dir = PathBuf::from("profiling").join(name);
-                    let configuration_path = profiling_dir.join("configuration.toml");
-                    cfg.write(&configuration_path).unwrap();
-                    run_with_configuration(configuration_path.to_str().unwrap());
+    if matches.is_present("list") {
+        return;
+    }
+
+    let pending_count = pending.len();
+    let pending = Arc::new(Mutex::new(pending));
+    let index = Arc::new(Mutex::new(index));
+
+    let threads: Vec<_> = (0..jobs.max(1))
+        .map(|_| {
+            let pending = Arc::clone(&pending);
+            let index = Arc::clone(&index);
+            std::thread::spawn(move || {
+                let mut failed_count = 0;
+                loop {
+                    // Don't hold the lock while running.
+                    let combination = match pending.lock().unwrap().pop_front() {
+                        Some(combination) => combination,
+                        None => break,
+                    };
+
+                    let mut status = Status::Failed;
+                    for attempt in 0..=retries {
+                        if attempt == 0 {
+                            info!("Profiling {}...", &combination.name);
+                        } else {
+                            warn!("Retrying {} ({}/{})...", &combination.name, attempt, retries);
+                        }
+                        if run(&combination) {
+                            status = Status::Complete;
+                            break;
+                        }
+                    }
+
+                    if status == Status::Failed {
+                        error!("Profiling {} failed {} times.", &combination.name, retries + 1);
+                        failed_count += 1;
+                    }
+                    index.lock().unwrap().update(&combination, status);
                 }
-            }
-        }
+                failed_count
+            })
+        })
+        .collect();
+
+    let failed_count: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+
+    if failed_count > 0 {
+        eprintln!("{} of {} combinations failed.", failed_count, pending_count);
+        std::process::exit(1);
     }
 }
@@ -1,42 +1,13 @@
 use clap::{App, Arg};
 use std::fs::File;
-use std::io::{BufReader, Write};
-use std::path::Path;
+use std::io::Write;
+use std::path::PathBuf;
 
 use renderer::profiling::*;
 use renderer::*;
 
-fn read_events(path: impl AsRef<Path>) -> std::io::Result<Vec<MeasurementEvent>> {
-    let mut file = BufReader::new(File::open(path)?);
-
-    let mut events = Vec::new();
-
-    loop {
-        match bincode::deserialize_from::<_, MeasurementEvent>(&mut file) {
-            Ok(event) => events.push(event),
-            Err(error) => {
-                // Unbox error.
-                match *error {
-                    bincode::ErrorKind::Io(error) => match error.kind() {
-                        std::io::ErrorKind::UnexpectedEof => {
-                            // This is what we expect.
-                            break;
-                        }
-                        other => panic!(other),
-                    },
-                    other => {
-                        panic!(other);
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(events)
-}
-
 fn main() {
-    let _matches = App::new("Profiling Reader")
+    let matches = App::new("Profiling Reader")
         .version("1.0")
         .author("Mick van Gelderen")
         .about("Reads profiling information from logs generated by renderer.")
@@ -46,23 +17,106 @@ fn main() {
                 .required(false)
                 .index(1),
         )
+        .arg(
+            Arg::with_name("chrome-trace")
+                .long("chrome-trace")
+                .value_name("PATH")
+                .help("Write a Chrome trace event JSON file, relative to the profiling directory")
+                .takes_value(true)
+                .min_values(0),
+        )
+        .arg(
+            Arg::with_name("cluster-stats")
+                .long("cluster-stats")
+                .value_name("FORMAT")
+                .help("Write per-frame cluster statistics and histograms to the profiling directory")
+                .possible_values(&["csv", "json"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("configuration")
+                .long("configuration")
+                .help("Print the configuration the run was recorded with"),
+        )
+        .arg(
+            Arg::with_name("dir")
+                .long("dir")
+                .value_name("DIR")
+                .help("Profiling directory, defaults to profiling/latest")
+                .takes_value(true),
+        )
         .get_matches();
 
-    let current_dir = std::env::current_dir().unwrap();
-    let base_profiling_dir = current_dir.join("profiling");
-    let latest_profiling_dir = base_profiling_dir.join("latest");
-    let current_profiling_dir =
-        std::fs::read_link(latest_profiling_dir).expect("Didn't find symlink to latest profiling directory.");
+    let current_profiling_dir = match matches.value_of("dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let current_dir = std::env::current_dir().unwrap();
+            let base_profiling_dir = current_dir.join("profiling");
+            let latest_profiling_dir = base_profiling_dir.join("latest");
+            std::fs::read_link(latest_profiling_dir).expect("Didn't find symlink to latest profiling directory.")
+        }
+    };
     dbg!(&current_profiling_dir);
     let profiling_path = current_profiling_dir.join("events.bin");
 
+    match RunMetadata::read(&current_profiling_dir) {
+        Ok(metadata) => {
+            println!("started at:   {}", metadata.started_at);
+            println!(
+                "git revision: {}",
+                metadata.git_revision.as_ref().map(String::as_str).unwrap_or("unknown")
+            );
+            println!("gl vendor:    {}", metadata.gl_vendor);
+            println!("gl renderer:  {}", metadata.gl_renderer);
+            println!("gl version:   {}", metadata.gl_version);
+            println!("scene:        {}", metadata.scene_path.display());
+            if let (Some(replay_path), Some(replay_hash)) = (&metadata.replay_path, &metadata.replay_hash) {
+                println!("replay:       {} ({})", replay_path.display(), replay_hash);
+            }
+            if matches.is_present("configuration") {
+                println!("{}", metadata.configuration);
+            }
+        }
+        Err(error) => eprintln!("Failed to read run metadata: {}", error),
+    }
+
     let events = read_events(profiling_path).unwrap();
 
+    if matches.is_present("chrome-trace") {
+        let trace_path = current_profiling_dir.join(matches.value_of("chrome-trace").unwrap_or("trace.json"));
+        let file = std::io::BufWriter::new(File::create(&trace_path).unwrap());
+        chrome_trace::write(&events, file).unwrap();
+        println!("Wrote {}", trace_path.display());
+    }
+
+    if let Some(format) = matches.value_of("cluster-stats") {
+        let frames = cluster_stats::collect(&events);
+        match format.parse().unwrap() {
+            cluster_stats::Format::Csv => {
+                let metrics_path = current_profiling_dir.join("cluster_stats.csv");
+                let histograms_path = current_profiling_dir.join("cluster_histograms.csv");
+                cluster_stats::write_csv(
+                    &frames,
+                    std::io::BufWriter::new(File::create(&metrics_path).unwrap()),
+                    std::io::BufWriter::new(File::create(&histograms_path).unwrap()),
+                )
+                .unwrap();
+                println!("Wrote {} and {}", metrics_path.display(), histograms_path.display());
+            }
+            cluster_stats::Format::Json => {
+                let path = current_profiling_dir.join("cluster_stats.json");
+                cluster_stats::write_json(&frames, std::io::BufWriter::new(File::create(&path).unwrap())).unwrap();
+                println!("Wrote {}", path.display());
+            }
+        }
+    }
+
     let mut sample_names = Vec::new();
     let mut max_run_index = None;
     let mut max_frame_index = None;
     let mut max_cluster_buffer_count = None;
     let mut max_basic_buffer_count = None;
+    let mut histogram_bin_count = None;
 
     fn option_max_assign<T: Copy + std::cmp::Ord>(o: &mut Option<T>, v: T) {
         *o = match *o {
@@ -92,8 +146,9 @@ fn main() {
                 assert_eq!(index.to_usize(), sample_names.len());
                 sample_names.push(name.clone());
             }
-            MeasurementEvent::RecordClusterBuffer(ref _cluster_buffer) => {
+            MeasurementEvent::RecordClusterBuffer(ref cluster_buffer) => {
                 cluster_buffer_count += 1;
+                histogram_bin_count = Some(cluster_buffer.histogram_bin_count());
             }
             MeasurementEvent::RecordBasicBuffer(ref _basic_buffer) => {
                 basic_buffer_count += 1;
@@ -131,6 +186,7 @@ fn main() {
     let sample_count = sample_names.len();
     let cluster_buffer_count = max_cluster_buffer_count.unwrap_or(0);
     let basic_buffer_count = max_basic_buffer_count.unwrap_or(0);
+    let histogram_bin_count = histogram_bin_count.unwrap_or(0);
 
     for i in 0..sample_count {
         println!("[{:3}] {:18} {:30}", i, &sample_names[i], &sample_paths[i]);
@@ -147,9 +203,10 @@ fn main() {
     let run_stride = frame_count * frame_stride;
     let u64_count = run_count * run_stride;
     let mut flat_samples: Vec<u64> = std::iter::repeat(std::u64::MAX).take(u64_count).collect();
-    let mut flat_cluster_buffers: Vec<ClusterBuffer> = std::iter::repeat_with(Default::default)
-        .take(frame_count * cluster_buffer_count)
-        .collect();
+    let mut flat_cluster_buffers: Vec<ClusterBuffer> =
+        std::iter::repeat_with(|| ClusterBuffer::new(histogram_bin_count as u32))
+            .take(frame_count * cluster_buffer_count)
+            .collect();
     let mut flat_basic_buffers: Vec<BasicBuffer> = std::iter::repeat_with(Default::default)
         .take(frame_count * basic_buffer_count)
         .collect();
@@ -210,6 +267,7 @@ fn main() {
         sample_count: u64,
         cluster_buffer_count: u64,
         basic_buffer_count: u64,
+        histogram_bin_count: u64,
     };
 
     file.write_all(
@@ -219,6 +277,7 @@ fn main() {
             sample_count: sample_count as u64,
             cluster_buffer_count: cluster_buffer_count as u64,
             basic_buffer_count: basic_buffer_count as u64,
+            histogram_bin_count: histogram_bin_count as u64,
         }
         .value_as_bytes(),
     )
@@ -236,6 +295,8 @@ fn main() {
     }
 
     file.write_all(flat_samples.vec_as_bytes()).unwrap();
-    file.write_all(flat_cluster_buffers.vec_as_bytes()).unwrap();
+    for cluster_buffer in flat_cluster_buffers.iter() {
+        file.write_all(cluster_buffer.as_slice().slice_as_bytes()).unwrap();
+    }
     file.write_all(flat_basic_buffers.vec_as_bytes()).unwrap();
 }
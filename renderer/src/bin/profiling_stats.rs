@@ -0,0 +1,142 @@
+use clap::{App, Arg};
+use std::path::{Path, PathBuf};
+
+use renderer::profiling::stats::*;
+use renderer::profiling::*;
+
+fn read_stats(profiling_dir: &Path) -> Vec<SampleStats> {
+    let events_path = profiling_dir.join("events.bin");
+    let events =
+        read_events(&events_path).unwrap_or_else(|error| panic!("Failed to read {}: {}", events_path.display(), error));
+    sample_stats(&events)
+}
+
+fn print_stats(stats: &[SampleStats]) {
+    println!(
+        "{:40} {:>6} | {:>8} {:>8} {:>8} {:>8} {:>8} | {:>6} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "path", "count", "cpu avg", "med", "p95", "p99", "std", "count", "gpu avg", "med", "p95", "p99", "std"
+    );
+    for s in stats.iter() {
+        println!(
+            "{:40} {:>6} | {:8.3} {:8.3} {:8.3} {:8.3} {:8.3} | {:>6} {:8.3} {:8.3} {:8.3} {:8.3} {:8.3}",
+            s.path,
+            s.cpu.count,
+            s.cpu.mean,
+            s.cpu.median,
+            s.cpu.p95,
+            s.cpu.p99,
+            s.cpu.stddev,
+            s.gpu.count,
+            s.gpu.mean,
+            s.gpu.median,
+            s.gpu.p95,
+            s.gpu.p99,
+            s.gpu.stddev,
+        );
+    }
+}
+
+fn main() {
+    let matches = App::new("Profiling Stats")
+        .version("1.0")
+        .author("Mick van Gelderen")
+        .about("Summarizes profiling runs in milliseconds and detects regressions between them.")
+        .arg(
+            Arg::with_name("dir")
+                .help("Profiling directory, defaults to profiling/latest")
+                .required(false)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .value_name("BASE_DIR")
+                .help("Compares against a baseline profiling directory and exits with 1 on regressions")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("threshold")
+                .long("threshold")
+                .value_name("PERCENT")
+                .help("Median increase that counts as a regression")
+                .default_value("5")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("alpha")
+                .long("alpha")
+                .value_name("P")
+                .help("Significance level of the Mann-Whitney U test an increase has to pass")
+                .default_value("0.01")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("min-median")
+                .long("min-median")
+                .value_name("MS")
+                .help("Ignores sample paths with a smaller baseline median")
+                .default_value("0.05")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("timer")
+                .long("timer")
+                .possible_values(&["cpu", "gpu"])
+                .default_value("gpu")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let profiling_dir = match matches.value_of("dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let latest_profiling_dir = std::env::current_dir().unwrap().join("profiling").join("latest");
+            std::fs::read_link(latest_profiling_dir).expect("Didn't find symlink to latest profiling directory.")
+        }
+    };
+
+    let stats = read_stats(&profiling_dir);
+    println!("{}", profiling_dir.display());
+    print_stats(&stats);
+
+    if let Some(base_dir) = matches.value_of("compare") {
+        let base_dir = PathBuf::from(base_dir);
+        let base_stats = read_stats(&base_dir);
+        let threshold: f64 = matches.value_of("threshold").unwrap().parse::<f64>().unwrap() / 100.0;
+        let alpha: f64 = matches.value_of("alpha").unwrap().parse().unwrap();
+        let min_median: f64 = matches.value_of("min-median").unwrap().parse().unwrap();
+        let timer: Timer = matches.value_of("timer").unwrap().parse().unwrap();
+
+        println!();
+        println!("Comparing against {} ({:?} medians)", base_dir.display(), timer);
+
+        let comparisons = compare(&base_stats, &stats, timer, min_median);
+        let mut regression_count = 0;
+        for c in comparisons.iter() {
+            let regression = c.is_regression(threshold, alpha);
+            if regression {
+                regression_count += 1;
+            }
+            println!(
+                "{:40} {:8.3} -> {:8.3} {:+7.1}% p={:.4}{}",
+                c.path,
+                c.base,
+                c.new,
+                c.change() * 100.0,
+                c.p,
+                if regression { " REGRESSION" } else { "" }
+            );
+        }
+
+        if regression_count > 0 {
+            eprintln!(
+                "{} of {} sample paths regressed by more than {}% with p < {}.",
+                regression_count,
+                comparisons.len(),
+                threshold * 100.0,
+                alpha
+            );
+            std::process::exit(1);
+        }
+    }
+}
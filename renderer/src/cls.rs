@@ -3,6 +3,7 @@ pub mod cluster_camera_resources;
 pub mod cluster_renderer;
 pub mod cluster_resources;
 pub mod cluster_space_buffer;
+pub mod cpu_reference;
 
 pub use cluster_camera_resources::*;
 pub use cluster_resources::*;
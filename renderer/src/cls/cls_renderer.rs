@@ -35,6 +35,7 @@ glsl_defines!(fixed_header {
         LIGHT_INDICES_BUFFER_BINDING = 11;
         CLUSTER_SPACE_BUFFER_BINDING = 12;
         PROFILING_CLUSTER_BUFFER_BINDING = 13;
+        LIGHT_DIR_BUFFER_BINDING = 14;
 
         // BASIC_ATOMIC_BINDING = 0;
 
@@ -47,9 +48,10 @@ glsl_defines!(fixed_header {
     uniforms: {
         DEPTH_SAMPLER_LOC = 0;
         VIEWPORT_LOC = 1;
+        // One location per view.
         REN_CLP_TO_CLU_CAM_LOC = 2;
-        ITEM_COUNT_LOC = 3;
-        LGT_TO_CLU_CAM_LOC = 4;
+        ITEM_COUNT_LOC = 4;
+        LGT_TO_CLU_CAM_LOC = 5;
     },
 });
 
@@ -117,7 +119,11 @@ impl Context<'_> {
         // Reborrow
         let gl = &self.gl;
         let cluster_resources = &mut self.cluster_resources_pool[cluster_resources_index];
-        cluster_resources.recompute(&self.main_resources_pool);
+        cluster_resources.recompute(&self.main_resources_pool, &self.configuration.foveation);
+
+        if cluster_resources.parameters.configuration.adaptive_buffers {
+            cluster_resources.grow_buffers(gl, self.profiling_context.cluster_buffers());
+        }
 
         let cluster_profiler_index = self.profiling_context.start(gl, cluster_resources.profilers.cluster);
 
@@ -148,9 +154,9 @@ impl Context<'_> {
 
             let camera_profiler_index = self.profiling_context.start(gl, camera_resources.profilers.camera);
 
-            let ren_clp_to_clu_cam = (cluster_resources.computed.wld_to_clu_cam * main_resources.camera.clp_to_wld)
-                .cast::<f32>()
-                .unwrap();
+            let wld_to_clu_cam = cluster_resources.computed.wld_to_clu_cam;
+            let [ren_clp_to_wld_0, ren_clp_to_wld_1] = main_resources.ren_clp_to_wld();
+            let ren_clp_to_clu_cam = [wld_to_clu_cam * ren_clp_to_wld_0, wld_to_clu_cam * ren_clp_to_wld_1];
 
             assert_eq!(true, main_resources.depth_available);
 
@@ -188,10 +194,10 @@ impl Context<'_> {
                                 let dimensions = main_resources.framebuffer.dimensions.cast::<f32>().unwrap();
                                 gl.uniform_4f(cls_renderer::VIEWPORT_LOC, [0.0, 0.0, dimensions.x, dimensions.y]);
 
-                                gl.uniform_matrix4f(
+                                rendering::uniform_view_matrices(
+                                    gl,
                                     cls_renderer::REN_CLP_TO_CLU_CAM_LOC,
-                                    gl::MajorAxis::Column,
-                                    ren_clp_to_clu_cam.as_ref(),
+                                    &ren_clp_to_clu_cam,
                                 );
 
                                 gl.bind_texture_unit(0, main_resources.framebuffer.depth_texture_name);
@@ -242,9 +248,7 @@ impl Context<'_> {
                                 gl.bind_buffer(gl::DRAW_INDIRECT_BUFFER, draw_resources.draw_command_buffer);
 
                                 gl.bind_vertex_array(self.resources.scene_vao);
-
-                                let draw_counts = &draw_resources.draw_counts;
-                                let draw_offsets = &draw_resources.draw_offsets;
+                                main_resources.enable_view_clipping(gl);
 
                                 for &(program, material_kind, profiler) in [
                                     (
@@ -276,10 +280,10 @@ impl Context<'_> {
                                     let dimensions = main_resources.framebuffer.dimensions.cast::<f32>().unwrap();
                                     gl.uniform_4f(cls_renderer::VIEWPORT_LOC, [0.0, 0.0, dimensions.x, dimensions.y]);
 
-                                    gl.uniform_matrix4f(
+                                    rendering::uniform_view_matrices(
+                                        gl,
                                         cls_renderer::REN_CLP_TO_CLU_CAM_LOC,
-                                        gl::MajorAxis::Column,
-                                        ren_clp_to_clu_cam.as_ref(),
+                                        &ren_clp_to_clu_cam,
                                     );
 
                                     for (material_index, material) in self
@@ -298,13 +302,7 @@ impl Context<'_> {
                                         }
 
                                         // Execute draw.
-                                        gl.multi_draw_elements_indirect(
-                                            gl::TRIANGLES,
-                                            gl::UNSIGNED_INT,
-                                            draw_offsets[material_index] as usize * std::mem::size_of::<DrawCommand>(),
-                                            draw_counts[material_index] as i32,
-                                            std::mem::size_of::<DrawCommand>() as i32,
-                                        );
+                                        draw_resources.multi_draw(gl, material_index);
                                     }
 
                                     gl.depth_func(gl::GREATER);
@@ -321,6 +319,7 @@ impl Context<'_> {
 
                                 gl.unuse_program();
 
+                                main_resources.disable_view_clipping(gl);
                                 gl.unbind_vertex_array();
                             }
                         },
@@ -350,9 +349,7 @@ impl Context<'_> {
                         gl.bind_buffer(gl::DRAW_INDIRECT_BUFFER, draw_resources.draw_command_buffer);
 
                         gl.bind_vertex_array(self.resources.scene_vao);
-
-                        let draw_counts = &draw_resources.draw_counts;
-                        let draw_offsets = &draw_resources.draw_offsets;
+                        main_resources.enable_view_clipping(gl);
 
                         for &(program, material_kind, profiler) in [(
                             transparent_program,
@@ -377,10 +374,10 @@ impl Context<'_> {
                             let dimensions = main_resources.framebuffer.dimensions.cast::<f32>().unwrap();
                             gl.uniform_4f(cls_renderer::VIEWPORT_LOC, [0.0, 0.0, dimensions.x, dimensions.y]);
 
-                            gl.uniform_matrix4f(
+                            rendering::uniform_view_matrices(
+                                gl,
                                 cls_renderer::REN_CLP_TO_CLU_CAM_LOC,
-                                gl::MajorAxis::Column,
-                                ren_clp_to_clu_cam.as_ref(),
+                                &ren_clp_to_clu_cam,
                             );
 
                             for (material_index, material) in self
@@ -397,13 +394,7 @@ impl Context<'_> {
                                 );
 
                                 // Execute draw.
-                                gl.multi_draw_elements_indirect(
-                                    gl::TRIANGLES,
-                                    gl::UNSIGNED_INT,
-                                    draw_offsets[material_index] as usize * std::mem::size_of::<DrawCommand>(),
-                                    draw_counts[material_index] as i32,
-                                    std::mem::size_of::<DrawCommand>() as i32,
-                                );
+                                draw_resources.multi_draw(gl, material_index);
                             }
 
                             gl.depth_func(gl::GREATER);
@@ -420,6 +411,7 @@ impl Context<'_> {
                             self.profiling_context.stop(gl, profiler_index);
                         }
 
+                        main_resources.disable_view_clipping(gl);
                         gl.unbind_vertex_array();
                     }
                 }
@@ -436,10 +428,12 @@ impl Context<'_> {
 
         // We have our fragments per cluster buffer here.
         // TODO: Don't do this when running the application??
+        // NOTE: Overflow counters are only reported outside of time sensitive
+        // runs.
         if !self.profiling_context.time_sensitive() {
             unsafe {
                 let buffer = &mut cluster_resources.profiling_cluster_buffer;
-                let byte_count = std::mem::size_of::<profiling::ClusterBuffer>();
+                let byte_count = self.profiling_context.cluster_buffer_byte_count();
                 buffer.invalidate(gl);
                 buffer.ensure_capacity(gl, byte_count);
                 buffer.clear_0u32(gl, byte_count);
@@ -585,6 +579,16 @@ impl Context<'_> {
                 buffer.name()
             });
 
+            gl.bind_buffer_base(gl::SHADER_STORAGE_BUFFER, cls_renderer::LIGHT_DIR_BUFFER_BINDING, {
+                let buffer = &mut cluster_resources.light_dir_buffer_ring[self.frame_index.to_usize()];
+                buffer.reconcile(
+                    gl,
+                    std::mem::size_of::<[f32; 4]>() * self.light_resources.header.light_count as usize,
+                );
+
+                buffer.name()
+            });
+
             let program = &mut self.cls_renderer.transform_lights_program;
             program.update(&mut rendering_context!(self));
             if let ProgramName::Linked(name) = program.name {
@@ -87,6 +87,7 @@ pub struct ClusterResources {
     pub active_cluster_light_offsets_buffer: DynamicBuffer,
 
     pub light_xyzr_buffer_ring: Ring3<StorageBuffer<StorageBufferKindWO>>,
+    pub light_dir_buffer_ring: Ring3<StorageBuffer<StorageBufferKindWO>>,
     pub light_indices_buffer: DynamicBuffer,
 
     pub offset_buffer: DynamicBuffer,
@@ -143,6 +144,7 @@ impl ClusterResources {
                 buffer
             },
             light_xyzr_buffer_ring: Ring3::new(|| unsafe { StorageBuffer::new(gl) }),
+            light_dir_buffer_ring: Ring3::new(|| unsafe { StorageBuffer::new(gl) }),
             light_indices_buffer: unsafe {
                 let mut buffer = Buffer::new(gl);
                 gl.buffer_label(&buffer, "light_indices");
@@ -195,7 +197,7 @@ impl ClusterResources {
             profiling_cluster_buffer: unsafe {
                 let mut buffer = Buffer::new(gl);
                 gl.buffer_label(&buffer, "profiling_cluster_buffer");
-                buffer.ensure_capacity(gl, std::mem::size_of::<profiling::ClusterBuffer>());
+                buffer.ensure_capacity(gl, profiling_context.cluster_buffer_byte_count());
                 buffer
             },
             profilers: ClusterStages::new(|stage| profiling_context.add_sample(stage.title())),
@@ -206,7 +208,44 @@ impl ClusterResources {
         }
     }
 
-    pub fn recompute(&mut self, main_resources_pool: &Pool<MainResources>) {
+    pub fn active_cluster_capacity(&self) -> u32 {
+        (self.active_cluster_cluster_indices_buffer.byte_capacity() / std::mem::size_of::<u32>()) as u32
+    }
+
+    pub fn light_index_capacity(&self) -> u32 {
+        (self.light_indices_buffer.byte_capacity() / std::mem::size_of::<u32>()) as u32
+    }
+
+    /// Grows the active cluster and light index buffers so that the counts
+    /// reported by the GPU in earlier frames fit.
+    pub fn grow_buffers(&mut self, gl: &gl::Gl, cluster_buffers: &[profiling::ClusterBuffer]) {
+        let active_cluster_count = cluster_buffers.iter().map(|b| b.active_cluster_count()).max().unwrap_or(0);
+        let light_indices_count = cluster_buffers.iter().map(|b| b.light_indices_count()).max().unwrap_or(0);
+
+        if active_cluster_count > self.active_cluster_capacity() {
+            let byte_count = std::mem::size_of::<u32>() * active_cluster_count as usize;
+            info!("Growing active cluster buffers to {} clusters.", active_cluster_count);
+            unsafe {
+                self.active_cluster_cluster_indices_buffer.ensure_capacity(gl, byte_count);
+                self.active_cluster_light_counts_buffer.ensure_capacity(gl, byte_count);
+                self.active_cluster_light_offsets_buffer.ensure_capacity(gl, byte_count);
+            }
+        }
+
+        if light_indices_count > self.light_index_capacity() {
+            let byte_count = std::mem::size_of::<u32>() * light_indices_count as usize;
+            info!("Growing light index buffer to {} indices.", light_indices_count);
+            unsafe {
+                self.light_indices_buffer.ensure_capacity(gl, byte_count);
+            }
+        }
+    }
+
+    pub fn recompute(
+        &mut self,
+        main_resources_pool: &Pool<MainResources>,
+        foveation: &configuration::FoveationConfiguration,
+    ) {
         let parameters = &self.parameters;
         let cfg = &parameters.configuration;
 
@@ -238,7 +277,7 @@ impl ClusterResources {
                     clp_to_cam: ClusterSpaceCoefficients::inverse_orthographic(&frustum, dimensions),
                 }
             }
-            ClusteringProjection::Perspective => {
+            ClusteringProjection::Perspective | ClusteringProjection::Logarithmic => {
                 let cameras = self.camera_resources_pool.used_slice();
 
                 match cameras.len() {
@@ -253,7 +292,7 @@ impl ClusterResources {
 
                         let clu_cam_to_clu_ori = Matrix4::from_translation(origin - Point3::origin());
                         let clu_ori_to_clu_cam = Matrix4::from_translation(Point3::origin() - origin);
-                        let frame_dimensions = main_resources.framebuffer.dimensions.cast::<f64>().unwrap();
+                        let frame_dimensions = foveation_renderer::shaded_dimensions(foveation, main_resources);
 
                         let dimensions = {
                             let dimensions =
@@ -514,7 +553,7 @@ impl ClusterResources {
                                 let dimensions = compute_perspective_dimensions(
                                     cfg,
                                     &main_resources.camera.frustum,
-                                    main_resources.framebuffer.dimensions.cast::<f64>().unwrap(),
+                                    foveation_renderer::shaded_dimensions(foveation, main_resources),
                                 );
                                 let volume = compute_perspective_volume(main_resources.camera.frustum);
                                 volume / dimensions.product()
@@ -546,6 +585,26 @@ impl ClusterResources {
             assert!(computed.dimensions[i] <= 1024);
         }
 
+        if cfg.projection == ClusteringProjection::Logarithmic {
+            // Same tangent planes as the perspective projection but with a
+            // configurable number of depth slices.
+            computed.dimensions.z = std::cmp::max(2, cfg.logarithmic_slices);
+            assert!(computed.dimensions.z <= 1024);
+            assert!(
+                computed.cluster_count() <= cfg.max_clusters,
+                "{} logarithmic slices need {} clusters but max_clusters is {}.",
+                computed.dimensions.z,
+                computed.cluster_count(),
+                cfg.max_clusters
+            );
+
+            let frustum = computed.frustum;
+            let near_split = ClusterSpaceCoefficients::logarithmic_near_split(&frustum, cfg.logarithmic_near_split);
+            let dimensions = computed.dimensions.cast::<f64>().unwrap();
+            computed.cam_to_clp = ClusterSpaceCoefficients::logarithmic(&frustum, dimensions, near_split);
+            computed.clp_to_cam = ClusterSpaceCoefficients::inverse_logarithmic(&frustum, dimensions, near_split);
+        }
+
         self.computed = computed;
     }
 
@@ -1,24 +1,28 @@
 use crate::*;
+use renderer::configuration::ClusteringProjection;
 
 #[repr(C)]
 pub struct ClusterSpaceBuffer {
-    dimensions: Vector3<u32>,
-    cluster_count: u32,
-    frustum: Frustum<f32>,
+    pub dimensions: Vector3<u32>,
+    pub cluster_count: u32,
+    pub frustum: Frustum<f32>,
     _pad0: [f32; 2],
-    cam_to_clp: ClusterSpaceCoefficients,
-    clp_to_cam: ClusterSpaceCoefficients,
+    pub cam_to_clp: ClusterSpaceCoefficients,
+    pub clp_to_cam: ClusterSpaceCoefficients,
+    pub max_active_clusters: u32,
+    pub max_light_indices: u32,
+    _pad1: [u32; 2],
 }
 
 #[derive(Debug, Copy, Clone, Default)]
 #[repr(C)]
 pub struct ClusterSpaceCoefficients {
-    ax: f32,
-    bx: f32,
-    ay: f32,
-    by: f32,
-    az: f32,
-    bz: f32,
+    pub ax: f32,
+    pub bx: f32,
+    pub ay: f32,
+    pub by: f32,
+    pub az: f32,
+    pub bz: f32,
     _pad: [f32; 2],
 }
 
@@ -117,20 +121,163 @@ impl ClusterSpaceCoefficients {
             _pad: Default::default(),
         }
     }
+
+    /// Clamps `near_split` to lie strictly between the near and the far plane.
+    /// Otherwise either the linear slice or ln(f / s) is empty and the z
+    /// coefficients are not finite.
+    #[inline]
+    pub fn logarithmic_near_split(frustum: &Frustum<f64>, near_split: f64) -> f64 {
+        near_split
+            .max(-frustum.z1 * (1.0 + 1.0 / 1024.0))
+            .min(-frustum.z0 * (1.0 - 1.0 / 1024.0))
+    }
+
+    /// The nearest slice is linear from the near plane up to `near_split`, the
+    /// remaining slices are spaced geometrically from `near_split` to the far
+    /// plane: z_clp = (Z - 1) - ln(z_cam / -s) * (Z - 1) / ln(f / s).
+    #[inline]
+    pub fn logarithmic(frustum: &Frustum<f64>, dimensions: Vector3<f64>, near_split: f64) -> Self {
+        let (ax, bx) = lic(frustum.x0, frustum.x1, 0.0, dimensions.x);
+        let (ay, by) = lic(frustum.y0, frustum.y1, 0.0, dimensions.y);
+        let (az, bz) = ((-1.0 / near_split), (dimensions.z - 1.0) / (-frustum.z0 / near_split).ln());
+
+        Self {
+            ax: ax as f32,
+            bx: bx as f32,
+            ay: ay as f32,
+            by: by as f32,
+            az: az as f32,
+            bz: bz as f32,
+            _pad: Default::default(),
+        }
+    }
+
+    /// z_cam = -s * exp(((Z - 1) - z_clp) * ln(f / s) / (Z - 1))
+    #[inline]
+    pub fn inverse_logarithmic(frustum: &Frustum<f64>, dimensions: Vector3<f64>, near_split: f64) -> Self {
+        let (ax, bx) = lic(0.0, dimensions.x, frustum.x0, frustum.x1);
+        let (ay, by) = lic(0.0, dimensions.y, frustum.y0, frustum.y1);
+        let (az, bz) = (-near_split, (-frustum.z0 / near_split).ln() / (dimensions.z - 1.0));
+
+        Self {
+            ax: ax as f32,
+            bx: bx as f32,
+            ay: ay as f32,
+            by: by as f32,
+            az: az as f32,
+            bz: bz as f32,
+            _pad: Default::default(),
+        }
+    }
 }
 
 impl ClusterSpaceBuffer {
-    pub fn from(
-        resources: &ClusterResources
+    pub fn new(
+        frustum: Frustum<f64>,
+        dimensions: Vector3<u32>,
+        cam_to_clp: ClusterSpaceCoefficients,
+        clp_to_cam: ClusterSpaceCoefficients,
     ) -> Self {
-        let dimensions = resources.computed.dimensions;
         Self {
             dimensions,
             cluster_count: dimensions.product(),
-            frustum: resources.computed.frustum.cast().unwrap(),
+            frustum: frustum.cast().unwrap(),
             _pad0: Default::default(),
-            cam_to_clp: resources.computed.cam_to_clp,
-            clp_to_cam: resources.computed.clp_to_cam,
+            cam_to_clp,
+            clp_to_cam,
+            // Unbounded unless constructed from the actual buffers.
+            max_active_clusters: std::u32::MAX,
+            max_light_indices: std::u32::MAX,
+            _pad1: Default::default(),
+        }
+    }
+
+    pub fn from(
+        resources: &ClusterResources
+    ) -> Self {
+        Self {
+            max_active_clusters: resources.active_cluster_capacity(),
+            max_light_indices: resources.light_index_capacity(),
+            ..Self::new(
+                resources.computed.frustum,
+                resources.computed.dimensions,
+                resources.computed.cam_to_clp,
+                resources.computed.clp_to_cam,
+            )
+        }
+    }
+}
+
+impl ClusterSpaceBuffer {
+    /// Mirrors `cluster_cam_to_clp` in `cluster_space_buffer.glsl`.
+    pub fn cluster_cam_to_clp(&self, projection: ClusteringProjection, pos_in_cam: Point3<f32>) -> Point3<f32> {
+        let ClusterSpaceCoefficients { ax, bx, ay, by, az, bz, .. } = self.cam_to_clp;
+        match projection {
+            ClusteringProjection::Perspective => {
+                let frac_1_neg_z_cam = -1.0 / pos_in_cam.z;
+                let z = pos_in_cam.z * az;
+                Point3::new(
+                    frac_1_neg_z_cam * (ax * pos_in_cam.x) + bx,
+                    frac_1_neg_z_cam * (ay * pos_in_cam.y) + by,
+                    self.dimensions.z as f32 - if z < 0.0 { std::f32::NEG_INFINITY } else { z.ln() * bz },
+                )
+            }
+            ClusteringProjection::Logarithmic => {
+                let frac_1_neg_z_cam = -1.0 / pos_in_cam.z;
+                let z = pos_in_cam.z * az;
+                let z_max = self.dimensions.z as f32;
+                Point3::new(
+                    frac_1_neg_z_cam * (ax * pos_in_cam.x) + bx,
+                    frac_1_neg_z_cam * (ay * pos_in_cam.y) + by,
+                    if z < 1.0 {
+                        let near = -self.frustum.z1;
+                        let split = -1.0 / az;
+                        z_max - (-pos_in_cam.z - near) / (split - near)
+                    } else {
+                        (z_max - 1.0) - z.ln() * bz
+                    },
+                )
+            }
+            ClusteringProjection::Orthographic => Point3::new(
+                ax * pos_in_cam.x + bx,
+                ay * pos_in_cam.y + by,
+                az * pos_in_cam.z + bz,
+            ),
+        }
+    }
+
+    /// Mirrors `cluster_clp_to_cam` in `cluster_space_buffer.glsl`.
+    pub fn cluster_clp_to_cam(&self, projection: ClusteringProjection, pos_in_clp: Point3<f32>) -> Point3<f32> {
+        let ClusterSpaceCoefficients { ax, bx, ay, by, az, bz, .. } = self.clp_to_cam;
+        match projection {
+            ClusteringProjection::Perspective => {
+                let z_cam = az * bz.powf(self.dimensions.z as f32 - pos_in_clp.z);
+                Point3::new(
+                    -z_cam * (ax * pos_in_clp.x + bx),
+                    -z_cam * (ay * pos_in_clp.y + by),
+                    z_cam,
+                )
+            }
+            ClusteringProjection::Logarithmic => {
+                let z_max = self.dimensions.z as f32;
+                let z_cam = if pos_in_clp.z > z_max - 1.0 {
+                    let near = -self.frustum.z1;
+                    let split = -az;
+                    -(near + (z_max - pos_in_clp.z) * (split - near))
+                } else {
+                    az * ((z_max - 1.0 - pos_in_clp.z) * bz).exp()
+                };
+                Point3::new(
+                    -z_cam * (ax * pos_in_clp.x + bx),
+                    -z_cam * (ay * pos_in_clp.y + by),
+                    z_cam,
+                )
+            }
+            ClusteringProjection::Orthographic => Point3::new(
+                ax * pos_in_clp.x + bx,
+                ay * pos_in_clp.y + by,
+                az * pos_in_clp.z + bz,
+            ),
         }
     }
 }
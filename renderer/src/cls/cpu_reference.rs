@@ -0,0 +1,750 @@
+//! CPU implementation of the clustered light shading compute passes in
+//! `resources/cls/`. Produces the same buffers as the GPU so the two can be
+//! compared.
+
+use crate::*;
+use cluster_space_buffer::ClusterSpaceBuffer;
+use renderer::configuration::ClusteringProjection;
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ClusteringOutput {
+    pub cluster_fragment_counts: Vec<u32>,
+    pub cluster_maybe_active_cluster_indices: Vec<u32>,
+    pub active_cluster_cluster_indices: Vec<u32>,
+    pub active_cluster_light_counts: Vec<u32>,
+    pub active_cluster_light_offsets: Vec<u32>,
+    pub light_indices: Vec<u32>,
+    pub active_cluster_overflow: u32,
+    pub light_indices_overflow: u32,
+}
+
+pub struct ClusteringInput<'a> {
+    pub projection: ClusteringProjection,
+    pub space: &'a ClusterSpaceBuffer,
+    /// Render clip space depth values, row by row. A value of 0.0 is treated as
+    /// the cleared (infinitely far) depth.
+    pub depth: &'a [f32],
+    pub depth_dimensions: Vector2<u32>,
+    pub ren_clp_to_clu_cam: Matrix4<f32>,
+    pub lights: &'a [light::LightBufferLight],
+    pub lgt_to_clu_cam: Matrix4<f32>,
+}
+
+pub fn compute_clustering(input: &ClusteringInput) -> ClusteringOutput {
+    let cluster_fragment_counts = count_fragments(
+        input.projection,
+        input.space,
+        input.depth,
+        input.depth_dimensions,
+        input.ren_clp_to_clu_cam,
+    );
+    let (cluster_maybe_active_cluster_indices, active_cluster_cluster_indices) =
+        compact_clusters(&cluster_fragment_counts, input.space.max_active_clusters);
+    let (light_xyzr, light_dir) = transform_lights(input.lights, input.lgt_to_clu_cam);
+    let active_cluster_light_counts = count_lights(
+        input.projection,
+        input.space,
+        &active_cluster_cluster_indices,
+        &light_xyzr,
+        &light_dir,
+    );
+    let active_cluster_light_offsets = compact_light_counts(&active_cluster_light_counts);
+    let light_indices = assign_lights(
+        input.projection,
+        input.space,
+        &active_cluster_cluster_indices,
+        &active_cluster_light_offsets,
+        &light_xyzr,
+        &light_dir,
+    );
+
+    let active_cluster_overflow = cluster_fragment_counts.iter().filter(|&&count| count > 0).count() as u32
+        - active_cluster_cluster_indices.len() as u32;
+    let light_indices_count = active_cluster_light_counts.iter().sum::<u32>();
+    let light_indices_overflow = light_indices_count - std::cmp::min(light_indices_count, input.space.max_light_indices);
+
+    ClusteringOutput {
+        cluster_fragment_counts,
+        cluster_maybe_active_cluster_indices,
+        active_cluster_cluster_indices,
+        active_cluster_light_counts,
+        active_cluster_light_offsets,
+        light_indices,
+        active_cluster_overflow,
+        light_indices_overflow,
+    }
+}
+
+fn index_3_to_1(idx: Vector3<u32>, dimensions: Vector3<u32>) -> u32 {
+    ((idx.z * dimensions.y) + idx.y) * dimensions.x + idx.x
+}
+
+fn index_1_to_3(index: u32, dimensions: Vector3<u32>) -> Vector3<u32> {
+    Vector3::new(
+        index % dimensions.x,
+        (index / dimensions.x) % dimensions.y,
+        index / dimensions.x / dimensions.y,
+    )
+}
+
+fn lerp_u32_f32(x: u32, x0: u32, x1: u32, y0: f32, y1: f32) -> f32 {
+    (y1 * (x - x0) as f32 + y0 * (x1 - x) as f32) / (x1 - x0) as f32
+}
+
+/// Mirrors `count_fragments_depth.comp` for a single sampled depth buffer.
+pub fn count_fragments(
+    projection: ClusteringProjection,
+    space: &ClusterSpaceBuffer,
+    depth: &[f32],
+    depth_dimensions: Vector2<u32>,
+    ren_clp_to_clu_cam: Matrix4<f32>,
+) -> Vec<u32> {
+    assert_eq!(depth.len(), depth_dimensions.product() as usize);
+
+    let mut counts = vec![0; space.cluster_count as usize];
+    let wh = depth_dimensions.cast::<f32>().unwrap();
+
+    for y in 0..depth_dimensions.y {
+        for x in 0..depth_dimensions.x {
+            let z_in_ren_clp = depth[(y * depth_dimensions.x + x) as usize];
+            if z_in_ren_clp == 0.0 {
+                continue;
+            }
+
+            let pos_in_ren_clp = Point3::new(
+                (2.0 * x as f32 + 1.0 - wh.x) / wh.x,
+                (2.0 * y as f32 + 1.0 - wh.y) / wh.y,
+                z_in_ren_clp,
+            );
+            let pos_in_clu_cam = ren_clp_to_clu_cam.transform_point(pos_in_ren_clp);
+            let pos_in_cls = space.cluster_cam_to_clp(projection, pos_in_clu_cam);
+
+            let dimensions = space.dimensions.cast::<f32>().unwrap();
+            if pos_in_cls.x >= 0.0
+                && pos_in_cls.y >= 0.0
+                && pos_in_cls.z >= 0.0
+                && pos_in_cls.x < dimensions.x
+                && pos_in_cls.y < dimensions.y
+                && pos_in_cls.z < dimensions.z
+            {
+                let idx_in_cls = Vector3::new(pos_in_cls.x as u32, pos_in_cls.y as u32, pos_in_cls.z as u32);
+                counts[index_3_to_1(idx_in_cls, space.dimensions) as usize] += 1;
+            }
+        }
+    }
+
+    counts
+}
+
+/// Mirrors the three `compact_clusters` passes. Returns the cluster to active
+/// cluster index + 1 (or 0) mapping and the active cluster to cluster mapping.
+/// Clusters beyond `max_active_clusters` are treated as inactive.
+pub fn compact_clusters(cluster_fragment_counts: &[u32], max_active_clusters: u32) -> (Vec<u32>, Vec<u32>) {
+    let mut cluster_maybe_active_cluster_indices = Vec::with_capacity(cluster_fragment_counts.len());
+    let mut active_cluster_cluster_indices = Vec::new();
+
+    for (cluster_index, &fragment_count) in cluster_fragment_counts.iter().enumerate() {
+        if fragment_count > 0 && (active_cluster_cluster_indices.len() as u32) < max_active_clusters {
+            active_cluster_cluster_indices.push(cluster_index as u32);
+            cluster_maybe_active_cluster_indices.push(active_cluster_cluster_indices.len() as u32);
+        } else {
+            cluster_maybe_active_cluster_indices.push(0);
+        }
+    }
+
+    (cluster_maybe_active_cluster_indices, active_cluster_cluster_indices)
+}
+
+/// Mirrors `transform_lights.comp`. Returns the light bounding spheres and the
+/// light directions and outer cosines.
+pub fn transform_lights(
+    lights: &[light::LightBufferLight],
+    lgt_to_clu_cam: Matrix4<f32>,
+) -> (Vec<[f32; 4]>, Vec<[f32; 4]>) {
+    lights
+        .iter()
+        .map(|light| {
+            let pos_in_clu_cam = lgt_to_clu_cam.transform_point(Point3::from(light.position));
+            let dir_in_clu_cam = if light.cos_outer > -1.0 {
+                lgt_to_clu_cam.transform_vector(Vector3::from(light.direction)).normalize()
+            } else {
+                Vector3::zero()
+            };
+            let extent = (Vector3::from(light.axis_0).magnitude2() + Vector3::from(light.axis_1).magnitude2()).sqrt();
+            (
+                [pos_in_clu_cam.x, pos_in_clu_cam.y, pos_in_clu_cam.z, light.attenuation.r1 + extent],
+                [dir_in_clu_cam.x, dir_in_clu_cam.y, dir_in_clu_cam.z, light.cos_outer],
+            )
+        })
+        .unzip()
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum ClusterBounds {
+    Perspective {
+        nx: Vector2<f32>,
+        px: Vector2<f32>,
+        ny: Vector2<f32>,
+        py: Vector2<f32>,
+        z0: f32,
+        z1: f32,
+        b0: Point3<f32>,
+        b1: Point3<f32>,
+    },
+    Orthographic {
+        p0: Point3<f32>,
+        p1: Point3<f32>,
+    },
+}
+
+impl ClusterBounds {
+    pub fn new(projection: ClusteringProjection, space: &ClusterSpaceBuffer, idx_in_cls: Vector3<u32>) -> Self {
+        let dimensions = space.dimensions;
+        match projection {
+            ClusteringProjection::Perspective | ClusteringProjection::Logarithmic => {
+                let frustum = &space.frustum;
+                let x0 = lerp_u32_f32(idx_in_cls.x, 0, dimensions.x, frustum.x0, frustum.x1);
+                let x1 = lerp_u32_f32(idx_in_cls.x + 1, 0, dimensions.x, frustum.x0, frustum.x1);
+                let y0 = lerp_u32_f32(idx_in_cls.y, 0, dimensions.y, frustum.y0, frustum.y1);
+                let y1 = lerp_u32_f32(idx_in_cls.y + 1, 0, dimensions.y, frustum.y0, frustum.y1);
+
+                // Depths. z0 < z1 < 0
+                let (z0, z1) = if projection == ClusteringProjection::Perspective {
+                    let add_d_1 = space.clp_to_cam.bz;
+                    (
+                        frustum.z1 * add_d_1.powf(dimensions.z as f32 - idx_in_cls.z as f32),
+                        frustum.z1 * add_d_1.powf(dimensions.z as f32 - (idx_in_cls.z + 1) as f32),
+                    )
+                } else {
+                    (
+                        space.cluster_clp_to_cam(projection, Point3::new(0.0, 0.0, idx_in_cls.z as f32)).z,
+                        space.cluster_clp_to_cam(projection, Point3::new(0.0, 0.0, (idx_in_cls.z + 1) as f32)).z,
+                    )
+                };
+
+                let corners = [
+                    Point3::new(-z0 * x0, -z0 * y0, z0),
+                    Point3::new(-z0 * x1, -z0 * y1, z0),
+                    Point3::new(-z1 * x0, -z1 * y0, z1),
+                    Point3::new(-z1 * x1, -z1 * y1, z1),
+                ];
+                let b0 = corners.iter().fold(corners[0], |b, &c| {
+                    Point3::new(f32::min(b.x, c.x), f32::min(b.y, c.y), f32::min(b.z, c.z))
+                });
+                let b1 = corners.iter().fold(corners[0], |b, &c| {
+                    Point3::new(f32::max(b.x, c.x), f32::max(b.y, c.y), f32::max(b.z, c.z))
+                });
+
+                ClusterBounds::Perspective {
+                    nx: Vector2::new(-1.0, -x0).normalize(),
+                    px: Vector2::new(1.0, x1).normalize(),
+                    ny: Vector2::new(-1.0, -y0).normalize(),
+                    py: Vector2::new(1.0, y1).normalize(),
+                    z0,
+                    z1,
+                    b0,
+                    b1,
+                }
+            }
+            ClusteringProjection::Orthographic => ClusterBounds::Orthographic {
+                p0: space.cluster_clp_to_cam(projection, Point3::from_vec(idx_in_cls.cast::<f32>().unwrap())),
+                p1: space.cluster_clp_to_cam(
+                    projection,
+                    Point3::from_vec((idx_in_cls + Vector3::new(1, 1, 1)).cast::<f32>().unwrap()),
+                ),
+            },
+        }
+    }
+
+    /// Mirrors the sphere tests in `count_lights.comp` and `assign_lights.comp`.
+    pub fn intersects_sphere(&self, [x, y, z, r]: [f32; 4]) -> bool {
+        match *self {
+            ClusterBounds::Perspective { nx, px, ny, py, z0, z1, .. } => {
+                let xz = Vector2::new(x, z);
+                let yz = Vector2::new(y, z);
+                !(nx.dot(xz) > r || px.dot(xz) > r || ny.dot(yz) > r || py.dot(yz) > r || (z0 - z) > r || (z - z1) > r)
+            }
+            ClusterBounds::Orthographic { p0, p1 } => {
+                let mut d = 0.0;
+                for &(c, c0, c1) in &[(x, p0.x, p1.x), (y, p0.y, p1.y), (z, p0.z, p1.z)] {
+                    let r = if c < c0 { c0 - c } else { c - c1 };
+                    if r > 0.0 {
+                        d += r * r;
+                    }
+                }
+                r * r > d
+            }
+        }
+    }
+
+    pub fn aabb(&self) -> (Point3<f32>, Point3<f32>) {
+        match *self {
+            ClusterBounds::Perspective { b0, b1, .. } => (b0, b1),
+            ClusterBounds::Orthographic { p0, p1 } => (p0, p1),
+        }
+    }
+
+    /// Mirrors `cone_intersects_aabb` in `cone.glsl`.
+    pub fn intersects_cone(&self, [x, y, z, r]: [f32; 4], [dx, dy, dz, cos_a]: [f32; 4]) -> bool {
+        if cos_a < 0.0 {
+            return true;
+        }
+        let (b0, b1) = self.aabb();
+        let v = b0.midpoint(b1) - Point3::new(x, y, z);
+        let radius = b0.distance(b1) * 0.5;
+        let v_sq = v.magnitude2();
+        let v1 = v.dot(Vector3::new(dx, dy, dz));
+        let sin_a = f32::max(0.0, 1.0 - cos_a * cos_a).sqrt();
+        let d = cos_a * f32::max(0.0, v_sq - v1 * v1).sqrt() - v1 * sin_a;
+        !(d > radius || v1 > radius + r || v1 < -radius)
+    }
+
+    pub fn intersects_light(&self, xyzr: [f32; 4], dir: [f32; 4]) -> bool {
+        self.intersects_sphere(xyzr) && self.intersects_cone(xyzr, dir)
+    }
+}
+
+/// Mirrors `count_lights.comp`.
+pub fn count_lights(
+    projection: ClusteringProjection,
+    space: &ClusterSpaceBuffer,
+    active_cluster_cluster_indices: &[u32],
+    light_xyzr: &[[f32; 4]],
+    light_dir: &[[f32; 4]],
+) -> Vec<u32> {
+    active_cluster_cluster_indices
+        .iter()
+        .map(|&cluster_index| {
+            let bounds = ClusterBounds::new(projection, space, index_1_to_3(cluster_index, space.dimensions));
+            light_xyzr
+                .iter()
+                .zip(light_dir.iter())
+                .filter(|&(&xyzr, &dir)| bounds.intersects_light(xyzr, dir))
+                .count() as u32
+        })
+        .collect()
+}
+
+/// Mirrors the three `compact_light_counts` passes: an exclusive prefix sum.
+pub fn compact_light_counts(active_cluster_light_counts: &[u32]) -> Vec<u32> {
+    let mut acc = 0;
+    active_cluster_light_counts
+        .iter()
+        .map(|&count| {
+            let offset = acc;
+            acc += count;
+            offset
+        })
+        .collect()
+}
+
+/// Mirrors `assign_lights.comp`. Light indices beyond `space.max_light_indices`
+/// are dropped like they are on the GPU.
+pub fn assign_lights(
+    projection: ClusteringProjection,
+    space: &ClusterSpaceBuffer,
+    active_cluster_cluster_indices: &[u32],
+    active_cluster_light_offsets: &[u32],
+    light_xyzr: &[[f32; 4]],
+    light_dir: &[[f32; 4]],
+) -> Vec<u32> {
+    let mut light_indices = Vec::new();
+
+    for (&cluster_index, &offset) in active_cluster_cluster_indices
+        .iter()
+        .zip(active_cluster_light_offsets.iter())
+    {
+        let bounds = ClusterBounds::new(projection, space, index_1_to_3(cluster_index, space.dimensions));
+        let mut light_index_index = offset;
+        for (light_index, (&xyzr, &dir)) in light_xyzr.iter().zip(light_dir.iter()).enumerate() {
+            if bounds.intersects_light(xyzr, dir) {
+                if light_index_index < space.max_light_indices {
+                    let i = light_index_index as usize;
+                    if light_indices.len() <= i {
+                        light_indices.resize(i + 1, 0);
+                    }
+                    light_indices[i] = light_index as u32;
+                }
+                light_index_index += 1;
+            }
+        }
+    }
+
+    light_indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cluster_space_buffer::ClusterSpaceCoefficients;
+
+    fn frustum(projection: ClusteringProjection) -> Frustum<f64> {
+        // Perspective frustums are in tangents, orthographic ones in camera
+        // space units.
+        let s = match projection {
+            ClusteringProjection::Orthographic => 64.0,
+            ClusteringProjection::Perspective | ClusteringProjection::Logarithmic => 1.0,
+        };
+        Frustum {
+            x0: -s,
+            x1: s,
+            y0: -s,
+            y1: s,
+            z0: -64.0,
+            z1: -1.0,
+        }
+    }
+
+    const NEAR_SPLIT: f64 = 4.0;
+
+    fn space(projection: ClusteringProjection, frustum: Frustum<f64>, dimensions: Vector3<u32>) -> ClusterSpaceBuffer {
+        let d = dimensions.cast::<f64>().unwrap();
+        let (cam_to_clp, clp_to_cam) = match projection {
+            ClusteringProjection::Orthographic => (
+                ClusterSpaceCoefficients::orthographic(&frustum, d),
+                ClusterSpaceCoefficients::inverse_orthographic(&frustum, d),
+            ),
+            ClusteringProjection::Perspective => (
+                ClusterSpaceCoefficients::perspective(&frustum, d),
+                ClusterSpaceCoefficients::inverse_perspective(&frustum, d),
+            ),
+            ClusteringProjection::Logarithmic => (
+                ClusterSpaceCoefficients::logarithmic(&frustum, d, NEAR_SPLIT),
+                ClusterSpaceCoefficients::inverse_logarithmic(&frustum, d, NEAR_SPLIT),
+            ),
+        };
+        ClusterSpaceBuffer::new(frustum, dimensions, cam_to_clp, clp_to_cam)
+    }
+
+    fn attenuation(r1: f32) -> light::AttenCoefs<f32> {
+        light::AttenCoefs {
+            i: 1.0,
+            i0: 1.0,
+            r0: 0.0,
+            r1,
+        }
+    }
+
+    fn point_light(position: Point3<f32>, r1: f32) -> light::LightBufferLight {
+        light::LightBufferLight::from_point_light(light::PointLight {
+            tint: [1.0; 3],
+            position,
+            attenuation: attenuation(r1),
+        })
+    }
+
+    fn spot_light(position: Point3<f32>, direction: Vector3<f32>, r1: f32) -> light::LightBufferLight {
+        light::LightBufferLight::from_spot_light(light::SpotLight {
+            tint: [1.0; 3],
+            position,
+            direction,
+            inner_angle: Deg(2.0).into(),
+            outer_angle: Deg(5.0).into(),
+            attenuation: attenuation(r1),
+        })
+    }
+
+    fn cluster_center(projection: ClusteringProjection, space: &ClusterSpaceBuffer, idx: Vector3<u32>) -> Point3<f32> {
+        space.cluster_clp_to_cam(
+            projection,
+            Point3::from_vec(idx.cast::<f32>().unwrap() + Vector3::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn check_invariants(output: &ClusteringOutput) {
+        for (active_cluster_index, &cluster_index) in output.active_cluster_cluster_indices.iter().enumerate() {
+            assert!(output.cluster_fragment_counts[cluster_index as usize] > 0);
+            assert_eq!(
+                active_cluster_index as u32 + 1,
+                output.cluster_maybe_active_cluster_indices[cluster_index as usize]
+            );
+        }
+        assert_eq!(
+            output.active_cluster_light_counts.len(),
+            output.active_cluster_light_offsets.len()
+        );
+        let mut acc = 0;
+        for (&count, &offset) in output
+            .active_cluster_light_counts
+            .iter()
+            .zip(output.active_cluster_light_offsets.iter())
+        {
+            assert_eq!(acc, offset);
+            acc += count;
+        }
+        assert_eq!(acc as usize, output.light_indices.len());
+    }
+
+    fn round_trip(projection: ClusteringProjection) {
+        let space = space(projection, frustum(projection), Vector3::new(4, 3, 8));
+        for &p in &[
+            Point3::new(0.5, 1.5, 0.5),
+            Point3::new(3.25, 0.75, 7.5),
+            Point3::new(2.0, 2.0, 4.0),
+        ] {
+            let p_cam = space.cluster_clp_to_cam(projection, p);
+            assert_relative_eq!(p, space.cluster_cam_to_clp(projection, p_cam), epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn orthographic_round_trip() {
+        round_trip(ClusteringProjection::Orthographic);
+    }
+
+    #[test]
+    fn perspective_round_trip() {
+        round_trip(ClusteringProjection::Perspective);
+    }
+
+    #[test]
+    fn logarithmic_round_trip() {
+        round_trip(ClusteringProjection::Logarithmic);
+    }
+
+    #[test]
+    fn perspective_slices() {
+        let f = frustum(ClusteringProjection::Perspective);
+        let space = space(ClusteringProjection::Perspective, f, Vector3::new(1, 1, 6));
+        // Geometric slicing: the far plane maps to 0, the near plane to Z and
+        // every slice doubles in depth.
+        let z = |z_cam: f32| {
+            space
+                .cluster_cam_to_clp(ClusteringProjection::Perspective, Point3::new(0.0, 0.0, z_cam))
+                .z
+        };
+        assert_relative_eq!(0.0, z(f.z0 as f32), epsilon = 1e-4);
+        assert_relative_eq!(6.0, z(f.z1 as f32), epsilon = 1e-4);
+        assert_relative_eq!(5.0, z(-2.0), epsilon = 1e-4);
+        assert_relative_eq!(1.0, z(-32.0), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn logarithmic_slices() {
+        let f = frustum(ClusteringProjection::Logarithmic);
+        let space = space(ClusteringProjection::Logarithmic, f, Vector3::new(1, 1, 6));
+        // The nearest slice is linear up to the split, the remaining 5 slices
+        // divide [4, 64] geometrically so every slice grows by 16^(1/5).
+        let z = |z_cam: f32| {
+            space
+                .cluster_cam_to_clp(ClusteringProjection::Logarithmic, Point3::new(0.0, 0.0, z_cam))
+                .z
+        };
+        assert_relative_eq!(0.0, z(f.z0 as f32), epsilon = 1e-4);
+        assert_relative_eq!(6.0, z(f.z1 as f32), epsilon = 1e-4);
+        assert_relative_eq!(5.5, z(-2.5), epsilon = 1e-4);
+        assert_relative_eq!(5.0, z(-NEAR_SPLIT as f32), epsilon = 1e-4);
+        assert_relative_eq!(3.75, z(-8.0), epsilon = 1e-4);
+        assert_relative_eq!(2.5, z(-16.0), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn logarithmic_near_split_is_clamped() {
+        let f = frustum(ClusteringProjection::Logarithmic);
+        let d = Vector3::new(1.0, 1.0, 6.0);
+        for &near_split in &[-1.0, 0.0, 0.5, 1.0, 64.0, 100.0] {
+            let s = ClusterSpaceCoefficients::logarithmic_near_split(&f, near_split);
+            assert!(-f.z1 < s && s < -f.z0);
+            for c in &[
+                ClusterSpaceCoefficients::logarithmic(&f, d, s),
+                ClusterSpaceCoefficients::inverse_logarithmic(&f, d, s),
+            ] {
+                assert!(c.az.is_finite() && c.bz.is_finite());
+            }
+        }
+    }
+
+    fn single_slice(projection: ClusteringProjection) {
+        let dimensions = Vector3::new(4, 4, 6);
+        let space = space(projection, frustum(projection), dimensions);
+
+        // The render camera coincides with the cluster camera.
+        let f = frustum(ClusteringProjection::Perspective);
+        let ren_clp_to_clu_cam = f.inverse_perspective(&RENDER_RANGE).cast::<f32>().unwrap();
+        let ren_cam_to_clp = f.perspective(&RENDER_RANGE);
+
+        // Fill the depth buffer with a plane at z = -5.0, which lies inside a
+        // single slice for both projections.
+        let z_in_ren_clp = ren_cam_to_clp.transform_point(Point3::new(0.0, 0.0, -5.0)).z as f32;
+        let depth_dimensions = Vector2::new(16, 16);
+        let mut depth = vec![z_in_ren_clp; depth_dimensions.product() as usize];
+        // Cleared pixels are skipped.
+        depth[0] = 0.0;
+
+        let z_slice = space.cluster_cam_to_clp(projection, Point3::new(0.0, 0.0, -5.0)).z as u32;
+
+        let lights = [
+            // Centered in an active cluster.
+            point_light(cluster_center(projection, &space, Vector3::new(1, 2, z_slice)), 0.01),
+            // Far away from all geometry.
+            point_light(Point3::new(0.0, 0.0, 1000.0), 1.0),
+            // Covers everything.
+            point_light(Point3::new(0.0, 0.0, -5.0), 1000.0),
+        ];
+
+        let output = compute_clustering(&ClusteringInput {
+            projection,
+            space: &space,
+            depth: &depth,
+            depth_dimensions,
+            ren_clp_to_clu_cam,
+            lights: &lights,
+            lgt_to_clu_cam: Matrix4::identity(),
+        });
+
+        check_invariants(&output);
+        assert_eq!(0, output.active_cluster_overflow);
+        assert_eq!(0, output.light_indices_overflow);
+
+        assert_eq!(
+            depth_dimensions.product() - 1,
+            output.cluster_fragment_counts.iter().sum::<u32>()
+        );
+        for &cluster_index in output.active_cluster_cluster_indices.iter() {
+            assert_eq!(z_slice, index_1_to_3(cluster_index, dimensions).z);
+        }
+        assert!(!output.active_cluster_cluster_indices.is_empty());
+
+        let target = index_3_to_1(Vector3::new(1, 2, z_slice), dimensions);
+        for (active_cluster_index, &cluster_index) in output.active_cluster_cluster_indices.iter().enumerate() {
+            let offset = output.active_cluster_light_offsets[active_cluster_index] as usize;
+            let count = output.active_cluster_light_counts[active_cluster_index] as usize;
+            let indices = &output.light_indices[offset..offset + count];
+            if cluster_index == target {
+                assert_eq!(&[0, 2], indices);
+            } else {
+                assert_eq!(&[2], indices);
+            }
+        }
+    }
+
+    #[test]
+    fn orthographic_single_slice() {
+        single_slice(ClusteringProjection::Orthographic);
+    }
+
+    #[test]
+    fn perspective_single_slice() {
+        single_slice(ClusteringProjection::Perspective);
+    }
+
+    #[test]
+    fn logarithmic_single_slice() {
+        single_slice(ClusteringProjection::Logarithmic);
+    }
+
+    #[test]
+    fn light_indices_are_capped() {
+        let projection = ClusteringProjection::Orthographic;
+        let mut space = space(projection, frustum(projection), Vector3::new(2, 2, 2));
+        space.max_light_indices = 5;
+        let light_xyzr = vec![[0.0, 0.0, -32.0, 1000.0]; 3];
+        let light_dir = vec![[0.0, 0.0, 0.0, -1.0]; 3];
+        let active_cluster_cluster_indices: Vec<u32> = (0..space.cluster_count).collect();
+        let counts = count_lights(projection, &space, &active_cluster_cluster_indices, &light_xyzr, &light_dir);
+        assert_eq!(vec![3; 8], counts);
+        let offsets = compact_light_counts(&counts);
+        let light_indices = assign_lights(
+            projection,
+            &space,
+            &active_cluster_cluster_indices,
+            &offsets,
+            &light_xyzr,
+            &light_dir,
+        );
+        assert_eq!(vec![0, 1, 2, 0, 1], light_indices);
+    }
+
+    #[test]
+    fn active_clusters_are_capped() {
+        let (maybe_active, active) = compact_clusters(&[0, 3, 1, 0, 2], 2);
+        assert_eq!(vec![0, 1, 2, 0, 0], maybe_active);
+        assert_eq!(vec![1, 2], active);
+    }
+
+    #[test]
+    fn area_lights() {
+        let projection = ClusteringProjection::Orthographic;
+        let space = space(projection, frustum(projection), Vector3::new(4, 4, 6));
+        let active_cluster_cluster_indices: Vec<u32> = (0..space.cluster_count).collect();
+
+        let area_light = |shape| {
+            light::LightBufferLight::from_area_light(light::AreaLight {
+                tint: [1.0; 3],
+                position: Point3::new(16.0, 16.0, -6.25),
+                shape,
+                attenuation: attenuation(0.01),
+            })
+        };
+
+        let lights = [
+            // Reaches into the neighbouring cluster along x.
+            area_light(light::AreaLightShape::Tube {
+                axis: Vector3::new(20.0, 0.0, 0.0),
+            }),
+            // Spans four clusters in xy but only lights up the clusters in
+            // front of it.
+            area_light(light::AreaLightShape::Rectangle {
+                axis_0: Vector3::new(20.0, 0.0, 0.0),
+                axis_1: Vector3::new(0.0, 20.0, 0.0),
+                normal: Some(Vector3::unit_z()),
+            }),
+        ];
+        let (light_xyzr, light_dir) = transform_lights(&lights, Matrix4::identity());
+
+        let bounds = |x, y, z| ClusterBounds::new(projection, &space, Vector3::new(x, y, z));
+        assert!(bounds(2, 2, 5).intersects_light(light_xyzr[0], light_dir[0]));
+        assert!(bounds(3, 2, 5).intersects_light(light_xyzr[0], light_dir[0]));
+        assert!(!bounds(0, 2, 5).intersects_light(light_xyzr[0], light_dir[0]));
+
+        assert!(bounds(2, 2, 5).intersects_light(light_xyzr[1], light_dir[1]));
+        assert!(bounds(3, 3, 5).intersects_light(light_xyzr[1], light_dir[1]));
+        assert!(!bounds(2, 2, 2).intersects_light(light_xyzr[1], light_dir[1]));
+
+        let counts = count_lights(projection, &space, &active_cluster_cluster_indices, &light_xyzr, &light_dir);
+        let offsets = compact_light_counts(&counts);
+        let light_indices = assign_lights(
+            projection,
+            &space,
+            &active_cluster_cluster_indices,
+            &offsets,
+            &light_xyzr,
+            &light_dir,
+        );
+        assert_eq!(counts.iter().sum::<u32>() as usize, light_indices.len());
+    }
+
+    #[test]
+    fn perspective_spot_lights() {
+        let projection = ClusteringProjection::Perspective;
+        let dimensions = Vector3::new(4, 4, 6);
+        let space = space(projection, frustum(projection), dimensions);
+        let active_cluster_cluster_indices: Vec<u32> = (0..space.cluster_count).collect();
+
+        let lights = [
+            // Narrow cone along the view direction.
+            spot_light(Point3::origin(), -Vector3::unit_z(), 1000.0),
+            // Pointing away from all clusters.
+            spot_light(Point3::origin(), Vector3::unit_z(), 1000.0),
+        ];
+        let (light_xyzr, light_dir) = transform_lights(&lights, Matrix4::identity());
+        let counts = count_lights(projection, &space, &active_cluster_cluster_indices, &light_xyzr, &light_dir);
+        let offsets = compact_light_counts(&counts);
+        let light_indices = assign_lights(
+            projection,
+            &space,
+            &active_cluster_cluster_indices,
+            &offsets,
+            &light_xyzr,
+            &light_dir,
+        );
+        assert_eq!(counts.iter().sum::<u32>() as usize, light_indices.len());
+        assert!(light_indices.iter().all(|&light_index| light_index == 0));
+
+        for (&cluster_index, &count) in active_cluster_cluster_indices.iter().zip(counts.iter()) {
+            let idx = index_1_to_3(cluster_index, dimensions);
+            let on_axis = (idx.x == 1 || idx.x == 2) && (idx.y == 1 || idx.y == 2);
+            assert_eq!(if on_axis { 1 } else { 0 }, count, "cluster {:?}", idx);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::camera;
 use crate::profiling::ProfilingConfiguration;
 use cgmath::*;
+use derive::EnumNext;
 use std::path::PathBuf;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
@@ -21,6 +22,11 @@ pub struct Configuration {
     pub global: GlobalConfiguration,
     pub mirror: MirrorConfiguration,
     pub light: LightConfiguration,
+    pub post: PostConfiguration,
+    pub transparency: TransparencyConfiguration,
+    pub lod: LodConfiguration,
+    pub foveation: FoveationConfiguration,
+    pub ibl: IblConfiguration,
     pub rain: RainConfiguration,
     pub window: crate::WindowConfiguration,
     pub gl: crate::GlConfiguration,
@@ -89,6 +95,9 @@ pub struct GlobalConfiguration {
     pub scene_path: PathBuf,
     pub sample_count: u32,
     pub display_parameters: bool,
+    pub gpu_culling: bool,
+    pub occlusion_culling: bool,
+    pub instanced_stereo: bool,
     pub cluster_visualisation: ClusterVisualisation,
     pub cluster_visualisation_mode: ClusterVisualisationMode,
     pub cluster_visualisation_max_lights: u32,
@@ -110,14 +119,121 @@ pub struct LightConfiguration {
     pub virtual_light_count: u32,
     pub static_lights: bool,
     pub head_light: bool,
+    pub spot_lights: Vec<SpotLight>,
     pub attenuation: Attenuation,
     pub shadows: LightShadowsConfiguration,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SpotLight {
+    pub tint: [f32; 3],
+    pub position: Point3<f64>,
+    pub direction: Vector3<f64>,
+    /// Degrees.
+    pub inner_angle: f64,
+    /// Degrees.
+    pub outer_angle: f64,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct LightShadowsConfiguration {
     pub enabled: bool,
     pub dimensions: Vector2<u32>,
+    pub atlas_lights: u32,
+    pub atlas_dimensions: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq, EnumNext)]
+pub enum Tonemapper {
+    Aces,
+    Reinhard,
+    Uncharted2,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PostConfiguration {
+    pub enabled: bool,
+    pub tonemapper: Tonemapper,
+    /// Exposure when auto exposure is disabled.
+    pub exposure: f32,
+    pub auto_exposure: AutoExposureConfiguration,
+    pub bloom: BloomConfiguration,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct AutoExposureConfiguration {
+    pub enabled: bool,
+    /// Stops added to the computed exposure.
+    pub compensation: f32,
+    /// Range of the luminance histogram in log2 luminance.
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    /// Speed at which the exposure adapts, per second.
+    pub adaptation_rate: f32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct BloomConfiguration {
+    pub enabled: bool,
+    /// Luminance above which pixels start to bloom.
+    pub threshold: f32,
+    pub intensity: f32,
+    pub levels: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq, EnumNext)]
+pub enum TransparencyMode {
+    /// Alpha blending in instance order.
+    Unsorted,
+    WeightedBlended,
+    LinkedList,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct TransparencyConfiguration {
+    pub mode: TransparencyMode,
+    /// Average number of transparent fragments per pixel that the linked list
+    /// node buffer can hold. Fragments beyond the capacity are dropped.
+    pub linked_list_fragments_per_pixel: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct LodConfiguration {
+    pub enabled: bool,
+    /// Projected size of the bounding sphere, its radius over its distance to
+    /// the camera, below which the first simplified level of detail is used.
+    /// Every halving of the size selects the next level.
+    pub threshold: f64,
+}
+
+/// Radial density masking for stereo rendering. Pixels are shaded at full
+/// density up to `inner_angle` degrees from the optical axis of an eye, at
+/// half density up to `outer_angle` and at a quarter density beyond.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct FoveationConfiguration {
+    pub enabled: bool,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+}
+
+/// Image based lighting from a procedural sky using the split sum
+/// approximation.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct IblConfiguration {
+    pub enabled: bool,
+    pub intensity: f32,
+    /// Number of samples per texel used to generate the lighting textures.
+    pub sample_count: u32,
+    pub sky: SkyConfiguration,
+}
+
+/// Linear radiance of the sky, interpolated from the horizon to the zenith
+/// above and to the ground below it.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct SkyConfiguration {
+    pub zenith_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    pub ground_color: [f32; 3],
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -160,6 +276,7 @@ pub enum ApplicationMode {
 pub enum ClusteringProjection {
     Orthographic,
     Perspective,
+    Logarithmic,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
@@ -183,9 +300,12 @@ pub struct ClusteredLightShadingConfiguration {
     pub perspective_pixels: Vector2<u32>,
     pub perspective_align: bool,
     pub perspective_displacement: f64,
+    pub logarithmic_slices: u32,
+    pub logarithmic_near_split: f64,
     pub max_clusters: u32,
     pub max_active_clusters: u32,
     pub max_light_indices: u32,
+    pub adaptive_buffers: bool,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Eq, PartialEq)]
@@ -0,0 +1,219 @@
+use crate::*;
+
+pub struct Renderer {
+    pub program: rendering::Program,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {
+        INSTANCE_CULL_BUFFER_BINDING = 0;
+        DRAW_COMMAND_BUFFER_BINDING = 1;
+        DRAW_RANGE_BUFFER_BINDING = 2;
+        CULL_STATS_BUFFER_BINDING = 3;
+        MESH_LOD_BUFFER_BINDING = 4;
+        INSTANCE_MATRICES_BUFFER_BINDING = 10;
+
+        HIZ_SAMPLER_BINDING = 0;
+    },
+    uniforms: {
+        WLD_TO_CAM_LOC = 0;
+        FRUSTUM_XY_LOC = 1;
+        FRUSTUM_Z_LOC = 2;
+        PROJECTION_KIND_LOC = 3;
+        INSTANCE_COUNT_LOC = 4;
+        HIZ_ENABLED_LOC = 5;
+        HIZ_WLD_TO_CLP_LOC = 6;
+        LOD_THRESHOLD_LOC = 7;
+        VIEW_COUNT_LOC = 8;
+        LOD_CAM_POS_IN_WLD_LOC = 9;
+    },
+});
+
+const PROJECTION_KIND_PERSPECTIVE: u32 = 1;
+const PROJECTION_KIND_ORTHOGRAPHIC: u32 = 2;
+
+/// A depth pyramid to occlusion cull against, see `main_resources::HiZ`.
+pub struct Occlusion {
+    pub texture_name: gl::TextureName,
+    pub wld_to_clp: Matrix4<f64>,
+}
+
+impl Context<'_> {
+    /// Computes the instance matrices and the draw commands of the instances
+    /// visible to `culling_camera`. Culling happens in a compute shader unless
+    /// it is disabled or the shader is not available. Occlusion culling is
+    /// only done on the GPU. Every visible instance is drawn once per view in
+    /// `wld_to_ren_clp`, so `culling_camera` has to enclose all of them. The
+    /// culling counts are recorded in the main resources when given. Levels of
+    /// detail are selected from `lod_camera` when given, which should be the
+    /// same for every culling view of a frame.
+    pub fn recompute_draw_resources(
+        &mut self,
+        draw_resources_index: usize,
+        main_resources_index: Option<usize>,
+        culling_camera: resources::CullingCamera,
+        lod_camera: Option<resources::LodCamera>,
+        wld_to_ren_clp: &[Matrix4<f64>],
+        occlusion: Option<Occlusion>,
+    ) {
+        let gl = self.gl;
+        let view_count = wld_to_ren_clp.len() as u32;
+        let draw_resources = &mut self.resources.draw_resources_pool[draw_resources_index];
+
+        draw_resources.recompute_instance_matrices(
+            gl,
+            &mut self.profiling_context,
+            wld_to_ren_clp,
+            &self.world_transforms,
+        );
+
+        if self.configuration.global.gpu_culling {
+            let program = &mut self.cull_renderer.program;
+            program.update(&mut rendering_context!(self));
+            if let ProgramName::Linked(name) = program.name {
+                let profiler_index = self
+                    .profiling_context
+                    .start(gl, draw_resources.compute_draw_commands_profiler);
+
+                draw_resources.reset_draw_commands(
+                    gl,
+                    &self.resources.material_instance_counts,
+                    &self.resources.material_instance_offsets,
+                );
+
+                let cull_stats_buffer = match main_resources_index {
+                    Some(main_resources_index) => unsafe {
+                        self.main_resources_pool[main_resources_index]
+                            .cull_stats
+                            .begin(gl, self.frame_index)
+                    },
+                    None => draw_resources.cull_stats_buffer,
+                };
+
+                unsafe {
+                    gl.use_program(name);
+
+                    gl.bind_buffer_base(
+                        gl::SHADER_STORAGE_BUFFER,
+                        INSTANCE_CULL_BUFFER_BINDING,
+                        self.resources.instance_cull_buffer,
+                    );
+                    gl.bind_buffer_base(
+                        gl::SHADER_STORAGE_BUFFER,
+                        DRAW_COMMAND_BUFFER_BINDING,
+                        draw_resources.draw_command_buffer,
+                    );
+                    gl.bind_buffer_base(
+                        gl::SHADER_STORAGE_BUFFER,
+                        DRAW_RANGE_BUFFER_BINDING,
+                        draw_resources.draw_range_buffer,
+                    );
+                    gl.bind_buffer_base(gl::SHADER_STORAGE_BUFFER, CULL_STATS_BUFFER_BINDING, cull_stats_buffer);
+                    gl.bind_buffer_base(
+                        gl::SHADER_STORAGE_BUFFER,
+                        MESH_LOD_BUFFER_BINDING,
+                        self.resources.mesh_lod_buffer,
+                    );
+                    gl.bind_buffer_base(
+                        gl::SHADER_STORAGE_BUFFER,
+                        INSTANCE_MATRICES_BUFFER_BINDING,
+                        draw_resources.instance_matrices_buffer,
+                    );
+
+                    let Frustum { x0, x1, y0, y1, z0, z1 } = culling_camera.frustum.cast::<f32>().unwrap();
+                    gl.uniform_matrix4f(
+                        WLD_TO_CAM_LOC,
+                        gl::MajorAxis::Column,
+                        culling_camera.wld_to_cam.cast::<f32>().unwrap().as_ref(),
+                    );
+                    gl.uniform_4f(FRUSTUM_XY_LOC, [x0, x1, y0, y1]);
+                    gl.uniform_2f(FRUSTUM_Z_LOC, [z0, z1]);
+                    gl.uniform_1ui(
+                        PROJECTION_KIND_LOC,
+                        match culling_camera.projection_kind {
+                            resources::ProjectionKind::Perspective => PROJECTION_KIND_PERSPECTIVE,
+                            resources::ProjectionKind::Orthographic => PROJECTION_KIND_ORTHOGRAPHIC,
+                        },
+                    );
+
+                    // A threshold of zero disables level of detail selection.
+                    match lod_camera {
+                        Some(lod_camera) => {
+                            gl.uniform_1f(LOD_THRESHOLD_LOC, lod_camera.threshold as f32);
+                            gl.uniform_3f(
+                                LOD_CAM_POS_IN_WLD_LOC,
+                                lod_camera.pos_in_wld.cast::<f32>().unwrap().into(),
+                            );
+                        }
+                        None => {
+                            gl.uniform_1f(LOD_THRESHOLD_LOC, 0.0);
+                        }
+                    }
+                    gl.uniform_1ui(VIEW_COUNT_LOC, view_count);
+
+                    match occlusion {
+                        Some(occlusion) => {
+                            gl.uniform_1ui(HIZ_ENABLED_LOC, 1);
+                            gl.uniform_matrix4f(
+                                HIZ_WLD_TO_CLP_LOC,
+                                gl::MajorAxis::Column,
+                                occlusion.wld_to_clp.cast::<f32>().unwrap().as_ref(),
+                            );
+                            gl.bind_texture_unit(HIZ_SAMPLER_BINDING, occlusion.texture_name);
+                        }
+                        None => {
+                            gl.uniform_1ui(HIZ_ENABLED_LOC, 0);
+                        }
+                    }
+
+                    let instance_count = self.resources.scene_file.instances.len() as u32;
+                    gl.uniform_1ui(INSTANCE_COUNT_LOC, instance_count);
+
+                    gl.memory_barrier(gl::MemoryBarrierFlag::BUFFER_UPDATE);
+                    gl.dispatch_compute(instance_count.ceiled_div(256), 1, 1);
+                    gl.memory_barrier(gl::MemoryBarrierFlag::COMMAND);
+
+                    gl.unuse_program();
+                }
+
+                self.profiling_context.stop(gl, profiler_index);
+
+                return;
+            }
+        }
+
+        let cull_stats = draw_resources.recompute_draw_commands(
+            gl,
+            &mut self.profiling_context,
+            culling_camera,
+            lod_camera,
+            view_count,
+            &self.world_transforms,
+            &self.resources.materials,
+            &self.resources.scene_file,
+        );
+
+        if let Some(main_resources_index) = main_resources_index {
+            self.main_resources_pool[main_resources_index].cull_stats.stats = cull_stats;
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        Renderer {
+            program: rendering::Program::new(
+                context.gl,
+                vec![Shader::new(
+                    context.gl,
+                    gl::COMPUTE_SHADER,
+                    EntryPoint::new(
+                        &mut shader_compilation_context!(context),
+                        "cull_instances.comp",
+                        fixed_header(),
+                    ),
+                )],
+            ),
+        }
+    }
+}
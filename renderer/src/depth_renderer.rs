@@ -59,9 +59,7 @@ impl Context<'_> {
                 gl.bind_buffer(gl::DRAW_INDIRECT_BUFFER, draw_resources.draw_command_buffer);
 
                 gl.bind_vertex_array(resources.scene_vao);
-
-                let draw_counts = &draw_resources.draw_counts;
-                let draw_offsets = &draw_resources.draw_offsets;
+                main_resources.enable_view_clipping(gl);
 
                 for &(program, material_kind, sample_index) in [
                     (
@@ -105,13 +103,7 @@ impl Context<'_> {
                         }
 
                         // Execute draw.
-                        gl.multi_draw_elements_indirect(
-                            gl::TRIANGLES,
-                            gl::UNSIGNED_INT,
-                            draw_offsets[material_index] as usize * std::mem::size_of::<DrawCommand>(),
-                            draw_counts[material_index] as i32,
-                            std::mem::size_of::<DrawCommand>() as i32,
-                        );
+                        draw_resources.multi_draw(gl, material_index);
                     }
 
                     self.profiling_context.stop(gl, profiler_index);
@@ -119,6 +111,7 @@ impl Context<'_> {
 
                 gl.unuse_program();
 
+                main_resources.disable_view_clipping(gl);
                 gl.unbind_vertex_array();
             }
         }
@@ -0,0 +1,230 @@
+//! Radial density masking. The periphery of a foveated main framebuffer is
+//! masked in 2x2 pixel quads by writing the nearest depth, which makes all
+//! depth tested passes skip the masked quads. Afterwards the masked quads are
+//! reconstructed from their shaded neighbours.
+
+use crate::*;
+
+pub struct Renderer {
+    pub mask_program: rendering::Program,
+    pub reconstruct_program: rendering::Program,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {
+        COLOR_SAMPLER_BINDING = 0;
+    },
+    uniforms: {
+        TANGENTS_LOC = 0;
+        ANGLES_LOC = 1;
+        VIEWPORT_LOC = 2;
+    },
+});
+
+pub struct Parameters {
+    pub main_resources_index: usize,
+}
+
+/// Fraction of the quads at the given position that is shaded, matches
+/// `foveation_level` in `foveation.glsl`.
+fn density(cfg: &configuration::FoveationConfiguration, frustum: &Frustum<f64>, pos_in_tex: Vector2<f64>) -> f64 {
+    let t = Vector2::new(
+        frustum.x0 + pos_in_tex.x * (frustum.x1 - frustum.x0),
+        frustum.y0 + pos_in_tex.y * (frustum.y1 - frustum.y0),
+    );
+    let angle = t.magnitude().atan();
+    if angle < (cfg.inner_angle as f64).to_radians() {
+        1.0
+    } else if angle < (cfg.outer_angle as f64).to_radians() {
+        0.5
+    } else {
+        0.25
+    }
+}
+
+/// Estimates the fraction of pixels that is shaded by sampling the density on
+/// a coarse grid.
+pub fn shaded_fraction(cfg: &configuration::FoveationConfiguration, frustum: &Frustum<f64>) -> f64 {
+    const N: usize = 64;
+    let mut sum = 0.0;
+    for y in 0..N {
+        for x in 0..N {
+            let pos_in_tex = Vector2::new(x as f64 + 0.5, y as f64 + 0.5) / N as f64;
+            sum += density(cfg, frustum, pos_in_tex);
+        }
+    }
+    sum / (N * N) as f64
+}
+
+/// The dimensions of a main framebuffer scaled down to the density at which
+/// it is shaded. Clusters are sized by these so that a cluster covers as many
+/// shaded pixels as without foveation.
+pub fn shaded_dimensions(cfg: &configuration::FoveationConfiguration, main_resources: &MainResources) -> Vector2<f64> {
+    let dimensions = main_resources.framebuffer.dimensions.cast::<f64>().unwrap();
+    if !main_resources.foveated {
+        return dimensions;
+    }
+    let views = main_resources.views();
+    let fraction = views
+        .iter()
+        .map(|(_, camera)| shaded_fraction(cfg, &camera.frustum))
+        .sum::<f64>()
+        / views.len() as f64;
+    dimensions * fraction.sqrt()
+}
+
+impl Context<'_> {
+    /// Masks the periphery of a main framebuffer. Expects the depth to be
+    /// cleared.
+    pub fn render_foveation_mask(&mut self, params: Parameters) {
+        let Context {
+            ref gl,
+            ref resources,
+            ref configuration,
+            foveation_renderer: ref mut renderer,
+            ..
+        } = *self;
+
+        let main_resources = &self.main_resources_pool[params.main_resources_index];
+        let framebuffer = &main_resources.framebuffer;
+
+        let profiler_index = self
+            .profiling_context
+            .start(gl, main_resources.profilers.foveation_mask_profiler);
+
+        unsafe {
+            renderer.mask_program.update(&mut rendering_context!(self));
+            if let ProgramName::Linked(program) = renderer.mask_program.name {
+                gl.bind_framebuffer(gl::FRAMEBUFFER, framebuffer.framebuffer_name);
+                gl.disable(gl::CULL_FACE);
+                gl.color_mask(
+                    gl::WriteMask::Disabled,
+                    gl::WriteMask::Disabled,
+                    gl::WriteMask::Disabled,
+                    gl::WriteMask::Disabled,
+                );
+
+                gl.use_program(program);
+                gl.bind_vertex_array(resources.full_screen_vao);
+                draw_views(gl, &configuration.foveation, main_resources);
+                gl.unbind_vertex_array();
+
+                gl.unuse_program();
+
+                gl.color_mask(
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                );
+                gl.enable(gl::CULL_FACE);
+            }
+        }
+
+        self.profiling_context.stop(gl, profiler_index);
+    }
+
+    /// Fills in the masked quads of a main framebuffer. Uses the resolve
+    /// texture of the post resources as a copy of the color, which is unused
+    /// without multisampling.
+    pub fn render_foveation_reconstruct(&mut self, params: Parameters) {
+        let Context {
+            ref gl,
+            ref resources,
+            ref configuration,
+            foveation_renderer: ref mut renderer,
+            ..
+        } = *self;
+
+        let main_resources = &self.main_resources_pool[params.main_resources_index];
+        let framebuffer = &main_resources.framebuffer;
+        let post = &main_resources.post;
+
+        let profiler_index = self
+            .profiling_context
+            .start(gl, main_resources.profilers.foveation_reconstruct_profiler);
+
+        unsafe {
+            renderer.reconstruct_program.update(&mut rendering_context!(self));
+            if let ProgramName::Linked(program) = renderer.reconstruct_program.name {
+                gl.blit_named_framebuffer(
+                    framebuffer.framebuffer_name.into(),
+                    post.resolve_framebuffer_name.into(),
+                    0,
+                    0,
+                    framebuffer.dimensions.x,
+                    framebuffer.dimensions.y,
+                    0,
+                    0,
+                    post.dimensions.x,
+                    post.dimensions.y,
+                    gl::BlitMask::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+
+                gl.bind_framebuffer(gl::FRAMEBUFFER, framebuffer.framebuffer_name);
+                gl.disable(gl::DEPTH_TEST);
+                gl.disable(gl::BLEND);
+                gl.disable(gl::CULL_FACE);
+                gl.depth_mask(gl::WriteMask::Disabled);
+
+                gl.use_program(program);
+                gl.bind_texture_unit(COLOR_SAMPLER_BINDING, post.resolve_texture_name);
+
+                gl.bind_vertex_array(resources.full_screen_vao);
+                draw_views(gl, &configuration.foveation, main_resources);
+                gl.unbind_vertex_array();
+
+                gl.unuse_program();
+
+                gl.depth_mask(gl::WriteMask::Enabled);
+                gl.enable(gl::CULL_FACE);
+                gl.enable(gl::DEPTH_TEST);
+            }
+        }
+
+        self.profiling_context.stop(gl, profiler_index);
+    }
+}
+
+/// Draws a full screen quad per view, centering the foveation on each eye.
+/// Leaves the viewport covering the whole framebuffer.
+unsafe fn draw_views(gl: &gl::Gl, cfg: &configuration::FoveationConfiguration, main_resources: &MainResources) {
+    gl.uniform_2f(ANGLES_LOC, [cfg.inner_angle.to_radians(), cfg.outer_angle.to_radians()]);
+    for (viewport, camera) in main_resources.views() {
+        let Frustum { x0, x1, y0, y1, .. } = camera.frustum.cast::<f32>().unwrap();
+        gl.uniform_4f(TANGENTS_LOC, [x0, x1, y0, y1]);
+        let Viewport { origin, dimensions } = viewport;
+        gl.uniform_4f(
+            VIEWPORT_LOC,
+            [
+                origin.x as f32,
+                origin.y as f32,
+                dimensions.x as f32,
+                dimensions.y as f32,
+            ],
+        );
+        viewport.set(gl);
+        gl.draw_elements(
+            gl::TRIANGLES,
+            (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+            gl::UNSIGNED_INT,
+            0,
+        );
+    }
+    Viewport::from_dimensions(main_resources.framebuffer.dimensions).set(gl);
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        Renderer {
+            mask_program: vs_fs_program(context, "overlay_renderer.vert", "foveation_mask.frag", fixed_header()),
+            reconstruct_program: vs_fs_program(
+                context,
+                "overlay_renderer.vert",
+                "foveation_reconstruct.frag",
+                fixed_header(),
+            ),
+        }
+    }
+}
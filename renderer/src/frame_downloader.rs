@@ -119,12 +119,14 @@ pub struct EncoderThread {
     handle: thread::JoinHandle<()>,
 }
 
-impl Default for EncoderThread {
-    fn default() -> Self {
+impl EncoderThread {
+    pub fn new(profiler: profiling::ThreadProfiler) -> Self {
         let (tx, rx) = mpsc::sync_channel::<(PathBuf, Image)>(2);
 
         let handle = thread::spawn(move || {
             for (path, image) in rx.iter() {
+                let span = profiler.start("encode frame");
+
                 // Clone bytes and flip-y while we're at it.
                 let image = unsafe {
                     let mut bytes = Vec::<u8>::with_capacity(image.image_byte_count());
@@ -150,14 +152,14 @@ impl Default for EncoderThread {
                 encoder
                     .encode(&image.bytes, image.width, image.height, image.format.into())
                     .unwrap();
+
+                profiler.stop(span);
             }
         });
 
         Self { tx, handle }
     }
-}
 
-impl EncoderThread {
     pub fn join(self) -> thread::Result<()> {
         let Self { tx, handle } = self;
         std::mem::drop(tx);
@@ -179,12 +181,14 @@ pub struct FrameDownloader {
 }
 
 impl FrameDownloader {
-    pub fn new() -> Self {
+    pub fn new(cpu_profiler: &profiling::CpuProfiler) -> Self {
         Self {
             buffers: Default::default(),
             transfers: Default::default(),
             next_thread_index: 0,
-            thread_pool: std::iter::repeat_with(EncoderThread::default).take(6).collect(),
+            thread_pool: (0..6)
+                .map(|i| EncoderThread::new(cpu_profiler.thread(format!("encoder {}", i))))
+                .collect(),
         }
     }
 
@@ -245,6 +245,43 @@ where
     }
 }
 
+/// Whether `multi_draw_elements_indirect_count` from ARB_indirect_parameters
+/// is available. It is core since OpenGL 4.6 but older drivers may still
+/// expose the extension.
+pub unsafe fn supports_indirect_draw_count(gl: &gl::Gl) -> bool {
+    let version = gl.get_string(gl::VERSION).to_string();
+    if parse_gl_version(&version).map_or(false, |version| version >= (4, 6)) {
+        return true;
+    }
+    let extension_count = gl.get_integerv(gl::NUM_EXTENSIONS);
+    (0..extension_count as u32)
+        .any(|index| gl.get_stringi(gl::EXTENSIONS, index).to_string() == "GL_ARB_indirect_parameters")
+}
+
+/// Finds the first "major.minor" in a version string. Some drivers prefix the
+/// version with vendor text.
+fn parse_gl_version(version: &str) -> Option<(u32, u32)> {
+    version.split_whitespace().find_map(|token| {
+        let mut numbers = token.split('.');
+        let major = numbers.next()?.parse::<u32>().ok()?;
+        let minor = numbers.next()?.parse::<u32>().ok()?;
+        Some((major, minor))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gl_versions() {
+        assert_eq!(Some((4, 6)), parse_gl_version("4.6.0 NVIDIA 440.82"));
+        assert_eq!(Some((4, 5)), parse_gl_version("4.5 (Core Profile) Mesa 19.2.8"));
+        assert_eq!(Some((3, 2)), parse_gl_version("OpenGL ES 3.2 build 1.10"));
+        assert_eq!(None, parse_gl_version("unknown"));
+    }
+}
+
 pub trait BufferNameExt: Sized {
     fn new(gl: &gl::Gl) -> Self;
 }
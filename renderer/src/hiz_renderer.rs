@@ -0,0 +1,105 @@
+use crate::*;
+
+pub struct Renderer {
+    pub program: rendering::Program,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {
+        DEPTH_SAMPLER_BINDING = 0;
+    },
+    uniforms: {},
+});
+
+pub struct Parameters {
+    pub main_resources_index: usize,
+}
+
+impl Context<'_> {
+    /// Builds the depth pyramid of a main resources from its depth buffer.
+    pub fn render_hiz(&mut self, params: Parameters) {
+        let Context {
+            ref gl,
+            ref resources,
+            ref mut hiz_renderer,
+            ..
+        } = *self;
+
+        let main_resources = &mut self.main_resources_pool[params.main_resources_index];
+
+        // Multisampled depth can not be sampled as a regular texture and the
+        // side by side eyes of instanced stereo do not share a projection.
+        if main_resources.framebuffer.sample_count != 0 || main_resources.eyes.is_some() {
+            main_resources.hiz.wld_to_clp = None;
+            return;
+        }
+
+        let profiler_index = self.profiling_context.start(gl, main_resources.profilers.hiz_profiler);
+
+        unsafe {
+            hiz_renderer.program.update(&mut rendering_context!(self));
+            if let ProgramName::Linked(program) = hiz_renderer.program.name {
+                let hiz = &mut main_resources.hiz;
+
+                gl.disable(gl::DEPTH_TEST);
+                gl.disable(gl::BLEND);
+                gl.disable(gl::CULL_FACE);
+                gl.depth_mask(gl::WriteMask::Disabled);
+                gl.color_mask(
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                    gl::WriteMask::Enabled,
+                );
+
+                gl.use_program(program);
+                gl.bind_vertex_array(resources.full_screen_vao);
+                gl.bind_framebuffer(gl::FRAMEBUFFER, hiz.framebuffer_name);
+
+                for level in 0..hiz.level_count {
+                    if level == 0 {
+                        gl.bind_texture_unit(DEPTH_SAMPLER_BINDING, main_resources.framebuffer.depth_texture_name);
+                    } else {
+                        // Restrict sampling to the previous level to avoid a feedback loop.
+                        gl.texture_parameteri(hiz.texture_name, gl::TEXTURE_BASE_LEVEL, (level - 1) as u32);
+                        gl.texture_parameteri(hiz.texture_name, gl::TEXTURE_MAX_LEVEL, (level - 1) as u32);
+                        gl.bind_texture_unit(DEPTH_SAMPLER_BINDING, hiz.texture_name);
+                    }
+
+                    gl.named_framebuffer_texture(hiz.framebuffer_name, gl::COLOR_ATTACHMENT0, hiz.texture_name, level);
+
+                    let dimensions = hiz.level_dimensions(level);
+                    gl.viewport(0, 0, dimensions.x, dimensions.y);
+
+                    gl.draw_elements(
+                        gl::TRIANGLES,
+                        (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+                        gl::UNSIGNED_INT,
+                        0,
+                    );
+                }
+
+                gl.texture_parameteri(hiz.texture_name, gl::TEXTURE_BASE_LEVEL, 0u32);
+                gl.texture_parameteri(hiz.texture_name, gl::TEXTURE_MAX_LEVEL, (hiz.level_count - 1) as u32);
+
+                gl.unbind_vertex_array();
+                gl.unuse_program();
+
+                gl.depth_mask(gl::WriteMask::Enabled);
+                gl.enable(gl::DEPTH_TEST);
+
+                hiz.wld_to_clp = Some(main_resources.camera.wld_to_clp);
+            }
+        }
+
+        self.profiling_context.stop(gl, profiler_index);
+    }
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        Renderer {
+            program: vs_fs_program(context, "overlay_renderer.vert", "hiz_renderer.frag", fixed_header()),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Split sum image based lighting, see "Real Shading in Unreal Engine 4" by
+//! Karis. A procedural sky is convolved into a prefiltered cube map with one
+//! roughness per mip level and an irradiance cube map, accompanied by a lookup
+//! table of the integrated specular BRDF. The textures are generated on the GPU
+//! whenever the configuration or one of the programs changes.
+
+use crate::*;
+
+const PREFILTERED_DIMENSIONS: i32 = 128;
+const PREFILTERED_LEVEL_COUNT: i32 = 6;
+const IRRADIANCE_DIMENSIONS: i32 = 32;
+const BRDF_LUT_DIMENSIONS: i32 = 128;
+
+pub struct Renderer {
+    pub prefilter_program: rendering::Program,
+    pub irradiance_program: rendering::Program,
+    pub brdf_lut_program: rendering::Program,
+    pub framebuffer_name: gl::FramebufferName,
+    pub prefiltered_texture_name: gl::TextureName,
+    pub irradiance_texture_name: gl::TextureName,
+    pub brdf_lut_texture_name: gl::TextureName,
+    /// Configuration the textures were last generated with.
+    pub generated: Option<configuration::IblConfiguration>,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {},
+    uniforms: {
+        SKY_ZENITH_COLOR_LOC = 0;
+        SKY_HORIZON_COLOR_LOC = 1;
+        SKY_GROUND_COLOR_LOC = 2;
+        SAMPLE_COUNT_LOC = 3;
+        ROUGHNESS_LOC = 4;
+    },
+});
+
+impl Context<'_> {
+    /// Regenerates the lighting textures when necessary.
+    pub fn update_ibl(&mut self) {
+        let Context {
+            ref gl,
+            ref resources,
+            ref configuration,
+            ibl_renderer: ref mut renderer,
+            ..
+        } = *self;
+
+        let cfg = &configuration.ibl;
+
+        let updated = renderer.prefilter_program.update(&mut rendering_context!(self))
+            | renderer.irradiance_program.update(&mut rendering_context!(self))
+            | renderer.brdf_lut_program.update(&mut rendering_context!(self));
+
+        if !cfg.enabled || (!updated && renderer.generated.as_ref() == Some(cfg)) {
+            return;
+        }
+
+        unsafe {
+            if let (
+                &ProgramName::Linked(prefilter_program),
+                &ProgramName::Linked(irradiance_program),
+                &ProgramName::Linked(brdf_lut_program),
+            ) = (
+                &renderer.prefilter_program.name,
+                &renderer.irradiance_program.name,
+                &renderer.brdf_lut_program.name,
+            ) {
+                gl.disable(gl::DEPTH_TEST);
+                gl.disable(gl::BLEND);
+                gl.disable(gl::CULL_FACE);
+                gl.depth_mask(gl::WriteMask::Disabled);
+                gl.bind_vertex_array(resources.full_screen_vao);
+                gl.bind_framebuffer(gl::FRAMEBUFFER, renderer.framebuffer_name);
+
+                let draw = |texture_name: gl::TextureName, level: i32, dimensions: i32| {
+                    gl.named_framebuffer_texture(renderer.framebuffer_name, gl::COLOR_ATTACHMENT0, texture_name, level);
+                    gl.viewport(0, 0, dimensions, dimensions);
+                    gl.draw_elements(
+                        gl::TRIANGLES,
+                        (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+                        gl::UNSIGNED_INT,
+                        0,
+                    );
+                };
+
+                let set_sky_uniforms = || {
+                    gl.uniform_3f(SKY_ZENITH_COLOR_LOC, cfg.sky.zenith_color);
+                    gl.uniform_3f(SKY_HORIZON_COLOR_LOC, cfg.sky.horizon_color);
+                    gl.uniform_3f(SKY_GROUND_COLOR_LOC, cfg.sky.ground_color);
+                    gl.uniform_1ui(SAMPLE_COUNT_LOC, cfg.sample_count);
+                };
+
+                gl.use_program(prefilter_program);
+                set_sky_uniforms();
+                for level in 0..PREFILTERED_LEVEL_COUNT {
+                    gl.uniform_1f(ROUGHNESS_LOC, level as f32 / (PREFILTERED_LEVEL_COUNT - 1) as f32);
+                    draw(
+                        renderer.prefiltered_texture_name,
+                        level,
+                        PREFILTERED_DIMENSIONS >> level,
+                    );
+                }
+
+                gl.use_program(irradiance_program);
+                set_sky_uniforms();
+                draw(renderer.irradiance_texture_name, 0, IRRADIANCE_DIMENSIONS);
+
+                gl.use_program(brdf_lut_program);
+                gl.uniform_1ui(SAMPLE_COUNT_LOC, cfg.sample_count);
+                draw(renderer.brdf_lut_texture_name, 0, BRDF_LUT_DIMENSIONS);
+
+                gl.unuse_program();
+                gl.unbind_vertex_array();
+                gl.depth_mask(gl::WriteMask::Enabled);
+                gl.enable(gl::CULL_FACE);
+                gl.enable(gl::DEPTH_TEST);
+
+                renderer.generated = Some(cfg.clone());
+            }
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        fn create_cube_face_program(context: &mut RenderingContext, fs: &'static str) -> rendering::Program {
+            rendering::Program::new(
+                context.gl,
+                [
+                    (gl::VERTEX_SHADER, "ibl_cube_face.vert"),
+                    (gl::GEOMETRY_SHADER, "ibl_cube_face.geom"),
+                    (gl::FRAGMENT_SHADER, fs),
+                ]
+                .iter()
+                .map(|&(kind, path)| {
+                    Shader::new(
+                        context.gl,
+                        kind,
+                        EntryPoint::new(&mut shader_compilation_context!(context), path, fixed_header()),
+                    )
+                })
+                .collect(),
+            )
+        }
+
+        let gl = context.gl;
+
+        unsafe {
+            let create_cube_map = |level_count: i32, dimensions: i32| {
+                let name = gl.create_texture(gl::TEXTURE_CUBE_MAP);
+                gl.texture_storage_2d(name, level_count, gl::RGBA16F, dimensions, dimensions);
+                gl.texture_parameteri(name, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR);
+                gl.texture_parameteri(name, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
+                name
+            };
+
+            let prefiltered_texture_name = create_cube_map(PREFILTERED_LEVEL_COUNT, PREFILTERED_DIMENSIONS);
+            let irradiance_texture_name = create_cube_map(1, IRRADIANCE_DIMENSIONS);
+
+            let brdf_lut_texture_name = gl.create_texture(gl::TEXTURE_2D);
+            gl.texture_storage_2d(
+                brdf_lut_texture_name,
+                1,
+                gl::RG16F,
+                BRDF_LUT_DIMENSIONS,
+                BRDF_LUT_DIMENSIONS,
+            );
+            gl.texture_parameteri(brdf_lut_texture_name, gl::TEXTURE_MIN_FILTER, gl::LINEAR);
+            gl.texture_parameteri(brdf_lut_texture_name, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
+            gl.texture_parameteri(brdf_lut_texture_name, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE);
+            gl.texture_parameteri(brdf_lut_texture_name, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE);
+
+            Renderer {
+                prefilter_program: create_cube_face_program(context, "ibl_prefilter.frag"),
+                irradiance_program: create_cube_face_program(context, "ibl_irradiance.frag"),
+                brdf_lut_program: vs_fs_program(context, "overlay_renderer.vert", "ibl_brdf_lut.frag", fixed_header()),
+                framebuffer_name: gl.create_framebuffer(),
+                prefiltered_texture_name,
+                irradiance_texture_name,
+                brdf_lut_texture_name,
+                generated: None,
+            }
+        }
+    }
+}
@@ -7,9 +7,11 @@ mod create_window;
 mod flatten;
 pub mod range;
 pub mod frustum;
+pub mod mesh_simplification;
 pub mod plane;
 pub mod profiling;
 pub mod profiling_by_value;
+pub mod recording;
 pub mod scene_file;
 
 pub use as_bytes::*;
@@ -46,6 +46,59 @@ pub struct PointLight {
     pub attenuation: AttenCoefs<f32>,
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct SpotLight {
+    pub tint: [f32; 3],
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub inner_angle: Rad<f32>,
+    pub outer_angle: Rad<f32>,
+    pub attenuation: AttenCoefs<f32>,
+}
+
+impl SpotLight {
+    pub fn from_configuration(value: &configuration::SpotLight, attenuation: AttenCoefs<f32>) -> Self {
+        Self {
+            tint: value.tint,
+            position: value.position.cast().unwrap(),
+            direction: value.direction.cast::<f32>().unwrap().normalize(),
+            inner_angle: Deg(value.inner_angle as f32).into(),
+            outer_angle: Deg(value.outer_angle as f32).into(),
+            attenuation,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AreaLightShape {
+    /// Spans `position ± axis_0 ± axis_1`. The axes should be perpendicular.
+    /// One-sided rectangles emit along the unit `normal`, two-sided
+    /// rectangles have no normal.
+    Rectangle {
+        axis_0: Vector3<f32>,
+        axis_1: Vector3<f32>,
+        normal: Option<Vector3<f32>>,
+    },
+    /// Spans `position ± axis`.
+    Tube { axis: Vector3<f32> },
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AreaLight {
+    pub tint: [f32; 3],
+    pub position: Point3<f32>,
+    pub shape: AreaLightShape,
+    pub attenuation: AttenCoefs<f32>,
+}
+
+pub const LIGHT_KIND_POINT: u32 = 0;
+pub const LIGHT_KIND_SPOT: u32 = 1;
+pub const LIGHT_KIND_RECTANGLE: u32 = 2;
+pub const LIGHT_KIND_TUBE: u32 = 3;
+
+/// Shadow index of lights without a tile in the shadow atlas.
+pub const SHADOW_INDEX_NONE: u32 = !0;
+
 pub struct LightSampleIndices {
     pub total: profiling::SampleIndex,
     pub compute: profiling::SampleIndex,
@@ -80,6 +133,21 @@ pub struct LightBufferLight {
     pub normal: u32,
 
     pub attenuation: AttenCoefs<f32>,
+
+    // Omnidirectional lights have cos_outer = -1.
+    pub direction: [f32; 3],
+    pub cos_outer: f32,
+
+    // Half extents of area lights, zero for other lights.
+    pub axis_0: [f32; 3],
+    pub cos_inner: f32,
+
+    pub axis_1: [f32; 3],
+    pub kind: u32,
+
+    // Cube map index in the shadow atlas or SHADOW_INDEX_NONE.
+    pub shadow_index: u32,
+    pub _pad1: [u32; 3],
 }
 
 impl LightBufferLight {
@@ -97,6 +165,217 @@ impl LightBufferLight {
             normal: 0,
 
             attenuation,
+
+            direction: [0.0; 3],
+            cos_outer: -1.0,
+
+            axis_0: [0.0; 3],
+            cos_inner: -1.0,
+
+            axis_1: [0.0; 3],
+            kind: LIGHT_KIND_POINT,
+
+            shadow_index: SHADOW_INDEX_NONE,
+            _pad1: Default::default(),
+        }
+    }
+
+    pub fn from_spot_light(spot_light: SpotLight) -> Self {
+        let SpotLight {
+            tint,
+            position,
+            direction,
+            inner_angle,
+            outer_angle,
+            attenuation,
+        } = spot_light;
+        let cos_outer = outer_angle.cos();
+        Self {
+            tint,
+            _pad0: 0.0,
+
+            position: position.into(),
+            normal: 0,
+
+            attenuation,
+
+            direction: direction.into(),
+            cos_outer,
+
+            axis_0: [0.0; 3],
+            // Keep the falloff range non-empty for smoothstep.
+            cos_inner: f32::max(inner_angle.cos(), cos_outer + 0.0001),
+
+            axis_1: [0.0; 3],
+            kind: LIGHT_KIND_SPOT,
+
+            shadow_index: SHADOW_INDEX_NONE,
+            _pad1: Default::default(),
+        }
+    }
+
+    pub fn from_area_light(area_light: AreaLight) -> Self {
+        let AreaLight {
+            tint,
+            position,
+            shape,
+            attenuation,
+        } = area_light;
+        let (kind, axis_0, axis_1) = match shape {
+            AreaLightShape::Rectangle { axis_0, axis_1, .. } => (LIGHT_KIND_RECTANGLE, axis_0, axis_1),
+            AreaLightShape::Tube { axis } => (LIGHT_KIND_TUBE, axis, Vector3::zero()),
+        };
+        let (direction, cos_outer, cos_inner) = match shape {
+            // One-sided rectangles are spot lights with a 90 degree outer
+            // angle. The direction is also the plane normal used in shading.
+            AreaLightShape::Rectangle {
+                normal: Some(normal), ..
+            } => (normal, 0.0, 0.0001),
+            AreaLightShape::Rectangle {
+                axis_0,
+                axis_1,
+                normal: None,
+            } => (axis_0.cross(axis_1).normalize(), -1.0, -1.0),
+            AreaLightShape::Tube { .. } => (Vector3::zero(), -1.0, -1.0),
+        };
+        Self {
+            tint,
+            _pad0: 0.0,
+
+            position: position.into(),
+            normal: 0,
+
+            attenuation,
+
+            direction: direction.into(),
+            cos_outer,
+
+            axis_0: axis_0.into(),
+            cos_inner,
+
+            axis_1: axis_1.into(),
+            kind,
+
+            shadow_index: SHADOW_INDEX_NONE,
+            _pad1: Default::default(),
+        }
+    }
+
+    /// Radius of the bounding sphere, including the area light extents.
+    pub fn radius(&self) -> f32 {
+        self.attenuation.r1 + (Vector3::from(self.axis_0).magnitude2() + Vector3::from(self.axis_1).magnitude2()).sqrt()
+    }
+
+    /// Approximates the fraction of the view covered by the light's sphere of
+    /// influence. Lights behind the viewer score 0.
+    pub fn shadow_importance(&self, cam_pos: Point3<f32>, cam_dir: Vector3<f32>) -> f32 {
+        let r = self.radius();
+        let cam_to_lgt = Point3::from(self.position) - cam_pos;
+        if cam_to_lgt.dot(cam_dir) < -r {
+            return 0.0;
+        }
+        let d = f32::max(cam_to_lgt.magnitude(), r);
+        (r / d) * (r / d)
+    }
+}
+
+/// Gives the `count` most important lights a tile in the shadow atlas.
+/// Returns the light indices in tile order.
+pub fn assign_shadow_indices(
+    lights: &mut [LightBufferLight],
+    cam_pos: Point3<f32>,
+    cam_dir: Vector3<f32>,
+    count: u32,
+) -> Vec<usize> {
+    let mut scored: Vec<(usize, f32)> = lights
+        .iter()
+        .enumerate()
+        .map(|(index, light)| (index, light.shadow_importance(cam_pos, cam_dir)))
+        .filter(|&(_, importance)| importance > 0.0)
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+    scored.truncate(count as usize);
+
+    for light in lights.iter_mut() {
+        light.shadow_index = SHADOW_INDEX_NONE;
+    }
+
+    scored
+        .into_iter()
+        .enumerate()
+        .map(|(tile, (index, _))| {
+            lights[index].shadow_index = tile as u32;
+            index
+        })
+        .collect()
+}
+
+pub struct ShadowAtlas {
+    /// Number of cube maps in the shadow atlas.
+    pub capacity: u32,
+    pub dimensions: u32,
+    pub framebuffer_name: gl::NonDefaultFramebufferName,
+    pub depth_texture_name: gl::TextureName,
+    pub distance_texture_name: gl::TextureName,
+}
+
+impl ShadowAtlas {
+    pub fn new(gl: &gl::Gl, capacity: u32, dimensions: u32) -> Self {
+        unsafe {
+            let framebuffer_name = gl.create_framebuffer();
+
+            // Without tiles a single cube map is still allocated so the
+            // sampler can be bound.
+            let create_texture = |format: gl::InternalFormat| {
+                let name = gl.create_texture(gl::TEXTURE_CUBE_MAP_ARRAY);
+                gl.texture_storage_3d(
+                    name,
+                    1,
+                    format,
+                    dimensions as i32,
+                    dimensions as i32,
+                    6 * std::cmp::max(1, capacity) as i32,
+                );
+                gl.texture_parameteri(name, gl::TEXTURE_MAX_LEVEL, 0u32);
+                gl.texture_parameteri(name, gl::TEXTURE_MIN_FILTER, gl::NEAREST);
+                gl.texture_parameteri(name, gl::TEXTURE_MAG_FILTER, gl::NEAREST);
+                name
+            };
+
+            let depth_texture_name = create_texture(gl::DEPTH_COMPONENT32.into());
+            let distance_texture_name = create_texture(gl::R32F.into());
+
+            gl.named_framebuffer_texture(framebuffer_name, gl::DEPTH_ATTACHMENT, depth_texture_name, 0);
+            gl.named_framebuffer_texture(framebuffer_name, gl::COLOR_ATTACHMENT0, distance_texture_name, 0);
+            gl.named_framebuffer_draw_buffers(framebuffer_name, &[gl::COLOR_ATTACHMENT0.into()]);
+
+            Self {
+                capacity,
+                dimensions,
+                framebuffer_name,
+                depth_texture_name,
+                distance_texture_name,
+            }
+        }
+    }
+
+    /// Reallocates the atlas when the configuration changed.
+    pub fn reconcile(&mut self, gl: &gl::Gl, capacity: u32, dimensions: u32) {
+        if self.capacity != capacity || self.dimensions != dimensions {
+            std::mem::replace(self, ShadowAtlas::new(gl, capacity, dimensions)).drop(gl);
+        }
+    }
+
+    pub fn drop(self, gl: &gl::Gl) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer_name);
+            gl.delete_texture(self.depth_texture_name);
+            gl.delete_texture(self.distance_texture_name);
         }
     }
 }
@@ -113,6 +392,10 @@ pub struct LightResources {
     pub tint_texture: gl::TextureName,
     pub shadow_map_profiler: profiling::SampleIndex,
     pub virtual_light_profiler: profiling::SampleIndex,
+    pub shadow_atlas: ShadowAtlas,
+    /// Light indices in shadow atlas tile order.
+    pub shadow_lights: Vec<usize>,
+    pub shadow_atlas_profiler: profiling::SampleIndex,
 }
 
 impl LightResources {
@@ -157,6 +440,8 @@ impl LightResources {
                 ],
             );
 
+            let shadow_atlas = ShadowAtlas::new(gl, cfg.light.shadows.atlas_lights, cfg.light.shadows.atlas_dimensions);
+
             Self {
                 buffer_ring: Ring3::new(|| StorageBuffer::new(gl)),
                 header: Default::default(),
@@ -169,6 +454,9 @@ impl LightResources {
                 tint_texture,
                 shadow_map_profiler: profiling_context.add_sample("shadow map"),
                 virtual_light_profiler: profiling_context.add_sample("place VPL"),
+                shadow_atlas,
+                shadow_lights: Vec::new(),
+                shadow_atlas_profiler: profiling_context.add_sample("shadow atlas"),
             }
         }
     }
@@ -179,7 +467,12 @@ impl LightResources {
         profiling_context: &mut profiling::ProfilingContext,
         frame_index: FrameIndex,
         point_lights: &[PointLight],
+        spot_lights: &[SpotLight],
+        area_lights: &[AreaLight],
         virtual_light_count: u32,
+        cam_pos_in_lgt: Point3<f32>,
+        cam_dir_in_lgt: Vector3<f32>,
+        shadow_atlas_lights: u32,
     ) {
         let profiler_index = profiling_context.start(gl, self.sample_indices.total);
 
@@ -187,7 +480,10 @@ impl LightResources {
             let profiler_index = profiling_context.start(gl, self.sample_indices.compute);
 
             self.header = light::LightBufferHeader {
-                light_count: std::convert::TryFrom::try_from(point_lights.len()).unwrap(),
+                light_count: std::convert::TryFrom::try_from(
+                    point_lights.len() + spot_lights.len() + area_lights.len(),
+                )
+                .unwrap(),
                 virtual_light_count,
                 _pad0: Default::default(),
             };
@@ -195,6 +491,17 @@ impl LightResources {
             self.body.clear();
             self.body
                 .extend(point_lights.iter().copied().map(LightBufferLight::from_point_light));
+            self.body
+                .extend(spot_lights.iter().copied().map(LightBufferLight::from_spot_light));
+            self.body
+                .extend(area_lights.iter().copied().map(LightBufferLight::from_area_light));
+
+            self.shadow_lights = assign_shadow_indices(
+                &mut self.body,
+                cam_pos_in_lgt,
+                cam_dir_in_lgt,
+                std::cmp::min(shadow_atlas_lights, self.shadow_atlas.capacity),
+            );
 
             profiling_context.stop(gl, profiler_index);
         }
@@ -218,3 +525,39 @@ impl LightResources {
         profiling_context.stop(gl, profiler_index);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point_light(position: Point3<f32>, r1: f32) -> LightBufferLight {
+        LightBufferLight::from_point_light(PointLight {
+            tint: [1.0; 3],
+            position,
+            attenuation: AttenCoefs {
+                i: 1.0,
+                i0: 0.01,
+                r0: 0.1,
+                r1,
+            },
+        })
+    }
+
+    #[test]
+    fn shadow_indices_prefer_close_lights_in_view() {
+        let mut lights = vec![
+            point_light(Point3::new(0.0, 0.0, -20.0), 2.0),
+            point_light(Point3::new(0.0, 0.0, 20.0), 2.0),
+            point_light(Point3::new(0.0, 0.0, -5.0), 2.0),
+            point_light(Point3::new(0.0, 0.0, -10.0), 2.0),
+        ];
+
+        let shadow_lights = assign_shadow_indices(&mut lights, Point3::origin(), -Vector3::unit_z(), 2);
+
+        assert_eq!(shadow_lights, vec![2, 3]);
+        assert_eq!(lights[2].shadow_index, 0);
+        assert_eq!(lights[3].shadow_index, 1);
+        assert_eq!(lights[0].shadow_index, SHADOW_INDEX_NONE);
+        assert_eq!(lights[1].shadow_index, SHADOW_INDEX_NONE);
+    }
+}
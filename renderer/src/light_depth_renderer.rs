@@ -44,6 +44,57 @@ mod compute {
     });
 }
 
+/// Computes the world to clip space transforms for the 6 faces of a cube map
+/// centered at `position`, in the face order OpenGL expects.
+pub fn cube_map_wld_to_clp_array(position: Point3<f32>, attenuation: light::AttenCoefs<f32>) -> [[[f32; 4]; 4]; 6] {
+    let cam_to_clp = Frustum {
+        x0: -1.0,
+        x1: 1.0,
+        y0: -1.0,
+        y1: 1.0,
+        z0: -attenuation.r1 as f64,
+        z1: -attenuation.r0 as f64 * 0.5_f64.sqrt(),
+    }
+    .perspective(&super::RENDER_RANGE);
+
+    let wld_to_cam = Matrix4::from_translation(-position.to_vec().cast::<f64>().unwrap());
+
+    [
+        // +X: s = -Z, t = -Y
+        (cam_to_clp
+            * Matrix4::from_angle_y(Rad::turn_div_4())
+            * Matrix4::from_angle_x(-Rad::turn_div_2())
+            * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+        // -X: s = Z, t = -Y
+        (cam_to_clp
+            * Matrix4::from_angle_y(-Rad::turn_div_4())
+            * Matrix4::from_angle_x(-Rad::turn_div_2())
+            * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+        (cam_to_clp * Matrix4::from_angle_x(-Rad::turn_div_4()) * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+        (cam_to_clp * Matrix4::from_angle_x(Rad::turn_div_4()) * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+        (cam_to_clp * Matrix4::from_angle_x(-Rad::turn_div_2()) * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+        (cam_to_clp * Matrix4::from_angle_z(-Rad::turn_div_2()) * wld_to_cam)
+            .cast()
+            .unwrap()
+            .into(),
+    ]
+}
+
 pub struct Parameters {
     pub draw_resources_index: usize,
 }
@@ -85,57 +136,9 @@ impl Context<'_> {
 
                 gl.bind_vertex_array(resources.scene_vao);
 
-                let draw_counts = &draw_resources.draw_counts;
-                let draw_offsets = &draw_resources.draw_offsets;
+                let light = self.light_resources.body[0];
 
-                let light = self.point_lights[0];
-
-                let cam_to_clp = Frustum {
-                    x0: -1.0,
-                    x1: 1.0,
-                    y0: -1.0,
-                    y1: 1.0,
-                    z0: -light.attenuation.r1 as f64,
-                    z1: -light.attenuation.r0 as f64 * 0.5_f64.sqrt(),
-                }
-                .perspective(&super::RENDER_RANGE);
-
-                let wld_to_cam = Matrix4::from_translation(-light.position.to_vec().cast::<f64>().unwrap());
-
-                let wld_to_clp_array: [[[f32; 4]; 4]; 6] = [
-                    // +X: s = -Z, t = -Y
-                    (cam_to_clp
-                        * Matrix4::from_angle_y(Rad::turn_div_4())
-                        * Matrix4::from_angle_x(-Rad::turn_div_2())
-                        * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                    // -X: s = Z, t = -Y
-                    (cam_to_clp
-                        * Matrix4::from_angle_y(-Rad::turn_div_4())
-                        * Matrix4::from_angle_x(-Rad::turn_div_2())
-                        * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                    (cam_to_clp * Matrix4::from_angle_x(-Rad::turn_div_4()) * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                    (cam_to_clp * Matrix4::from_angle_x(Rad::turn_div_4()) * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                    (cam_to_clp * Matrix4::from_angle_x(-Rad::turn_div_2()) * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                    (cam_to_clp * Matrix4::from_angle_z(-Rad::turn_div_2()) * wld_to_cam)
-                        .cast()
-                        .unwrap()
-                        .into(),
-                ];
+                let wld_to_clp_array = cube_map_wld_to_clp_array(Point3::from(light.position), light.attenuation);
 
                 for &(program, material_kind) in [
                     (opaque_program, resources::MaterialKind::Opaque),
@@ -167,13 +170,7 @@ impl Context<'_> {
                         // gl.bind_texture_unit(SPECULAR_SAMPLER_BINDING, textures[material.specular_texture_index].name);
 
                         // Execute draw.
-                        gl.multi_draw_elements_indirect(
-                            gl::TRIANGLES,
-                            gl::UNSIGNED_INT,
-                            draw_offsets[material_index] as usize * std::mem::size_of::<DrawCommand>(),
-                            draw_counts[material_index] as i32,
-                            std::mem::size_of::<DrawCommand>() as i32,
-                        );
+                        draw_resources.multi_draw(gl, material_index);
                     }
                 }
 
@@ -37,33 +37,38 @@ impl Context<'_> {
                     light_resources.buffer_ring[self.frame_index.to_usize()].name(),
                 );
 
-                let camera = &self.main_resources_pool[params.main_resources_index].camera;
+                let main_resources = &self.main_resources_pool[params.main_resources_index];
 
                 gl.bind_vertex_array(self.resources.quad_vao);
                 gl.use_program(program);
-                gl.uniform_matrix4f(
-                    WLD_TO_CAM_LOC,
-                    gl::MajorAxis::Column,
-                    camera.wld_to_cam.cast().unwrap().as_ref(),
-                );
-                gl.uniform_matrix4f(
-                    CAM_TO_CLP_LOC,
-                    gl::MajorAxis::Column,
-                    camera.cam_to_clp.cast().unwrap().as_ref(),
-                );
 
                 gl.depth_mask(gl::FALSE);
                 gl.enable(gl::BLEND);
                 gl.blend_func(gl::SRC_ALPHA, gl::ONE);
 
-                gl.draw_elements_instanced_base_vertex(
-                    gl::TRIANGLES,
-                    6,
-                    gl::UNSIGNED_INT,
-                    0,
-                    light_resources.header.light_count as u32,
-                    0,
-                );
+                for (viewport, camera) in main_resources.views() {
+                    viewport.set(gl);
+                    gl.uniform_matrix4f(
+                        WLD_TO_CAM_LOC,
+                        gl::MajorAxis::Column,
+                        camera.wld_to_cam.cast().unwrap().as_ref(),
+                    );
+                    gl.uniform_matrix4f(
+                        CAM_TO_CLP_LOC,
+                        gl::MajorAxis::Column,
+                        camera.cam_to_clp.cast().unwrap().as_ref(),
+                    );
+
+                    gl.draw_elements_instanced_base_vertex(
+                        gl::TRIANGLES,
+                        6,
+                        gl::UNSIGNED_INT,
+                        0,
+                        light_resources.header.light_count as u32,
+                        0,
+                    );
+                }
+                Viewport::from_dimensions(main_resources.framebuffer.dimensions).set(gl);
 
                 gl.depth_mask(gl::TRUE);
                 gl.disable(gl::BLEND);
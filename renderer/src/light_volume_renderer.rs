@@ -34,13 +34,6 @@ impl Context<'_> {
                     self.light_resources.buffer_ring[self.frame_index.to_usize()].name(),
                 );
 
-                let wld_to_clp = &self.main_resources_pool[params.main_resources_index].camera.wld_to_clp;
-                self.gl.uniform_matrix4f(
-                    WLD_TO_CLP_LOC,
-                    gl::MajorAxis::Column,
-                    wld_to_clp.cast::<f32>().unwrap().as_ref(),
-                );
-
                 self.gl.uniform_1f(
                     OPACITY_LOC,
                     self.configuration.light.volume_opacity,
@@ -49,7 +42,19 @@ impl Context<'_> {
                 self.gl.depth_mask(gl::FALSE);
                 self.gl.enable(gl::BLEND);
                 self.gl.blend_func(gl::SRC_ALPHA, gl::ONE);
-                self.resources.icosphere1280.draw_instances(&self.gl, self.point_lights.len() as u32);
+                let main_resources = &self.main_resources_pool[params.main_resources_index];
+                for (viewport, camera) in main_resources.views() {
+                    viewport.set(&self.gl);
+                    self.gl.uniform_matrix4f(
+                        WLD_TO_CLP_LOC,
+                        gl::MajorAxis::Column,
+                        camera.wld_to_clp.cast::<f32>().unwrap().as_ref(),
+                    );
+                    self.resources
+                        .icosphere1280
+                        .draw_instances(&self.gl, self.light_resources.header.light_count);
+                }
+                Viewport::from_dimensions(main_resources.framebuffer.dimensions).set(&self.gl);
                 self.gl.depth_mask(gl::TRUE);
                 self.gl.disable(gl::BLEND);
 
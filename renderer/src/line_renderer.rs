@@ -16,6 +16,8 @@ pub struct Parameters<'a> {
     pub indices: &'a [[u32; 2]],
     pub obj_to_clp: &'a Matrix4<f64>,
     pub color: [f32; 3],
+    /// Screen space lines like the overlay graphs are drawn on top of everything.
+    pub depth_test: bool,
 }
 
 glsl_defines!(fixed_header {
@@ -64,9 +66,14 @@ impl Renderer {
 
                 gl.bind_vertex_array(self.vertex_array_name);
 
-                gl.enable(gl::DEPTH_TEST);
-                gl.depth_func(gl::GREATER);
-                gl.depth_mask(gl::WriteMask::Enabled);
+                if params.depth_test {
+                    gl.enable(gl::DEPTH_TEST);
+                    gl.depth_func(gl::GREATER);
+                    gl.depth_mask(gl::WriteMask::Enabled);
+                } else {
+                    gl.disable(gl::DEPTH_TEST);
+                    gl.depth_mask(gl::WriteMask::Disabled);
+                }
 
                 gl.draw_elements(
                     gl::LINES,
@@ -66,7 +66,9 @@ macro_rules! impl_enum_map {
             $variant: ident => $field: ident,
         )* }
     ) => {
-        #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        #[derive(
+            Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize,
+        )]
         pub struct $Map<T> {
             $(
                 pub $field: T,
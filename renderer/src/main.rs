@@ -23,12 +23,16 @@ pub mod cgmath_ext;
 mod cls;
 pub mod color;
 mod cube_mesh;
+mod cull_renderer;
 mod dds_ext;
 mod depth_renderer;
 mod filters;
+mod foveation_renderer;
 mod frame_downloader;
 pub mod gl_ext;
 mod glutin_ext;
+mod hiz_renderer;
+mod ibl_renderer;
 mod icosphere1280;
 mod keyboard;
 mod light;
@@ -40,10 +44,13 @@ mod main_resources;
 mod math;
 mod overlay_renderer;
 mod pool;
+mod post_renderer;
 mod rain;
 mod rendering;
 mod resources;
+mod sample_graphs;
 mod shader_compiler;
+mod shadow_atlas_renderer;
 mod symlink;
 mod text_renderer;
 mod text_rendering;
@@ -79,6 +86,7 @@ use openvr as vr;
 use renderer::camera;
 use renderer::plane::Plane3;
 use renderer::profiling::*;
+use renderer::recording::{RecordingHeader, RecordingReader, RecordingWriter};
 use renderer::*;
 use std::fs;
 use std::io;
@@ -138,7 +146,7 @@ impl_enum_map! {
 
 pub const EYE_KEYS: [Eye; 2] = [Eye::Left, Eye::Right];
 
-#[derive(Debug, Clone, Copy)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct FrustumTangents {
     x0: f64,
     x1: f64,
@@ -271,11 +279,28 @@ impl WindowEvent {
     }
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct VrEyePose {
+    pub cam_to_hmd: Matrix4<f64>,
+    pub tangents: FrustumTangents,
+}
+
+/// What the renderer reads from the headset every frame. Recorded after the
+/// other events of the frame, replaying it renders the stereo path as if the
+/// headset were present.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct VrPose {
+    pub win_size: Vector2<i32>,
+    pub hmd_to_bdy: Matrix4<f64>,
+    pub eyes: EyeMap<VrEyePose>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 pub enum FrameEvent {
     WindowEvent(WindowEvent),
     DeviceKey(glutin::KeyboardInput),
     DeviceMotion { axis: glutin::AxisId, value: f64 },
+    VrPose(VrPose),
 }
 
 type FrameEvents = Vec<FrameEvent>;
@@ -312,7 +337,7 @@ pub struct MainContext {
     pub fs_watcher: notify::RecommendedWatcher,
     pub fs_rx: mpsc::Receiver<notify::DebouncedEvent>,
 
-    pub record_file: Option<io::BufWriter<fs::File>>,
+    pub record_file: Option<RecordingWriter<io::BufWriter<fs::File>>>,
     pub current: ::incremental::Current,
     pub shader_compiler: ShaderCompiler,
     pub profiling_context: ProfilingContext,
@@ -328,6 +353,7 @@ pub struct MainContext {
     // Renderers
     pub depth_renderer: depth_renderer::Renderer,
     pub light_depth_renderer: light_depth_renderer::Renderer,
+    pub shadow_atlas_renderer: shadow_atlas_renderer::Renderer,
     pub line_renderer: line_renderer::Renderer,
     pub basic_renderer: basic_renderer::Renderer,
     pub light_renderer: light_renderer::Renderer,
@@ -336,6 +362,11 @@ pub struct MainContext {
     pub cluster_renderer: cluster_renderer::Renderer,
     pub text_renderer: text_renderer::Renderer,
     pub cls_renderer: cls_renderer::Renderer,
+    pub cull_renderer: cull_renderer::Renderer,
+    pub hiz_renderer: hiz_renderer::Renderer,
+    pub post_renderer: post_renderer::Renderer,
+    pub foveation_renderer: foveation_renderer::Renderer,
+    pub ibl_renderer: ibl_renderer::Renderer,
 
     // More opengl resources...
     pub resources: Resources,
@@ -422,7 +453,13 @@ impl MainContext {
                     _ => Err(error),
                 })
                 .unwrap();
-            symlink_dir(&current_profiling_dir, &latest_profiling_dir).unwrap();
+            symlink_dir(&current_profiling_dir, &latest_profiling_dir)
+                .or_else(|error| match error.kind() {
+                    // Another renderer may have recreated it in the mean time.
+                    std::io::ErrorKind::AlreadyExists => Ok(()),
+                    _ => Err(error),
+                })
+                .unwrap();
 
             // FIXME(mickvangelderen): Don't do this because the profile binary writes the configuration here.
             // std::fs::copy(&configuration_path, current_profiling_dir.join("configuration.toml")).unwrap();
@@ -437,20 +474,47 @@ impl MainContext {
                 std::fs::create_dir_all(&frames_dir).unwrap();
             }
 
-            ProfilingContext::new(&gl, current_profiling_dir.as_path(), &configuration.profiling)
-        };
-
-        let mut record_file = match configuration.global.mode {
-            configuration::ApplicationMode::Record => Some(io::BufWriter::new(
-                fs::File::create(&configuration.record.path).unwrap(),
-            )),
-            _ => None,
+            let replay_path = match configuration.global.mode {
+                configuration::ApplicationMode::Replay => Some(configuration.replay.path.clone()),
+                _ => None,
+            };
+            let configuration_value = toml::Value::try_from(&configuration).unwrap();
+            RunMetadata {
+                started_at: chrono::Local::now().to_rfc3339(),
+                git_revision: profiling::run_metadata::git_revision(),
+                gl_vendor: unsafe { gl.get_string(gl::VENDOR) }.to_string(),
+                gl_renderer: unsafe { gl.get_string(gl::RENDERER) }.to_string(),
+                gl_version: unsafe { gl.get_string(gl::VERSION) }.to_string(),
+                scene_path: resource_dir.join(&configuration.global.scene_path),
+                // Opening the replay reports a missing or unreadable file.
+                replay_hash: replay_path
+                    .as_ref()
+                    .and_then(|path| match profiling::run_metadata::file_hash(path) {
+                        Ok(hash) => Some(hash),
+                        Err(error) => {
+                            warn!("Failed to hash {:?}: {}", path, error);
+                            None
+                        }
+                    }),
+                replay_path,
+                configuration: configuration_value.clone(),
+            }
+            .write(&current_profiling_dir)
+            .unwrap();
+
+            ProfilingContext::new(
+                &gl,
+                current_profiling_dir.as_path(),
+                &configuration.profiling,
+                configuration_value.to_string(),
+            )
         };
 
         let mut replay_file = match configuration.global.mode {
-            configuration::ApplicationMode::Replay => {
-                Some(io::BufReader::new(fs::File::open(&configuration.replay.path).unwrap()))
-            }
+            configuration::ApplicationMode::Replay => Some(
+                RecordingReader::<_, FrameEvents>::open(&configuration.replay.path, CameraKey::iter().len())
+                    .unwrap_or_else(|error| panic!("Failed to read {:?}: {}", &configuration.replay.path, error)),
+            ),
             _ => None,
         };
 
@@ -480,9 +544,10 @@ impl MainContext {
                 }
             };
 
-            match replay_file.as_mut() {
+            match replay_file.as_ref() {
                 Some(file) => {
-                    read_cameras(file, &mut initial_cameras);
+                    check_recording_header(&file.header, &configuration);
+                    initial_cameras = CameraMap::new(|key| file.header.cameras[key as usize].into());
                 }
                 None => {
                     match fs::File::open("state.bin") {
@@ -498,22 +563,18 @@ impl MainContext {
             }
         }
 
-        let replay_frame_events = replay_file.as_mut().map(|file| {
-            let mut accumulator = Vec::new();
-
-            while let Ok(events) = bincode::deserialize_from(&mut *file) {
-                accumulator.push(events);
-            }
+        let replay_frame_events = replay_file.as_mut().map(|file| file.read_frames().unwrap());
 
-            accumulator
-        });
-
-        if let Some(file) = record_file.as_mut() {
-            for key in CameraKey::iter() {
-                let camera = initial_cameras[key];
-                file.write_all(camera.value_as_bytes()).unwrap();
-            }
-        }
+        let record_file = match configuration.global.mode {
+            configuration::ApplicationMode::Record => Some(
+                RecordingWriter::create(
+                    &configuration.record.path,
+                    recording_header(&configuration, &initial_cameras),
+                )
+                .unwrap(),
+            ),
+            _ => None,
+        };
 
         let sans_serif = FontContext::new(&gl, resource_dir.join("fonts/OpenSans-Regular.fnt"));
         let monospace = FontContext::new(&gl, resource_dir.join("fonts/RobotoMono-Regular.fnt"));
@@ -528,10 +589,15 @@ impl MainContext {
                 attenuation_mode: AttenuationMode::PhyRed2,
                 prefix_sum: configuration.prefix_sum,
                 clustered_light_shading: configuration.clustered_light_shading,
-                profiling: shader_compiler::ProfilingVariables { time_sensitive: false },
+                profiling: shader_compiler::ProfilingVariables {
+                    time_sensitive: false,
+                    histogram_bin_count: profiling_context.histogram_bin_count(),
+                },
                 sample_count: configuration.global.sample_count,
                 depth_prepass: true,
+                tonemapper: configuration.post.tonemapper,
             },
+            profiling_context.main_thread().clone(),
         );
 
         let mut rendering_context = RenderingContext {
@@ -543,6 +609,7 @@ impl MainContext {
 
         let depth_renderer = depth_renderer::Renderer::new(&mut rendering_context);
         let light_depth_renderer = light_depth_renderer::Renderer::new(&mut rendering_context);
+        let shadow_atlas_renderer = shadow_atlas_renderer::Renderer::new(&mut rendering_context);
         let line_renderer = line_renderer::Renderer::new(&mut rendering_context);
         let basic_renderer = basic_renderer::Renderer::new(&mut rendering_context);
         let light_renderer = light_renderer::Renderer::new(&mut rendering_context);
@@ -551,11 +618,16 @@ impl MainContext {
         let cluster_renderer = cluster_renderer::Renderer::new(&mut rendering_context);
         let text_renderer = text_renderer::Renderer::new(&mut rendering_context);
         let cls_renderer = cls_renderer::Renderer::new(&mut rendering_context);
+        let cull_renderer = cull_renderer::Renderer::new(&mut rendering_context);
+        let hiz_renderer = hiz_renderer::Renderer::new(&mut rendering_context);
+        let post_renderer = post_renderer::Renderer::new(&mut rendering_context);
+        let foveation_renderer = foveation_renderer::Renderer::new(&mut rendering_context);
+        let ibl_renderer = ibl_renderer::Renderer::new(&mut rendering_context);
 
         drop(rendering_context);
 
-        let resources = resources::Resources::new(&gl, &resource_dir, &configuration);
-        let frame_downloader = FrameDownloader::new();
+        let resources = resources::Resources::new(&gl, &resource_dir, &configuration, profiling_context.main_thread());
+        let frame_downloader = FrameDownloader::new(&profiling_context.cpu_profiler());
 
         let initial_win_dpi = gl_window.get_hidpi_factor();
         let initial_win_size = gl_window.get_inner_size().unwrap().to_physical(initial_win_dpi);
@@ -589,6 +661,7 @@ impl MainContext {
             monospace,
             depth_renderer,
             light_depth_renderer,
+            shadow_atlas_renderer,
             line_renderer,
             basic_renderer,
             light_renderer,
@@ -597,6 +670,11 @@ impl MainContext {
             cluster_renderer,
             text_renderer,
             cls_renderer,
+            cull_renderer,
+            hiz_renderer,
+            post_renderer,
+            foveation_renderer,
+            ibl_renderer,
             resources,
             frame_downloader,
             sample_indices: MainSampleIndices::new(&mut profiling_context),
@@ -628,7 +706,7 @@ pub struct Context<'s> {
     pub vr: &'s mut Option<vr::Context>,
     pub fs_rx: &'s mut mpsc::Receiver<notify::DebouncedEvent>,
 
-    pub record_file: &'s mut Option<io::BufWriter<fs::File>>,
+    pub record_file: &'s mut Option<RecordingWriter<io::BufWriter<fs::File>>>,
     pub current: &'s mut ::incremental::Current,
     pub shader_compiler: &'s mut ShaderCompiler,
     pub profiling_context: &'s mut ProfilingContext,
@@ -641,6 +719,7 @@ pub struct Context<'s> {
     // Renderers
     pub depth_renderer: &'s mut depth_renderer::Renderer,
     pub light_depth_renderer: &'s mut light_depth_renderer::Renderer,
+    pub shadow_atlas_renderer: &'s mut shadow_atlas_renderer::Renderer,
     pub line_renderer: &'s mut line_renderer::Renderer,
     pub basic_renderer: &'s mut basic_renderer::Renderer,
     pub light_renderer: &'s mut light_renderer::Renderer,
@@ -649,6 +728,11 @@ pub struct Context<'s> {
     pub cluster_renderer: &'s mut cluster_renderer::Renderer,
     pub text_renderer: &'s mut text_renderer::Renderer,
     pub cls_renderer: &'s mut cls_renderer::Renderer,
+    pub cull_renderer: &'s mut cull_renderer::Renderer,
+    pub hiz_renderer: &'s mut hiz_renderer::Renderer,
+    pub post_renderer: &'s mut post_renderer::Renderer,
+    pub foveation_renderer: &'s mut foveation_renderer::Renderer,
+    pub ibl_renderer: &'s mut ibl_renderer::Renderer,
 
     // More opengl resources...
     pub resources: &'s mut Resources,
@@ -671,6 +755,12 @@ pub struct Context<'s> {
     pub focus: bool,
     pub tick: u64,
     pub event_index: usize,
+    /// Events of the current frame that still have to be written to the
+    /// recording.
+    pub recorded_frame_events: Vec<FrameEvent>,
+    /// The pose of the current frame when replaying a recording made with a
+    /// headset.
+    pub vr_pose: Option<VrPose>,
     pub frame_index: FrameIndex,
     pub keyboard_state: KeyboardState,
     pub export_frames: Option<ExportStopCondition>,
@@ -717,6 +807,7 @@ impl<'s> Context<'s> {
             ref mut monospace,
             ref mut depth_renderer,
             ref mut light_depth_renderer,
+            ref mut shadow_atlas_renderer,
             ref mut line_renderer,
             ref mut basic_renderer,
             ref mut light_renderer,
@@ -725,6 +816,11 @@ impl<'s> Context<'s> {
             ref mut cluster_renderer,
             ref mut text_renderer,
             ref mut cls_renderer,
+            ref mut cull_renderer,
+            ref mut hiz_renderer,
+            ref mut post_renderer,
+            ref mut foveation_renderer,
+            ref mut ibl_renderer,
             ref mut resources,
             ref mut frame_downloader,
             sample_indices,
@@ -778,6 +874,7 @@ impl<'s> Context<'s> {
             // Renderers
             depth_renderer,
             light_depth_renderer,
+            shadow_atlas_renderer,
             line_renderer,
             basic_renderer,
             light_renderer,
@@ -786,6 +883,11 @@ impl<'s> Context<'s> {
             cluster_renderer,
             text_renderer,
             cls_renderer,
+            cull_renderer,
+            hiz_renderer,
+            post_renderer,
+            foveation_renderer,
+            ibl_renderer,
 
             // More opengl resources...
             resources,
@@ -807,6 +909,8 @@ impl<'s> Context<'s> {
             focus: false,
             tick: 0,
             event_index: 0,
+            recorded_frame_events: Vec::new(),
+            vr_pose: None,
             frame_index: FrameIndex::from_usize(0),
             keyboard_state: Default::default(),
             export_frames: None,
@@ -890,6 +994,8 @@ impl<'s> Context<'s> {
                 .replace_clustered_light_shading(&mut self.current, self.configuration.clustered_light_shading);
             self.shader_compiler
                 .replace_sample_count(&mut self.current, self.configuration.global.sample_count);
+            self.shader_compiler
+                .replace_tonemapper(&mut self.current, self.configuration.post.tonemapper);
 
             unsafe {
                 if self.configuration.gl.framebuffer_srgb {
@@ -907,6 +1013,7 @@ impl<'s> Context<'s> {
         let mut new_light_space = self.shader_compiler.light_space();
         let mut new_attenuation_mode = self.shader_compiler.attenuation_mode();
         let mut new_render_technique = self.shader_compiler.render_technique();
+        let mut new_tonemapper = self.shader_compiler.tonemapper();
         let mut reset_debug_camera = false;
 
         let mut frame_events: Vec<FrameEvent> = Vec::new();
@@ -935,10 +1042,13 @@ impl<'s> Context<'s> {
             }
         });
 
-        if let Some(file) = self.record_file.as_mut() {
-            bincode::serialize_into(file, &frame_events).unwrap();
+        if self.record_file.is_some() {
+            // Written at the end of the frame when the VR pose is known.
+            self.recorded_frame_events.extend(frame_events.iter().cloned());
         }
 
+        self.vr_pose = None;
+
         for event in match self.replay_frame_events {
             Some(ref replay_frame_events) => replay_frame_events[self.event_index].iter(),
             None => frame_events.iter(),
@@ -996,6 +1106,22 @@ impl<'s> Context<'s> {
                                     self.shader_compiler
                                         .replace_depth_prepass(&mut self.current, self.depth_prepass);
                                 }
+                                VirtualKeyCode::Key6 => {
+                                    new_tonemapper.wrapping_next_assign();
+                                }
+                                VirtualKeyCode::Key7 => {
+                                    self.configuration.transparency.mode.wrapping_next_assign();
+                                }
+                                VirtualKeyCode::Key8 => {
+                                    // Cycles through collapsing nothing, the second and the first level of
+                                    // the profiling overlay.
+                                    let collapse_depth = &mut self.configuration.profiling.collapse_depth;
+                                    *collapse_depth = match *collapse_depth {
+                                        None => Some(2),
+                                        Some(depth) if depth > 1 => Some(depth - 1),
+                                        Some(_) => None,
+                                    };
+                                }
                                 VirtualKeyCode::R => {
                                     reset_debug_camera = true;
                                 }
@@ -1056,6 +1182,9 @@ impl<'s> Context<'s> {
                         }
                     }
                 }
+                FrameEvent::VrPose(ref pose) => {
+                    self.vr_pose = Some(pose.clone());
+                }
             }
         }
 
@@ -1067,6 +1196,8 @@ impl<'s> Context<'s> {
             .replace_attenuation_mode(&mut self.current, new_attenuation_mode);
         self.shader_compiler
             .replace_render_technique(&mut self.current, new_render_technique);
+        self.shader_compiler
+            .replace_tonemapper(&mut self.current, new_tonemapper);
 
         if new_target_camera_key != self.target_camera_key {
             self.target_camera_key = new_target_camera_key;
@@ -1210,7 +1341,7 @@ impl<'s> Context<'s> {
             }
         }
 
-        if self.vr.is_some() {
+        if self.vr.is_some() || self.vr_pose.is_some() {
             // Pitch makes me dizzy.
             self.transition_camera.current_camera.transform.pitch = Rad(0.0);
         }
@@ -1277,10 +1408,10 @@ impl<'s> Context<'s> {
             }
         }
 
-        let stereo_data = self
-            .vr
-            .as_ref()
-            .map(|vr| {
+        let vr_pose = match self.vr_pose {
+            // Prefer the replayed pose so replays don't depend on the presence of a headset.
+            Some(ref pose) => Some(pose.clone()),
+            None => self.vr.as_ref().map(|vr| {
                 let mut poses: [vr::sys::TrackedDevicePose_t; vr::sys::k_unMaxTrackedDeviceCount as usize] =
                     unsafe { mem::zeroed() };
                 // NOTE: OpenVR will block upon querying the pose for as long as
@@ -1292,24 +1423,37 @@ impl<'s> Context<'s> {
 
                 let hmd_pose = poses[vr::sys::k_unTrackedDeviceIndex_Hmd as usize];
                 assert!(hmd_pose.bPoseIsValid, "Received invalid pose from VR.");
-                let bdy_hmd =
-                    BdyHmd::from_hmd_to_bdy(Matrix4::from_hmd(hmd_pose.mDeviceToAbsoluteTracking.m).cast().unwrap());
 
-                StereoData {
+                VrPose {
                     win_size: Vector2::new(win_size.width, win_size.height).cast().unwrap(),
-                    bdy_hmd,
+                    hmd_to_bdy: Matrix4::from_hmd(hmd_pose.mDeviceToAbsoluteTracking.m).cast().unwrap(),
                     eyes: EyeMap::new(|eye_key| {
                         let eye = Eye::from(eye_key);
-                        let cam_to_hmd = Matrix4::from_hmd(vr.system().get_eye_to_head_transform(eye))
-                            .cast()
-                            .unwrap();
-                        let hmd_cam = HmdCam::from_cam_to_hmd(cam_to_hmd);
-                        EyeData {
+                        VrEyePose {
+                            cam_to_hmd: Matrix4::from_hmd(vr.system().get_eye_to_head_transform(eye))
+                                .cast()
+                                .unwrap(),
                             tangents: FrustumTangents::from(vr.system().get_projection_raw(eye)),
-                            hmd_cam,
                         }
                     }),
                 }
+            }),
+        };
+
+        if let Some(ref pose) = vr_pose {
+            if self.record_file.is_some() {
+                self.recorded_frame_events.push(FrameEvent::VrPose(pose.clone()));
+            }
+        }
+
+        let stereo_data = vr_pose
+            .map(|pose| StereoData {
+                win_size: pose.win_size,
+                bdy_hmd: BdyHmd::from_hmd_to_bdy(pose.hmd_to_bdy),
+                eyes: pose.eyes.map(|eye| EyeData {
+                    tangents: eye.tangents,
+                    hmd_cam: HmdCam::from_cam_to_hmd(eye.cam_to_hmd),
+                }),
             })
             .or_else({
                 let configuration = &mut self.configuration;
@@ -1375,7 +1519,7 @@ impl<'s> Context<'s> {
         self.mirror_resources = None;
 
         self.world_transforms
-            .recompute(&self.gl, &mut self.profiling_context, &self.resources.scene_file);
+            .recompute(&mut self.profiling_context, &self.resources.scene_file);
 
         if self.configuration.mirror.enabled {
             self.mirror_resources = Some(MirrorResources::compute(&self.configuration.mirror));
@@ -1427,13 +1571,6 @@ impl<'s> Context<'s> {
                 });
             }
 
-            if self.configuration.light.static_lights {
-                for mut point_light in self.resources.point_lights.iter().copied() {
-                    point_light.attenuation = attenuation;
-                    self.point_lights.push(point_light);
-                }
-            }
-
             for rain_drop in self.rain_drops.iter() {
                 self.point_lights.push(light::PointLight {
                     tint: rain_drop.tint.into(),
@@ -1441,41 +1578,111 @@ impl<'s> Context<'s> {
                     attenuation,
                 });
             }
+        }
 
-            if let Some(ExportStopCondition::AllLights) = self.export_frames {
-                if self.export_index < self.point_lights.len() {
-                    self.point_lights.truncate(self.export_index);
-                } else {
-                    export_complete = true;
-                }
+        let mut area_lights: Vec<light::AreaLight> = if self.configuration.light.static_lights {
+            let attenuation = light::AttenCoefs::from(self.configuration.light.attenuation)
+                .cast()
+                .unwrap();
+
+            self.resources
+                .area_lights
+                .iter()
+                .map(|&area_light| light::AreaLight {
+                    attenuation,
+                    ..area_light
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut spot_lights: Vec<light::SpotLight> = {
+            let attenuation = light::AttenCoefs::from(self.configuration.light.attenuation)
+                .cast()
+                .unwrap();
+
+            self.configuration
+                .light
+                .spot_lights
+                .iter()
+                .map(|spot_light| light::SpotLight::from_configuration(spot_light, attenuation))
+                .collect()
+        };
+
+        if let Some(ExportStopCondition::AllLights) = self.export_frames {
+            // Lights are added in the same order as they appear in the light buffer.
+            let light_count = self.point_lights.len() + spot_lights.len() + area_lights.len();
+            if self.export_index < light_count {
+                let mut remaining = self.export_index;
+                self.point_lights.truncate(remaining);
+                remaining -= self.point_lights.len();
+                spot_lights.truncate(remaining);
+                remaining -= spot_lights.len();
+                area_lights.truncate(remaining);
+            } else {
+                export_complete = true;
             }
         }
 
+        // Levels of detail are selected from the head for every culling view
+        // of this frame.
+        let lod_camera = if self.configuration.lod.enabled {
+            let render_bdy_to_wld = self
+                .transition_camera
+                .current_camera
+                .transform
+                .pos_to_parent()
+                .cast::<f64>()
+                .unwrap();
+            let hmd_to_bdy = match stereo_data {
+                Some(StereoData { ref bdy_hmd, .. }) => bdy_hmd.hmd_to_bdy,
+                None => Matrix4::identity(),
+            };
+            Some(resources::LodCamera {
+                pos_in_wld: (render_bdy_to_wld * hmd_to_bdy).transform_point(Point3::origin()),
+                threshold: self.configuration.lod.threshold,
+            })
+        } else {
+            None
+        };
+
+        self.light_resources.shadow_atlas.reconcile(
+            &self.gl,
+            self.configuration.light.shadows.atlas_lights,
+            self.configuration.light.shadows.atlas_dimensions,
+        );
+
         unsafe {
+            let render_camera_transform = self.transition_camera.current_camera.transform;
+
             self.light_resources.recompute(
                 &self.gl,
                 &mut self.profiling_context,
                 self.frame_index,
                 &self.point_lights,
+                &spot_lights,
+                &area_lights,
                 self.configuration.light.virtual_light_count,
+                render_camera_transform.position,
+                render_camera_transform.rot_to_parent() * -Vector3::unit_z(),
+                self.configuration.light.shadows.atlas_lights,
             );
 
             if self.configuration.light.virtual_light_count > 0
-                || self.configuration.light.shadows.enabled && self.point_lights.len() > 0
+                || self.configuration.light.shadows.enabled && self.light_resources.header.light_count > 0
             {
                 let draw_resources_index = next_draw_resources(
                     &mut self.resources.draw_resources_pool,
                     &self.gl,
                     &mut self.profiling_context,
                 );
-                let draw_resources = &mut self.resources.draw_resources_pool[draw_resources_index];
-
-                let light = self.point_lights[0];
-                draw_resources.recompute(
-                    &self.gl,
-                    &mut self.profiling_context,
+                let light = self.light_resources.body[0];
+                self.recompute_draw_resources(
+                    draw_resources_index,
+                    None,
                     resources::CullingCamera {
-                        wld_to_cam: Matrix4::from_translation(-light.position.to_vec().cast::<f64>().unwrap()),
+                        wld_to_cam: Matrix4::from_translation(-Vector3::from(light.position).cast::<f64>().unwrap()),
                         frustum: {
                             let r = light.attenuation.r1 as f64;
                             Frustum {
@@ -1489,10 +1696,9 @@ impl<'s> Context<'s> {
                         },
                         projection_kind: resources::ProjectionKind::Orthographic,
                     },
-                    Matrix4::identity(),
-                    &self.world_transforms,
-                    &self.resources.materials,
-                    &self.resources.scene_file,
+                    lod_camera,
+                    &[Matrix4::identity()],
+                    None,
                 );
 
                 self.gl
@@ -1533,8 +1739,78 @@ impl<'s> Context<'s> {
                     draw_resources_index: draw_resources_index,
                 });
             }
+
+            if !self.light_resources.shadow_lights.is_empty() {
+                let profiler_index = self
+                    .profiling_context
+                    .start(self.gl, self.light_resources.shadow_atlas_profiler);
+
+                let atlas_dimensions = self.light_resources.shadow_atlas.dimensions as i32;
+
+                self.gl
+                    .bind_framebuffer(gl::FRAMEBUFFER, self.light_resources.shadow_atlas.framebuffer_name);
+                self.gl.viewport(0, 0, atlas_dimensions, atlas_dimensions);
+
+                self.gl.enable(gl::DEPTH_TEST);
+                self.gl.depth_func(gl::GEQUAL);
+
+                self.gl.enable(gl::CULL_FACE);
+                self.gl.cull_face(gl::BACK);
+
+                self.gl.clear_color(
+                    std::f32::INFINITY,
+                    std::f32::INFINITY,
+                    std::f32::INFINITY,
+                    std::f32::INFINITY,
+                );
+                self.gl.clear_depth(0.0);
+                self.gl.clear(gl::ClearFlag::COLOR_BUFFER | gl::ClearFlag::DEPTH_BUFFER);
+
+                for (shadow_index, light_index) in self.light_resources.shadow_lights.clone().into_iter().enumerate() {
+                    let draw_resources_index = next_draw_resources(
+                        &mut self.resources.draw_resources_pool,
+                        &self.gl,
+                        &mut self.profiling_context,
+                    );
+                    let light = self.light_resources.body[light_index];
+                    self.recompute_draw_resources(
+                        draw_resources_index,
+                        None,
+                        resources::CullingCamera {
+                            wld_to_cam: Matrix4::from_translation(
+                                -Vector3::from(light.position).cast::<f64>().unwrap(),
+                            ),
+                            frustum: {
+                                let r = light.radius() as f64;
+                                Frustum {
+                                    x0: -r,
+                                    x1: r,
+                                    y0: -r,
+                                    y1: r,
+                                    z0: -r,
+                                    z1: r,
+                                }
+                            },
+                            projection_kind: resources::ProjectionKind::Orthographic,
+                        },
+                        lod_camera,
+                        &[Matrix4::identity()],
+                        None,
+                    );
+
+                    self.render_shadow_atlas_tile(shadow_atlas_renderer::Parameters {
+                        draw_resources_index,
+                        light_index,
+                        shadow_index: shadow_index as u32,
+                    });
+                }
+
+                self.profiling_context.stop(self.gl, profiler_index);
+            }
         }
 
+        self.update_ibl();
+
         let mut cluster_resources_index = None;
 
         match stereo_data {
@@ -1554,6 +1830,7 @@ impl<'s> Context<'s> {
                 if self.shader_compiler.render_technique() == RenderTechnique::Clustered
                     && self.configuration.clustered_light_shading.grouping
                         == configuration::ClusteringGrouping::Enclosed
+                    && !self.configuration.global.instanced_stereo
                 {
                     cluster_resources_index = Some(self.cluster_resources_pool.next_unused(
                         &self.gl,
@@ -1587,6 +1864,7 @@ impl<'s> Context<'s> {
                 //         gl: &self.gl,
                 //         profiling_context: &mut self.profiling_context,
                 //         camera: render_c3,
+                //         eyes: None,
                 //         draw_resources_index,
                 //         cluster_resources_index: None,
                 //         dimensions: win_size,
@@ -1596,44 +1874,55 @@ impl<'s> Context<'s> {
                 //             let h = self.win_size.height as i32;
                 //             Some(Viewport::from_coordinates(Point2::origin(), Point2::new(w, h)))
                 //         },
+                //         display_source: None,
                 //         should_render: true,
+                //         foveated: false,
                 //     };
 
                 //     let _ =
                 //         next_main_resources(&mut self.main_resources_pool, main_resources_parameters);
                 // }
 
-                for &eye_key in EYE_KEYS.iter() {
-                    let EyeData { tangents, ref hmd_cam } = eyes[eye_key];
-
-                    let BdyCam { bdy_to_cam, cam_to_bdy } = BdyCam::new(bdy_hmd, hmd_cam);
+                if self.configuration.global.instanced_stereo {
+                    // Both eyes are rendered side by side by a single main resources. Culling and
+                    // clustering use a camera behind the eyes that encloses both of them.
+                    let eye_positions_and_tangents = eyes.map(|EyeData { tangents, hmd_cam }| {
+                        (hmd_cam.cam_to_hmd.transform_point(Point3::origin()), tangents)
+                    });
 
-                    let render_c3 = {
-                        let wld_to_cam = bdy_to_cam * render_wld_to_bdy;
-                        let cam_to_wld = render_bdy_to_wld * cam_to_bdy;
-                        CameraParameters::new(
-                            wld_to_cam,
-                            cam_to_wld,
-                            stereo_frustum(&render_camera, tangents),
-                            RENDER_RANGE,
-                        )
+                    let camera_parameters = |camera: &camera::Camera,
+                                             wld_to_bdy: Matrix4<f64>,
+                                             bdy_to_wld: Matrix4<f64>| {
+                        let enclosing = {
+                            let (pos_in_hmd, frustum) = enclosing_stereo_frustum(camera, eye_positions_and_tangents);
+                            let hmd_cam = HmdCam::from_cam_to_hmd(Matrix4::from_translation(pos_in_hmd.to_vec()));
+                            let BdyCam { bdy_to_cam, cam_to_bdy } = BdyCam::new(bdy_hmd, &hmd_cam);
+                            CameraParameters::new(
+                                bdy_to_cam * wld_to_bdy,
+                                bdy_to_wld * cam_to_bdy,
+                                frustum,
+                                RENDER_RANGE,
+                            )
+                        };
+                        let eyes = eyes.map(|EyeData { tangents, hmd_cam }| {
+                            let BdyCam { bdy_to_cam, cam_to_bdy } = BdyCam::new(bdy_hmd, &hmd_cam);
+                            CameraParameters::new(
+                                bdy_to_cam * wld_to_bdy,
+                                bdy_to_wld * cam_to_bdy,
+                                stereo_frustum(camera, tangents),
+                                RENDER_RANGE,
+                            )
+                        });
+                        (enclosing, eyes)
                     };
 
-                    let cluster_c3 = {
-                        let wld_to_cam = bdy_to_cam * cluster_wld_to_bdy;
-                        let cam_to_wld = cluster_bdy_to_wld * cam_to_bdy;
-                        CameraParameters::new(
-                            wld_to_cam,
-                            cam_to_wld,
-                            stereo_frustum(&cluster_camera, tangents),
-                            RENDER_RANGE,
-                        )
-                    };
+                    let (render_c3, render_eyes) =
+                        camera_parameters(&render_camera, render_wld_to_bdy, render_bdy_to_wld);
+                    let (cluster_c3, cluster_eyes) =
+                        camera_parameters(&cluster_camera, cluster_wld_to_bdy, cluster_bdy_to_wld);
 
-                    if self.shader_compiler.render_technique() == RenderTechnique::Clustered
-                        && self.configuration.clustered_light_shading.grouping
-                            == configuration::ClusteringGrouping::Individual
-                    {
+                    // With a single camera both groupings cluster in its space.
+                    if self.shader_compiler.render_technique() == RenderTechnique::Clustered {
                         cluster_resources_index = Some(self.cluster_resources_pool.next_unused(
                             &self.gl,
                             &mut self.profiling_context,
@@ -1645,6 +1934,8 @@ impl<'s> Context<'s> {
                         ));
                     }
 
+                    let dimensions = Vector2::new(win_size.x * 2, win_size.y);
+
                     let draw_resources_index = next_draw_resources(
                         &mut self.resources.draw_resources_pool,
                         &self.gl,
@@ -1655,44 +1946,30 @@ impl<'s> Context<'s> {
                         gl: &self.gl,
                         profiling_context: &mut self.profiling_context,
                         camera: render_c3,
+                        eyes: Some(render_eyes),
                         draw_resources_index,
                         cluster_resources_index,
-                        dimensions: win_size,
+                        dimensions,
                         sample_count: self.configuration.global.sample_count,
-                        display_viewport: {
-                            let w = self.win_size.width as i32;
-                            let h = self.win_size.height as i32;
-
-                            match self.configuration.virtual_stereo.show {
-                                configuration::VirtualStereoShow::Left => {
-                                    if let vr::Eye::Left = eye_key {
-                                        Some(Viewport::from_coordinates(Point2::origin(), Point2::new(w, h)))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                configuration::VirtualStereoShow::Right => {
-                                    if let vr::Eye::Right = eye_key {
-                                        Some(Viewport::from_coordinates(Point2::origin(), Point2::new(w, h)))
-                                    } else {
-                                        None
-                                    }
-                                }
-                                configuration::VirtualStereoShow::Both => Some(match eye_key {
-                                    vr::Eye::Left => {
-                                        Viewport::from_coordinates(Point2::origin(), Point2::new(w / 2, h))
-                                    }
-                                    vr::Eye::Right => {
-                                        Viewport::from_coordinates(Point2::new(w - w / 2, 0), Point2::new(w, h))
-                                    }
-                                }),
-                            }
+                        display_viewport: Some(Viewport::from_dimensions(Vector2::new(
+                            self.win_size.width as i32,
+                            self.win_size.height as i32,
+                        ))),
+                        // Both eyes are side by side in the framebuffer, pick the one to show.
+                        display_source: match self.configuration.virtual_stereo.show {
+                            configuration::VirtualStereoShow::Left => Some(Viewport::from_coordinates(
+                                Point2::origin(),
+                                Point2::new(win_size.x, win_size.y),
+                            )),
+                            configuration::VirtualStereoShow::Right => Some(Viewport::from_coordinates(
+                                Point2::new(win_size.x, 0),
+                                Point2::new(dimensions.x, dimensions.y),
+                            )),
+                            configuration::VirtualStereoShow::Both => None,
                         },
                         should_render: true,
-                        // should_render: match self.target_camera_key {
-                        //     CameraKey::Main => true,
-                        //     CameraKey::Debug => false,
-                        // }
+                        // Masked depth can not be reconstructed per sample.
+                        foveated: self.configuration.foveation.enabled && self.configuration.global.sample_count == 0,
                     };
 
                     let main_resources_index =
@@ -1715,12 +1992,15 @@ impl<'s> Context<'s> {
                                 gl: &self.gl,
                                 profiling_context: &mut self.profiling_context,
                                 camera: cluster_c3,
+                                eyes: Some(cluster_eyes),
                                 draw_resources_index,
                                 cluster_resources_index,
-                                dimensions: win_size,
+                                dimensions,
                                 sample_count: self.configuration.global.sample_count,
                                 display_viewport: None,
+                                display_source: None,
                                 should_render: false,
+                                foveated: false,
                             };
 
                             next_main_resources(&mut self.main_resources_pool, main_resources_parameters)
@@ -1732,6 +2012,146 @@ impl<'s> Context<'s> {
                             ClusterCameraParameters { main_resources_index },
                         );
                     }
+                } else {
+                    for &eye_key in EYE_KEYS.iter() {
+                        let EyeData { tangents, ref hmd_cam } = eyes[eye_key];
+
+                        let BdyCam { bdy_to_cam, cam_to_bdy } = BdyCam::new(bdy_hmd, hmd_cam);
+
+                        let render_c3 = {
+                            let wld_to_cam = bdy_to_cam * render_wld_to_bdy;
+                            let cam_to_wld = render_bdy_to_wld * cam_to_bdy;
+                            CameraParameters::new(
+                                wld_to_cam,
+                                cam_to_wld,
+                                stereo_frustum(&render_camera, tangents),
+                                RENDER_RANGE,
+                            )
+                        };
+
+                        let cluster_c3 = {
+                            let wld_to_cam = bdy_to_cam * cluster_wld_to_bdy;
+                            let cam_to_wld = cluster_bdy_to_wld * cam_to_bdy;
+                            CameraParameters::new(
+                                wld_to_cam,
+                                cam_to_wld,
+                                stereo_frustum(&cluster_camera, tangents),
+                                RENDER_RANGE,
+                            )
+                        };
+
+                        if self.shader_compiler.render_technique() == RenderTechnique::Clustered
+                            && self.configuration.clustered_light_shading.grouping
+                                == configuration::ClusteringGrouping::Individual
+                        {
+                            cluster_resources_index = Some(self.cluster_resources_pool.next_unused(
+                                &self.gl,
+                                &mut self.profiling_context,
+                                ClusterParameters {
+                                    configuration: self.configuration.clustered_light_shading,
+                                    wld_to_clu_ori: cluster_c3.wld_to_cam,
+                                    clu_ori_to_wld: cluster_c3.cam_to_wld,
+                                },
+                            ));
+                        }
+
+                        let draw_resources_index = next_draw_resources(
+                            &mut self.resources.draw_resources_pool,
+                            &self.gl,
+                            &mut self.profiling_context,
+                        );
+
+                        let main_resources_parameters = MainResourcesParameters {
+                            gl: &self.gl,
+                            profiling_context: &mut self.profiling_context,
+                            camera: render_c3,
+                            eyes: None,
+                            draw_resources_index,
+                            cluster_resources_index,
+                            dimensions: win_size,
+                            sample_count: self.configuration.global.sample_count,
+                            display_viewport: {
+                                let w = self.win_size.width as i32;
+                                let h = self.win_size.height as i32;
+
+                                match self.configuration.virtual_stereo.show {
+                                    configuration::VirtualStereoShow::Left => {
+                                        if let vr::Eye::Left = eye_key {
+                                            Some(Viewport::from_coordinates(Point2::origin(), Point2::new(w, h)))
+                                        } else {
+                                            None
+                                        }
+                                    }
+                                    configuration::VirtualStereoShow::Right => {
+                                        if let vr::Eye::Right = eye_key {
+                                            Some(Viewport::from_coordinates(Point2::origin(), Point2::new(w, h)))
+                                        } else {
+                                            None
+                                        }
+                                    }
+                                    configuration::VirtualStereoShow::Both => Some(match eye_key {
+                                        vr::Eye::Left => {
+                                            Viewport::from_coordinates(Point2::origin(), Point2::new(w / 2, h))
+                                        }
+                                        vr::Eye::Right => {
+                                            Viewport::from_coordinates(Point2::new(w - w / 2, 0), Point2::new(w, h))
+                                        }
+                                    }),
+                                }
+                            },
+                            display_source: None,
+                            should_render: true,
+                            // Masked depth can not be reconstructed per sample.
+                            foveated: self.configuration.foveation.enabled
+                                && self.configuration.global.sample_count == 0,
+                            // should_render: match self.target_camera_key {
+                            //     CameraKey::Main => true,
+                            //     CameraKey::Debug => false,
+                            // }
+                        };
+
+                        let main_resources_index =
+                            next_main_resources(&mut self.main_resources_pool, main_resources_parameters);
+
+                        if self.shader_compiler.render_technique() == RenderTechnique::Clustered {
+                            let camera_resources_pool = &mut self.cluster_resources_pool
+                                [cluster_resources_index.unwrap()]
+                            .camera_resources_pool;
+
+                            let main_resources_index = if render_camera == cluster_camera {
+                                main_resources_index
+                            } else {
+                                let draw_resources_index = next_draw_resources(
+                                    &mut self.resources.draw_resources_pool,
+                                    &self.gl,
+                                    &mut self.profiling_context,
+                                );
+
+                                let main_resources_parameters = MainResourcesParameters {
+                                    gl: &self.gl,
+                                    profiling_context: &mut self.profiling_context,
+                                    camera: cluster_c3,
+                                    eyes: None,
+                                    draw_resources_index,
+                                    cluster_resources_index,
+                                    dimensions: win_size,
+                                    sample_count: self.configuration.global.sample_count,
+                                    display_viewport: None,
+                                    display_source: None,
+                                    should_render: false,
+                                    foveated: false,
+                                };
+
+                                next_main_resources(&mut self.main_resources_pool, main_resources_parameters)
+                            };
+
+                            let _ = camera_resources_pool.next_unused(
+                                &self.gl,
+                                &mut self.profiling_context,
+                                ClusterCameraParameters { main_resources_index },
+                            );
+                        }
+                    }
                 }
             }
             None => {
@@ -1803,12 +2223,15 @@ impl<'s> Context<'s> {
                     gl: &self.gl,
                     profiling_context: &mut self.profiling_context,
                     camera: render_c3,
+                    eyes: None,
                     draw_resources_index,
                     cluster_resources_index,
                     dimensions,
                     sample_count: self.configuration.global.sample_count,
                     display_viewport: Some(Viewport::from_dimensions(dimensions)),
+                    display_source: None,
                     should_render: true,
+                    foveated: false,
                 };
 
                 let main_resources_index =
@@ -1831,12 +2254,15 @@ impl<'s> Context<'s> {
                             gl: &self.gl,
                             profiling_context: &mut self.profiling_context,
                             camera: cluster_c3,
+                            eyes: None,
                             draw_resources_index,
                             cluster_resources_index,
                             dimensions,
                             sample_count: self.configuration.global.sample_count,
                             display_viewport: None,
+                            display_source: None,
                             should_render: false,
+                            foveated: false,
                         };
 
                         next_main_resources(&mut self.main_resources_pool, main_resources_parameters)
@@ -1853,19 +2279,31 @@ impl<'s> Context<'s> {
 
         for main_resources_index in 0..self.main_resources_pool.len() {
             let main_resources = &self.main_resources_pool[main_resources_index];
-            let draw_resources = &mut self.resources.draw_resources_pool[main_resources.draw_resources_index];
-            draw_resources.recompute(
-                &self.gl,
-                &mut self.profiling_context,
-                resources::CullingCamera {
-                    wld_to_cam: main_resources.camera.wld_to_cam,
-                    frustum: main_resources.camera.frustum,
-                    projection_kind: resources::ProjectionKind::Perspective,
-                },
-                main_resources.camera.wld_to_clp,
-                &self.world_transforms,
-                &self.resources.materials,
-                &self.resources.scene_file,
+            let draw_resources_index = main_resources.draw_resources_index;
+            let culling_camera = resources::CullingCamera {
+                wld_to_cam: main_resources.camera.wld_to_cam,
+                frustum: main_resources.camera.frustum,
+                projection_kind: resources::ProjectionKind::Perspective,
+            };
+            let wld_to_ren_clp = main_resources.wld_to_ren_clp();
+            let occlusion = if self.configuration.global.occlusion_culling {
+                main_resources
+                    .hiz
+                    .wld_to_clp
+                    .map(|wld_to_clp| cull_renderer::Occlusion {
+                        texture_name: main_resources.hiz.texture_name,
+                        wld_to_clp,
+                    })
+            } else {
+                None
+            };
+            self.recompute_draw_resources(
+                draw_resources_index,
+                Some(main_resources_index),
+                culling_camera,
+                lod_camera,
+                &wld_to_ren_clp,
+                occlusion,
             );
 
             self.clear_main(main_resources_index);
@@ -1930,11 +2368,17 @@ impl<'s> Context<'s> {
             });
 
             if let Some(view) = profiling_basic_buffer {
+                let dimensions = self.main_resources_pool[main_resources_index].dimensions;
                 unsafe {
-                    self.profiling_context.end_basic_buffer(self.gl, view);
+                    self.profiling_context
+                        .end_basic_buffer(self.gl, view, (dimensions.x * dimensions.y) as u32);
                 }
             }
 
+            if self.configuration.global.occlusion_culling {
+                self.render_hiz(hiz_renderer::Parameters { main_resources_index });
+            }
+
             if self.configuration.light.render_points {
                 self.render_lights(&light_renderer::Parameters { main_resources_index });
             }
@@ -1943,97 +2387,131 @@ impl<'s> Context<'s> {
                 self.render_light_volumes(light_volume_renderer::Parameters { main_resources_index });
             }
 
-            if self.target_camera_key == CameraKey::Debug {
-                let vertices: Vec<[f32; 3]> = RENDER_RANGE
-                    .vertices()
-                    .iter()
-                    .map(|point| point.cast().unwrap().into())
-                    .collect();
-
-                for cluster_resources_index in self.cluster_resources_pool.used_index_iter() {
-                    let main_resources = &self.main_resources_pool[main_resources_index];
-                    let cluster_resources = &self.cluster_resources_pool[cluster_resources_index];
-                    for camera_resources in cluster_resources.camera_resources_pool.used_slice().iter() {
-                        let cluster_main_resources =
-                            &self.main_resources_pool[camera_resources.parameters.main_resources_index];
-                        self.line_renderer.render(
-                            &mut rendering_context!(self),
-                            &line_renderer::Parameters {
-                                vertices: &vertices[..],
-                                indices: &RENDER_RANGE.line_mesh_indices(),
-                                obj_to_clp: &(main_resources.camera.wld_to_clp
-                                    * cluster_main_resources.camera.clp_to_wld),
-                                color: color::GREEN,
-                            },
-                        );
-                    }
+            if self.main_resources_pool[main_resources_index].foveated {
+                self.render_foveation_reconstruct(foveation_renderer::Parameters { main_resources_index });
+            }
 
-                    {
-                        let cluster_range =
-                            Range3::from_vector(cluster_resources.computed.dimensions.cast::<f64>().unwrap());
-
-                        let vertices: Vec<[f32; 3]> = cluster_range
-                            .vertices()
-                            .iter()
-                            .map(|point| point.cast().unwrap().into())
-                            .collect();
-
-                        let clu_clp_to_clu_cam = match self.configuration.clustered_light_shading.projection {
-                            configuration::ClusteringProjection::Perspective => {
-                                cluster_resources.computed.frustum.inverse_perspective(&cluster_range)
-                            }
-                            configuration::ClusteringProjection::Orthographic => {
-                                cluster_resources.computed.frustum.inverse_orthographic(&cluster_range)
-                            }
-                        };
+            // Debug geometry is drawn one view at a time.
+            let views: Vec<(Viewport<i32>, Matrix4<f64>)> = self.main_resources_pool[main_resources_index]
+                .views()
+                .into_iter()
+                .map(|(viewport, camera)| (viewport, camera.wld_to_clp))
+                .collect();
+            for &(viewport, wld_to_clp) in views.iter() {
+                viewport.set(&self.gl);
+
+                if self.target_camera_key == CameraKey::Debug {
+                    let vertices: Vec<[f32; 3]> = RENDER_RANGE
+                        .vertices()
+                        .iter()
+                        .map(|point| point.cast().unwrap().into())
+                        .collect();
+
+                    for cluster_resources_index in self.cluster_resources_pool.used_index_iter() {
+                        let main_resources = &self.main_resources_pool[main_resources_index];
+                        let cluster_resources = &self.cluster_resources_pool[cluster_resources_index];
+                        for camera_resources in cluster_resources.camera_resources_pool.used_slice().iter() {
+                            let cluster_main_resources =
+                                &self.main_resources_pool[camera_resources.parameters.main_resources_index];
+                            self.line_renderer.render(
+                                &mut rendering_context!(self),
+                                &line_renderer::Parameters {
+                                    vertices: &vertices[..],
+                                    indices: &RENDER_RANGE.line_mesh_indices(),
+                                    obj_to_clp: &(wld_to_clp * cluster_main_resources.camera.clp_to_wld),
+                                    color: color::GREEN,
+                                    depth_test: true,
+                                },
+                            );
+                        }
 
-                        let clu_clp_to_wld = cluster_resources.computed.clu_cam_to_wld * clu_clp_to_clu_cam;
+                        {
+                            let cluster_range =
+                                Range3::from_vector(cluster_resources.computed.dimensions.cast::<f64>().unwrap());
 
-                        self.line_renderer.render(
-                            &mut rendering_context!(self),
-                            &line_renderer::Parameters {
-                                vertices: &vertices,
-                                indices: &cluster_range.line_mesh_indices(),
-                                obj_to_clp: &(main_resources.camera.wld_to_clp * clu_clp_to_wld),
-                                color: color::RED,
-                            },
-                        );
+                            let vertices: Vec<[f32; 3]> = cluster_range
+                                .vertices()
+                                .iter()
+                                .map(|point| point.cast().unwrap().into())
+                                .collect();
+
+                            let clu_clp_to_clu_cam = match self.configuration.clustered_light_shading.projection {
+                                configuration::ClusteringProjection::Perspective
+                                | configuration::ClusteringProjection::Logarithmic => {
+                                    cluster_resources.computed.frustum.inverse_perspective(&cluster_range)
+                                }
+                                configuration::ClusteringProjection::Orthographic => {
+                                    cluster_resources.computed.frustum.inverse_orthographic(&cluster_range)
+                                }
+                            };
+
+                            let clu_clp_to_wld = cluster_resources.computed.clu_cam_to_wld * clu_clp_to_clu_cam;
+
+                            self.line_renderer.render(
+                                &mut rendering_context!(self),
+                                &line_renderer::Parameters {
+                                    vertices: &vertices,
+                                    indices: &cluster_range.line_mesh_indices(),
+                                    obj_to_clp: &(wld_to_clp * clu_clp_to_wld),
+                                    color: color::RED,
+                                    depth_test: true,
+                                },
+                            );
+                        }
                     }
                 }
-            }
 
-            if match self.configuration.global.cluster_visualisation_mode {
-                configuration::ClusterVisualisationMode::Disabled => false,
-                configuration::ClusterVisualisationMode::Enabled => true,
-                configuration::ClusterVisualisationMode::DebugOnly => self.target_camera_key == CameraKey::Debug,
-            } {
-                // Only render clusters from my own view.
-                if let Some(cluster_resources_index) =
-                    self.main_resources_pool[main_resources_index].cluster_resources_index
-                {
-                    let clu_cam_to_ren_clp = &(self.main_resources_pool[main_resources_index].camera.wld_to_clp
-                        * self.cluster_resources_pool[cluster_resources_index]
-                            .computed
-                            .clu_cam_to_wld);
-                    self.render_debug_clusters(&cluster_renderer::Parameters {
-                        cluster_resources_index,
-                        clu_cam_to_ren_clp,
-                        visualisation: self.configuration.global.cluster_visualisation,
-                        visible_only: self.configuration.global.cluster_visualisation_visible_only,
-                    });
+                if match self.configuration.global.cluster_visualisation_mode {
+                    configuration::ClusterVisualisationMode::Disabled => false,
+                    configuration::ClusterVisualisationMode::Enabled => true,
+                    configuration::ClusterVisualisationMode::DebugOnly => self.target_camera_key == CameraKey::Debug,
+                } {
+                    // Only render clusters from my own view.
+                    if let Some(cluster_resources_index) =
+                        self.main_resources_pool[main_resources_index].cluster_resources_index
+                    {
+                        let clu_cam_to_ren_clp = &(wld_to_clp
+                            * self.cluster_resources_pool[cluster_resources_index]
+                                .computed
+                                .clu_cam_to_wld);
+                        self.render_debug_clusters(&cluster_renderer::Parameters {
+                            cluster_resources_index,
+                            clu_cam_to_ren_clp,
+                            visualisation: self.configuration.global.cluster_visualisation,
+                            visible_only: self.configuration.global.cluster_visualisation_visible_only,
+                        });
+                    }
                 }
             }
+            Viewport::from_dimensions(self.main_resources_pool[main_resources_index].framebuffer.dimensions)
+                .set(&self.gl);
+
+            let post_enabled = self.configuration.post.enabled
+                && self.main_resources_pool[main_resources_index]
+                    .display_viewport
+                    .is_some();
+            if post_enabled {
+                self.render_post(post_renderer::Parameters { main_resources_index });
+            }
 
             let main_resources = &self.main_resources_pool[main_resources_index];
             if let Some(viewport) = main_resources.display_viewport {
+                let framebuffer_name = if post_enabled {
+                    main_resources.post.display_framebuffer_name
+                } else {
+                    main_resources.framebuffer.framebuffer_name
+                };
+                let source = main_resources
+                    .display_source
+                    .unwrap_or_else(|| Viewport::from_dimensions(main_resources.framebuffer.dimensions));
                 unsafe {
                     self.gl.blit_named_framebuffer(
-                        main_resources.framebuffer.framebuffer_name.into(),
+                        framebuffer_name.into(),
                         gl::FramebufferName::Default,
-                        0,
-                        0,
-                        main_resources.framebuffer.dimensions.x,
-                        main_resources.framebuffer.dimensions.y,
+                        source.p0().x,
+                        source.p0().y,
+                        source.p1().x,
+                        source.p1().y,
                         viewport.p0().x,
                         viewport.p0().y,
                         viewport.p1().x,
@@ -2067,6 +2545,11 @@ impl<'s> Context<'s> {
                     Light Count:      {:<14} | \
                     Light Intensity:  {:<14} | \
                     Light Radius:     {:<14}\n\
+                    Tonemapper:       {:<14} | \
+                    Exposure:         {:<14} | \
+                    Bloom:            {:<14} | \
+                    Transparency:     {:<14}\n\
+                    Foveation:        {:<14}\n\
                     ",
                     match self.configuration.global.mode {
                         configuration::ApplicationMode::Normal => "".to_string(),
@@ -2088,19 +2571,96 @@ impl<'s> Context<'s> {
                             let size = self.configuration.clustered_light_shading.perspective_pixels;
                             format!("{}x{}px", size.x, size.y)
                         }
+                        configuration::ClusteringProjection::Logarithmic => {
+                            let size = self.configuration.clustered_light_shading.perspective_pixels;
+                            let slices = self.configuration.clustered_light_shading.logarithmic_slices;
+                            format!("{}x{}px, {} slices", size.x, size.y, slices)
+                        }
                         configuration::ClusteringProjection::Orthographic => {
                             let size = self.configuration.clustered_light_shading.orthographic_sides;
                             format!("{:.2}x{:.2}x{:.2}m", size.x, size.y, size.z)
                         }
                     },
                     format!("{:?}", self.shader_compiler.attenuation_mode()),
-                    self.point_lights.len(),
+                    self.light_resources.header.light_count,
                     self.configuration.light.attenuation.i,
                     format!("{:.2}", self.configuration.light.attenuation.r1()),
+                    if self.configuration.post.enabled {
+                        format!("{:?}", self.shader_compiler.tonemapper())
+                    } else {
+                        "Disabled".to_string()
+                    },
+                    if self.configuration.post.auto_exposure.enabled {
+                        format!("Auto {:+.1} EV", self.configuration.post.auto_exposure.compensation)
+                    } else {
+                        format!("{:.2}", self.configuration.post.exposure)
+                    },
+                    if self.configuration.post.bloom.enabled {
+                        format!("{:.2}", self.configuration.post.bloom.intensity)
+                    } else {
+                        "Disabled".to_string()
+                    },
+                    format!("{:?}", self.configuration.transparency.mode),
+                    match self
+                        .main_resources_pool
+                        .iter()
+                        .find(|main_resources| main_resources.foveated)
+                    {
+                        Some(main_resources) => format!(
+                            "{:.0}% shaded",
+                            100.0
+                                * foveation_renderer::shaded_fraction(
+                                    &self.configuration.foveation,
+                                    &main_resources.views()[0].1.frustum
+                                )
+                        ),
+                        None => "Disabled".to_string(),
+                    },
+                ),
+            );
+        }
+
+        if self.configuration.global.display_parameters {
+            let mut cull_stats = resources::CullStats::default();
+            for main_resources in self.main_resources_pool.iter() {
+                if main_resources.should_render {
+                    let stats = main_resources.cull_stats.stats;
+                    cull_stats.frustum_culled += stats.frustum_culled;
+                    cull_stats.occlusion_culled += stats.occlusion_culled;
+                }
+            }
+            self.overlay_textbox.write(
+                &self.monospace,
+                &format!(
+                    "Culled: {} outside the frustum, {} occluded\n",
+                    cull_stats.frustum_culled, cull_stats.occlusion_culled,
                 ),
             );
         }
 
+        {
+            let cluster_buffers = self.profiling_context.cluster_buffers();
+            let active_cluster_overflow = cluster_buffers.iter().map(|b| b.active_cluster_overflow()).max().unwrap_or(0);
+            let light_indices_overflow = cluster_buffers.iter().map(|b| b.light_indices_overflow()).max().unwrap_or(0);
+            if active_cluster_overflow > 0 || light_indices_overflow > 0 {
+                self.overlay_textbox.write(
+                    &self.monospace,
+                    &format!(
+                        "WARNING: cluster buffer overflow, dropped {} active clusters and {} light indices{}\n",
+                        active_cluster_overflow,
+                        light_indices_overflow,
+                        if self.configuration.clustered_light_shading.adaptive_buffers {
+                            ", growing buffers"
+                        } else {
+                            ", enable adaptive_buffers or raise the limits"
+                        },
+                    ),
+                );
+            }
+        }
+
+        let mut graphs = sample_graphs::SampleGraphs::default();
+
         let Self {
             ref mut overlay_textbox,
             ref monospace,
@@ -2125,41 +2685,69 @@ impl<'s> Context<'s> {
             }
 
             let mut depth = 0;
+            // Depth of the collapsed sample we are inside of.
+            let mut collapsed_depth: Option<usize> = None;
             if let Some(events) = self.profiling_context.events(self.frame_index) {
-                for event in events {
+                for (event_index, event) in events.iter().enumerate() {
                     match *event {
                         profiling::FrameEvent::BeginTimeSpan(sample_index) => {
                             let sample_name = self.profiling_context.sample_names[sample_index.to_usize()];
 
-                            let hide = self.configuration.profiling.hide.iter().any(|s| s == sample_name);
-
-                            let title = format!("{}{}", "  ".repeat(depth), sample_name);
-
-                            if !hide {
-                                if let Some(stats) = self.profiling_context.stats(sample_index) {
-                                    overlay_textbox.write(
-                                        &monospace,
-                                        // &format!(
-                                        //     "[{:>3}] {:<30} | CPU {:>7.1}μs < {:>7.1}μs < {:>7.1}μs | GPU {:>7.1}μs < {:>7.1}μs < {:>7.1}μs\n",
-                                        //     sample_index.to_usize(),
-                                        //     &title,
-                                        //     stats.cpu_elapsed_min as f64 / 1000.0,
-                                        //     stats.cpu_elapsed_avg as f64 / 1000.0,
-                                        //     stats.cpu_elapsed_max as f64 / 1000.0,
-                                        //     stats.gpu_elapsed_min as f64 / 1000.0,
-                                        //     stats.gpu_elapsed_avg as f64 / 1000.0,
-                                        //     stats.gpu_elapsed_max as f64 / 1000.0,
-                                        // ),
-                                        &format!(
-                                            "[{:>3}] {:<30} | CPU {:>7.1}μs - {:>7.1}μs | GPU {:>7.1}μs - {:>7.1}μs\n",
-                                            sample_index.to_usize(),
-                                            &title,
-                                            stats.cpu_elapsed_min as f64 / 1000.0,
-                                            stats.cpu_elapsed_max as f64 / 1000.0,
-                                            stats.gpu_elapsed_min as f64 / 1000.0,
-                                            stats.gpu_elapsed_max as f64 / 1000.0,
-                                        ),
-                                    );
+                            if collapsed_depth.is_none() {
+                                let hide = self.configuration.profiling.hide.iter().any(|s| s == sample_name);
+                                let collapse = self.configuration.profiling.collapse.iter().any(|s| s == sample_name)
+                                    || self
+                                        .configuration
+                                        .profiling
+                                        .collapse_depth
+                                        .map_or(false, |collapse_depth| depth >= collapse_depth);
+                                let has_children = match events.get(event_index + 1) {
+                                    Some(profiling::FrameEvent::BeginTimeSpan(_)) => true,
+                                    _ => false,
+                                };
+
+                                if collapse {
+                                    collapsed_depth = Some(depth);
+                                }
+
+                                let title = format!(
+                                    "{}{} {}",
+                                    "  ".repeat(depth),
+                                    match (has_children, collapse) {
+                                        (false, _) => " ",
+                                        (true, false) => "-",
+                                        (true, true) => "+",
+                                    },
+                                    sample_name
+                                );
+
+                                if !hide {
+                                    if let Some(stats) = self.profiling_context.stats(sample_index) {
+                                        let row_top = overlay_textbox.cursor_y;
+                                        overlay_textbox.write(
+                                            &monospace,
+                                            &format!(
+                                                "[{:>3}] {:<30} | CPU {:>7.1}μs - {:>7.1}μs | GPU {:>7.1}μs - {:>7.1}μs",
+                                                sample_index.to_usize(),
+                                                &title,
+                                                stats.cpu_elapsed_min as f64 / 1000.0,
+                                                stats.cpu_elapsed_max as f64 / 1000.0,
+                                                stats.gpu_elapsed_min as f64 / 1000.0,
+                                                stats.gpu_elapsed_max as f64 / 1000.0,
+                                            ),
+                                        );
+                                        let row_right = overlay_textbox.cursor_x;
+                                        overlay_textbox.write(&monospace, "\n");
+
+                                        if self.configuration.profiling.graphs {
+                                            graphs.push(
+                                                self.profiling_context.history(sample_index, sample_graphs::FRAMES),
+                                                row_top as f32,
+                                                row_right as f32,
+                                                (row_top - overlay_textbox.cursor_y) as f32,
+                                            );
+                                        }
+                                    }
                                 }
                             }
 
@@ -2167,6 +2755,9 @@ impl<'s> Context<'s> {
                         }
                         profiling::FrameEvent::EndTimeSpan => {
                             depth -= 1;
+                            if collapsed_depth == Some(depth) {
+                                collapsed_depth = None;
+                            }
                         }
                         _ => {
                             // Whatever.
@@ -2181,6 +2772,29 @@ impl<'s> Context<'s> {
             self.gl.viewport(0, 0, dimensions.x, dimensions.y);
             self.gl.bind_framebuffer(gl::FRAMEBUFFER, gl::FramebufferName::Default);
             self.render_text();
+
+            if !graphs.vertices.is_empty() {
+                let pix_to_clp: Matrix4<f64> =
+                    cgmath::ortho(0.0, dimensions.x as f64, 0.0, dimensions.y as f64, -1.0, 1.0);
+                let graphs_to_clp = pix_to_clp * graphs.graphs_to_pix();
+                for &(indices, color) in [
+                    (&graphs.cpu_indices, color::YELLOW),
+                    (&graphs.gpu_indices, color::MAGENTA),
+                ]
+                .iter()
+                {
+                    self.line_renderer.render(
+                        &mut rendering_context!(self),
+                        &line_renderer::Parameters {
+                            vertices: &graphs.vertices,
+                            indices,
+                            obj_to_clp: &graphs_to_clp,
+                            color,
+                            depth_test: false,
+                        },
+                    );
+                }
+            }
         }
 
         self.frame_downloader.process_transfers(&self.gl, self.frame_index);
@@ -2247,6 +2861,11 @@ impl<'s> Context<'s> {
 
         self.gl_window.swap_buffers().unwrap();
 
+        if let Some(file) = self.record_file.as_mut() {
+            file.write_frame(&self.recorded_frame_events).unwrap();
+        }
+        self.recorded_frame_events.clear();
+
         self.frame_index.increment();
 
         // TODO: Borrow the pool instead.
@@ -2306,6 +2925,10 @@ impl<'s> Context<'s> {
             gl.enable(gl::CULL_FACE);
             gl.cull_face(gl::BACK);
         }
+
+        if main_resources.foveated {
+            self.render_foveation_mask(foveation_renderer::Parameters { main_resources_index });
+        }
     }
 }
 
@@ -2323,10 +2946,21 @@ fn main() {
                 .default_value(Configuration::DEFAULT_PATH)
                 .help("Specify the path to the configuration file."),
         )
+        .arg(
+            clap::Arg::with_name("convert recording")
+                .long("convert-recording")
+                .value_names(&["INPUT", "OUTPUT"])
+                .help("Converts a recording to the current format and exits."),
+        )
         .get_matches();
 
     let configuration_path = std::fs::canonicalize(matches.value_of("configuration path").unwrap()).unwrap();
 
+    if let Some(mut paths) = matches.values_of("convert recording") {
+        convert_recording(paths.next().unwrap(), paths.next().unwrap());
+        return;
+    }
+
     let mut context = MainContext::new(configuration_path);
 
     let mut run_index = RunIndex::from_usize(0);
@@ -2344,6 +2978,7 @@ fn main() {
             &mut context.current,
             shader_compiler::ProfilingVariables {
                 time_sensitive: context.profiling_context.time_sensitive(),
+                histogram_bin_count: context.profiling_context.histogram_bin_count(),
             },
         );
 
@@ -2357,6 +2992,10 @@ fn main() {
 
         match context.configuration.global.mode {
             configuration::ApplicationMode::Normal | configuration::ApplicationMode::Record => {
+                if let Some(file) = context.record_file.take() {
+                    file.finish().unwrap();
+                }
+
                 // Save state.
                 let mut file = io::BufWriter::new(fs::File::create("state.bin").unwrap());
                 for key in CameraKey::iter() {
@@ -2374,6 +3013,78 @@ fn main() {
     }
 }
 
+/// Leaves out the settings that differ between recording and replaying.
+fn recording_configuration_hash(configuration: &Configuration) -> u64 {
+    let mut value = toml::Value::try_from(configuration).unwrap();
+    let table = value.as_table_mut().unwrap();
+    table.remove("record");
+    table.remove("replay");
+    if let Some(global) = table.get_mut("global").and_then(toml::Value::as_table_mut) {
+        global.remove("mode");
+    }
+    profiling::run_metadata::fnv1a_64(value.to_string().as_bytes())
+}
+
+fn recording_header(configuration: &Configuration, cameras: &CameraMap<camera::Camera>) -> RecordingHeader {
+    RecordingHeader {
+        scene_path: Some(configuration.global.scene_path.clone()),
+        configuration_hash: Some(recording_configuration_hash(configuration)),
+        seed: Some(SEED),
+        cameras: CameraKey::iter().map(|key| cameras[key].into()).collect(),
+        frame_count: 0,
+        index_offset: 0,
+    }
+}
+
+/// Replaying with a different scene, configuration or seed does not reproduce
+/// the recorded frames.
+fn check_recording_header(header: &RecordingHeader, configuration: &Configuration) {
+    assert_eq!(
+        CameraKey::iter().len(),
+        header.cameras.len(),
+        "The recording has an unexpected number of cameras."
+    );
+    if let Some(ref scene_path) = header.scene_path {
+        if *scene_path != configuration.global.scene_path {
+            warn!(
+                "Replaying a recording of scene {:?} in scene {:?}.",
+                scene_path, configuration.global.scene_path
+            );
+        }
+    }
+    if let Some(configuration_hash) = header.configuration_hash {
+        if configuration_hash != recording_configuration_hash(configuration) {
+            warn!("The recording was made with a different configuration.");
+        }
+    }
+    if let Some(seed) = header.seed {
+        if seed != SEED {
+            warn!("The recording was made with a different RNG seed.");
+        }
+    }
+}
+
+/// Rewrites a recording in the current version. What old recordings don't
+/// store stays unknown, the current configuration may not be the one they were
+/// made with.
+fn convert_recording(input: impl AsRef<Path>, output: impl AsRef<Path>) {
+    let input = input.as_ref();
+    let mut reader = RecordingReader::<_, FrameEvents>::open(input, CameraKey::iter().len())
+        .unwrap_or_else(|error| panic!("Failed to read {:?}: {}", input, error));
+    let mut writer = RecordingWriter::create(output, reader.header.clone()).unwrap();
+    for frame_index in 0..reader.frame_count() {
+        writer.write_frame(&reader.read_frame(frame_index).unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    info!(
+        "Converted {} frames of version {} recording {:?}.",
+        reader.frame_count(),
+        reader.version,
+        input
+    );
+}
+
 // FIXME: Use.
 #[allow(unused)]
 fn gen_texture_t(name: gl::TextureName) -> vr::sys::Texture_t {
@@ -2398,6 +3109,48 @@ fn stereo_frustum(camera: &camera::Camera, tangents: FrustumTangents) -> Frustum
     tangents.to_frustum(camera.properties.z0 as f64, camera.properties.z1 as f64)
 }
 
+/// Computes a frustum that contains the frustums of both eyes given their
+/// positions in head space. The eyes are assumed to look along the negative
+/// z-axis of the head. Returns the position of its apex in head space.
+fn enclosing_stereo_frustum(
+    camera: &camera::Camera,
+    eyes: EyeMap<(Point3<f64>, FrustumTangents)>,
+) -> (Point3<f64>, Frustum<f64>) {
+    let (l, r) = (eyes.left.1, eyes.right.1);
+    let tangents = FrustumTangents {
+        x0: l.x0.min(r.x0),
+        x1: l.x1.max(r.x1),
+        y0: l.y0.min(r.y0),
+        y1: l.y1.max(r.y1),
+    };
+    debug_assert!(tangents.x0 < 0.0 && tangents.x1 > 0.0 && tangents.y0 < 0.0 && tangents.y1 > 0.0);
+
+    // Move the apex back from between the eyes until every side plane passes
+    // both eyes on the outside.
+    let center = eyes.left.0.midpoint(eyes.right.0);
+    let back = [eyes.left.0, eyes.right.0]
+        .iter()
+        .map(|&pos| {
+            let p = pos - center;
+            p.z + [
+                p.x / tangents.x0,
+                p.x / tangents.x1,
+                p.y / tangents.y0,
+                p.y / tangents.y1,
+            ]
+            .iter()
+            .fold(0.0, |a: f64, &b| a.max(b))
+        })
+        .fold(std::f64::NEG_INFINITY, f64::max);
+    let apex = center + Vector3::new(0.0, 0.0, back);
+
+    // The far plane has to lie beyond the far planes of both eyes.
+    let extra_depth = apex.z - eyes.left.0.z.min(eyes.right.0.z);
+    let frustum = tangents.to_frustum(camera.properties.z0 as f64 - extra_depth, camera.properties.z1 as f64);
+
+    (apex, frustum)
+}
+
 fn next_draw_resources(
     pool: &mut Pool<resources::DrawResources>,
     gl: &gl::Gl,
@@ -89,6 +89,309 @@ unsafe fn create_texture(
     name
 }
 
+/// Depth pyramid where every texel holds the farthest depth of its footprint
+/// in the level below. Level 0 has half the resolution of the depth buffer.
+pub struct HiZ {
+    pub dimensions: Vector2<i32>,
+    pub level_count: i32,
+    pub texture_name: gl::TextureName,
+    pub framebuffer_name: gl::NonDefaultFramebufferName,
+    /// Transform of the camera the pyramid was last built for.
+    pub wld_to_clp: Option<Matrix4<f64>>,
+}
+
+impl HiZ {
+    pub fn new(gl: &gl::Gl, depth_dimensions: Vector2<i32>) -> Self {
+        let dimensions = depth_dimensions.map(|n| std::cmp::max(1, (n + 1) / 2));
+        let level_count = 32 - (std::cmp::max(dimensions.x, dimensions.y) as u32).leading_zeros() as i32;
+
+        unsafe {
+            let texture_name = gl.create_texture(gl::TEXTURE_2D);
+            gl.texture_storage_2d(texture_name, level_count, gl::R32F, dimensions.x, dimensions.y);
+            gl.texture_parameteri(texture_name, gl::TEXTURE_MAX_LEVEL, (level_count - 1) as u32);
+            gl.texture_parameteri(texture_name, gl::TEXTURE_MIN_FILTER, gl::NEAREST);
+            gl.texture_parameteri(texture_name, gl::TEXTURE_MAG_FILTER, gl::NEAREST);
+
+            let framebuffer_name = gl.create_framebuffer();
+            gl.named_framebuffer_draw_buffers(framebuffer_name, &[gl::COLOR_ATTACHMENT0.into()]);
+
+            Self {
+                dimensions,
+                level_count,
+                texture_name,
+                framebuffer_name,
+                wld_to_clp: None,
+            }
+        }
+    }
+
+    pub fn level_dimensions(&self, level: i32) -> Vector2<i32> {
+        self.dimensions.map(|n| std::cmp::max(1, n >> level))
+    }
+
+    pub fn reconcile(&mut self, gl: &gl::Gl, depth_dimensions: Vector2<i32>) {
+        if self.dimensions != depth_dimensions.map(|n| std::cmp::max(1, (n + 1) / 2)) {
+            std::mem::replace(self, HiZ::new(gl, depth_dimensions)).drop(gl);
+        }
+    }
+
+    pub fn drop(mut self, gl: &gl::Gl) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer_name);
+            gl.delete_texture(self.texture_name);
+        }
+    }
+}
+
+/// Textures and buffers of the post processing chain that turns the HDR main
+/// framebuffer into the image that is displayed.
+pub struct PostResources {
+    pub dimensions: Vector2<i32>,
+
+    /// Single sampled copy of the main color when it is multisampled.
+    pub resolve_framebuffer_name: gl::NonDefaultFramebufferName,
+    pub resolve_texture_name: gl::TextureName,
+
+    /// Mip chain starting at half resolution. The number of levels used is
+    /// configurable so the full chain is allocated.
+    pub bloom_dimensions: Vector2<i32>,
+    pub bloom_level_count: i32,
+    pub bloom_framebuffer_name: gl::NonDefaultFramebufferName,
+    pub bloom_texture_name: gl::TextureName,
+
+    /// Tonemapped but still linear, the conversion to sRGB happens when
+    /// blitting to the window.
+    pub display_framebuffer_name: gl::NonDefaultFramebufferName,
+    pub display_texture_name: gl::TextureName,
+
+    pub histogram_buffer_name: gl::BufferName,
+    /// Holds the adapted average luminance and the resulting exposure, see
+    /// `post_renderer::ExposureBuffer`.
+    pub exposure_buffer_name: gl::BufferName,
+}
+
+impl PostResources {
+    const COLOR_FORMAT: gl::symbols::RGBA16F = gl::RGBA16F;
+    pub const HISTOGRAM_BIN_COUNT: usize = 256;
+
+    pub fn new(gl: &gl::Gl, dimensions: Vector2<i32>) -> Self {
+        let bloom_dimensions = dimensions.map(|n| std::cmp::max(1, n / 2));
+        let bloom_level_count =
+            32 - (std::cmp::max(bloom_dimensions.x, bloom_dimensions.y) as u32).leading_zeros() as i32;
+
+        unsafe {
+            let resolve_texture_name = create_texture(gl, Self::COLOR_FORMAT, dimensions, 0);
+            let resolve_framebuffer_name = gl.create_framebuffer();
+            gl.named_framebuffer_texture(resolve_framebuffer_name, gl::COLOR_ATTACHMENT0, resolve_texture_name, 0);
+            gl.named_framebuffer_draw_buffers(resolve_framebuffer_name, &[gl::COLOR_ATTACHMENT0.into()]);
+
+            let bloom_texture_name = gl.create_texture(gl::TEXTURE_2D);
+            gl.texture_storage_2d(
+                bloom_texture_name,
+                bloom_level_count,
+                Self::COLOR_FORMAT,
+                bloom_dimensions.x,
+                bloom_dimensions.y,
+            );
+            gl.texture_parameteri(
+                bloom_texture_name,
+                gl::TEXTURE_MAX_LEVEL,
+                (bloom_level_count - 1) as u32,
+            );
+            gl.texture_parameteri(bloom_texture_name, gl::TEXTURE_MIN_FILTER, gl::LINEAR);
+            gl.texture_parameteri(bloom_texture_name, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
+            gl.texture_parameteri(bloom_texture_name, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE);
+            gl.texture_parameteri(bloom_texture_name, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE);
+            let bloom_framebuffer_name = gl.create_framebuffer();
+            gl.named_framebuffer_draw_buffers(bloom_framebuffer_name, &[gl::COLOR_ATTACHMENT0.into()]);
+
+            let display_texture_name = create_texture(gl, Self::COLOR_FORMAT, dimensions, 0);
+            let display_framebuffer_name = gl.create_framebuffer();
+            gl.named_framebuffer_texture(display_framebuffer_name, gl::COLOR_ATTACHMENT0, display_texture_name, 0);
+            gl.named_framebuffer_draw_buffers(display_framebuffer_name, &[gl::COLOR_ATTACHMENT0.into()]);
+
+            let histogram_buffer_name = gl.create_buffer();
+            gl.named_buffer_data(
+                histogram_buffer_name,
+                [0u32; Self::HISTOGRAM_BIN_COUNT].value_as_bytes(),
+                gl::DYNAMIC_DRAW,
+            );
+
+            let exposure_buffer_name = gl.create_buffer();
+            gl.named_buffer_data(
+                exposure_buffer_name,
+                post_renderer::ExposureBuffer::default().value_as_bytes(),
+                gl::DYNAMIC_DRAW,
+            );
+
+            Self {
+                dimensions,
+
+                resolve_framebuffer_name,
+                resolve_texture_name,
+
+                bloom_dimensions,
+                bloom_level_count,
+                bloom_framebuffer_name,
+                bloom_texture_name,
+
+                display_framebuffer_name,
+                display_texture_name,
+
+                histogram_buffer_name,
+                exposure_buffer_name,
+            }
+        }
+    }
+
+    pub fn bloom_level_dimensions(&self, level: i32) -> Vector2<i32> {
+        self.bloom_dimensions.map(|n| std::cmp::max(1, n >> level))
+    }
+
+    pub fn reconcile(&mut self, gl: &gl::Gl, dimensions: Vector2<i32>) {
+        if self.dimensions != dimensions {
+            std::mem::replace(self, PostResources::new(gl, dimensions)).drop(gl);
+        }
+    }
+
+    pub fn drop(mut self, gl: &gl::Gl) {
+        unsafe {
+            gl.delete_framebuffer(self.resolve_framebuffer_name);
+            gl.delete_texture(self.resolve_texture_name);
+            gl.delete_framebuffer(self.bloom_framebuffer_name);
+            gl.delete_texture(self.bloom_texture_name);
+            gl.delete_framebuffer(self.display_framebuffer_name);
+            gl.delete_texture(self.display_texture_name);
+            gl.delete_buffer(self.histogram_buffer_name);
+            gl.delete_buffer(self.exposure_buffer_name);
+        }
+    }
+}
+
+/// Accumulation targets for weighted blended order independent transparency.
+/// Shares the depth texture of the main framebuffer so transparent fragments
+/// are tested against the opaque geometry.
+pub struct WeightedBlendedOit {
+    pub dimensions: Vector2<i32>,
+    pub sample_count: u32,
+    pub depth_texture_name: gl::TextureName,
+
+    pub framebuffer_name: gl::NonDefaultFramebufferName,
+    pub accum_texture_name: gl::TextureName,
+    /// Sum of -log(1 - alpha), which allows additive blending for both targets.
+    pub revealage_texture_name: gl::TextureName,
+}
+
+impl WeightedBlendedOit {
+    pub fn new(gl: &gl::Gl, main_framebuffer: &MainFramebuffer) -> Self {
+        let MainFramebuffer {
+            dimensions,
+            sample_count,
+            depth_texture_name,
+            ..
+        } = *main_framebuffer;
+
+        unsafe {
+            let framebuffer_name = gl.create_framebuffer();
+
+            let accum_texture_name = create_texture(gl, gl::RGBA16F, dimensions, sample_count);
+            let revealage_texture_name = create_texture(gl, gl::R16F, dimensions, sample_count);
+
+            gl.named_framebuffer_texture(framebuffer_name, gl::DEPTH_STENCIL_ATTACHMENT, depth_texture_name, 0);
+            gl.named_framebuffer_texture(framebuffer_name, gl::COLOR_ATTACHMENT0, accum_texture_name, 0);
+            gl.named_framebuffer_texture(framebuffer_name, gl::COLOR_ATTACHMENT1, revealage_texture_name, 0);
+            gl.named_framebuffer_draw_buffers(
+                framebuffer_name,
+                &[gl::COLOR_ATTACHMENT0.into(), gl::COLOR_ATTACHMENT1.into()],
+            );
+
+            assert_eq!(
+                gl::FramebufferStatus::from(gl::FRAMEBUFFER_COMPLETE),
+                gl.check_named_framebuffer_status(framebuffer_name, gl::FRAMEBUFFER),
+            );
+
+            Self {
+                dimensions,
+                sample_count,
+                depth_texture_name,
+
+                framebuffer_name,
+                accum_texture_name,
+                revealage_texture_name,
+            }
+        }
+    }
+
+    pub fn matches(&self, main_framebuffer: &MainFramebuffer) -> bool {
+        self.dimensions == main_framebuffer.dimensions
+            && self.sample_count == main_framebuffer.sample_count
+            && self.depth_texture_name == main_framebuffer.depth_texture_name
+    }
+
+    pub fn drop(mut self, gl: &gl::Gl) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer_name);
+            gl.delete_texture(self.accum_texture_name);
+            gl.delete_texture(self.revealage_texture_name);
+        }
+    }
+}
+
+/// Per pixel linked lists of transparent fragments. The head buffer starts
+/// with the number of allocated nodes followed by one head per pixel, zero
+/// marks the end of a list and other values are node indices plus one.
+pub struct LinkedListOit {
+    pub dimensions: Vector2<i32>,
+    pub node_capacity: usize,
+    pub head_buffer_name: gl::BufferName,
+    pub node_buffer_name: gl::BufferName,
+}
+
+impl LinkedListOit {
+    /// Matches the node layout in `oit_linked_list.glsl`.
+    pub const NODE_BYTES: usize = 16;
+
+    pub fn new(gl: &gl::Gl, dimensions: Vector2<i32>, fragments_per_pixel: u32) -> Self {
+        let pixel_count = (dimensions.x * dimensions.y) as usize;
+        let node_capacity = pixel_count * fragments_per_pixel as usize;
+
+        unsafe {
+            let head_buffer_name = gl.create_buffer();
+            gl.named_buffer_reserve(
+                head_buffer_name,
+                (1 + pixel_count) * std::mem::size_of::<u32>(),
+                gl::DYNAMIC_DRAW,
+            );
+
+            let node_buffer_name = gl.create_buffer();
+            gl.named_buffer_reserve(node_buffer_name, node_capacity * Self::NODE_BYTES, gl::DYNAMIC_DRAW);
+
+            Self {
+                dimensions,
+                node_capacity,
+                head_buffer_name,
+                node_buffer_name,
+            }
+        }
+    }
+
+    pub fn matches(&self, dimensions: Vector2<i32>, fragments_per_pixel: u32) -> bool {
+        self.dimensions == dimensions
+            && self.node_capacity == (dimensions.x * dimensions.y) as usize * fragments_per_pixel as usize
+    }
+
+    pub fn head_buffer_bytes(&self) -> usize {
+        (1 + (self.dimensions.x * self.dimensions.y) as usize) * std::mem::size_of::<u32>()
+    }
+
+    pub fn drop(mut self, gl: &gl::Gl) {
+        unsafe {
+            gl.delete_buffer(self.head_buffer_name);
+            gl.delete_buffer(self.node_buffer_name);
+        }
+    }
+}
+
 pub struct MainProfilers {
     pub depth_profiler: SampleIndex,
     pub depth_opaque_profiler: SampleIndex,
@@ -97,6 +400,13 @@ pub struct MainProfilers {
     pub basic_opaque_profiler: SampleIndex,
     pub basic_masked_profiler: SampleIndex,
     pub basic_transparent_profiler: SampleIndex,
+    pub hiz_profiler: SampleIndex,
+    pub post_profiler: SampleIndex,
+    pub post_exposure_profiler: SampleIndex,
+    pub post_bloom_profiler: SampleIndex,
+    pub post_tonemap_profiler: SampleIndex,
+    pub foveation_mask_profiler: SampleIndex,
+    pub foveation_reconstruct_profiler: SampleIndex,
 }
 
 impl MainProfilers {
@@ -110,30 +420,77 @@ impl MainProfilers {
             basic_opaque_profiler: profiling_context.add_sample("opaque"),
             basic_masked_profiler: profiling_context.add_sample("masked"),
             basic_transparent_profiler: profiling_context.add_sample("transparent"),
+
+            hiz_profiler: profiling_context.add_sample("hi-z"),
+
+            post_profiler: profiling_context.add_sample("post"),
+            post_exposure_profiler: profiling_context.add_sample("exposure"),
+            post_bloom_profiler: profiling_context.add_sample("bloom"),
+            post_tonemap_profiler: profiling_context.add_sample("tonemap"),
+
+            foveation_mask_profiler: profiling_context.add_sample("fov mask"),
+            foveation_reconstruct_profiler: profiling_context.add_sample("fov reconstruct"),
         }
     }
 }
 
+/// Maps the clip space of an eye to its half of a side by side framebuffer.
+fn eye_clp_to_ren_clp(eye: Eye) -> Matrix4<f64> {
+    let offset = match eye {
+        Eye::Left => -0.5,
+        Eye::Right => 0.5,
+    };
+    Matrix4::from_translation(Vector3::new(offset, 0.0, 0.0)) * Matrix4::from_nonuniform_scale(0.5, 1.0, 1.0)
+}
+
+fn ren_clp_to_eye_clp(eye: Eye) -> Matrix4<f64> {
+    eye_clp_to_ren_clp(eye).invert().unwrap()
+}
+
 pub struct MainResourcesParameters<'a> {
     pub gl: &'a gl::Gl,
     pub profiling_context: &'a mut profiling::ProfilingContext,
     pub camera: CameraParameters,
+    pub eyes: Option<EyeMap<CameraParameters>>,
     pub draw_resources_index: usize,
     pub cluster_resources_index: Option<ClusterResourcesIndex>,
     pub dimensions: Vector2<i32>,
     pub sample_count: u32,
     pub display_viewport: Option<Viewport<i32>>,
+    pub display_source: Option<Viewport<i32>>,
     pub should_render: bool,
+    pub foveated: bool,
 }
 
 pub struct MainResources {
+    /// Used for culling and clustering. Encloses both eyes with instanced
+    /// stereo.
     pub camera: CameraParameters,
+    /// With instanced stereo the eyes are rendered side by side in a single
+    /// pass, see `stereo_view.glsl`.
+    pub eyes: Option<EyeMap<CameraParameters>>,
+    /// Holds the `CameraBuffer` of the views.
+    pub camera_buffer_name: gl::BufferName,
     pub draw_resources_index: usize,
     pub cluster_resources_index: Option<ClusterResourcesIndex>,
     pub display_viewport: Option<Viewport<i32>>,
+    /// The part of the framebuffer that is shown in `display_viewport`, all
+    /// of it when `None`.
+    pub display_source: Option<Viewport<i32>>,
     pub depth_available: bool,
     pub should_render: bool,
+    /// Whether the periphery is masked, see `foveation_renderer`.
+    pub foveated: bool,
     pub framebuffer: MainFramebuffer,
+    /// Built from the depth of the previous frame rendered with these resources.
+    pub hiz: HiZ,
+    /// Culling counts of this view. Like `hiz`, they come from earlier frames
+    /// rendered with these resources.
+    pub cull_stats: resources::CullStatsRing,
+    pub post: PostResources,
+    /// Only allocated while the corresponding transparency mode is used.
+    pub weighted_blended_oit: Option<WeightedBlendedOit>,
+    pub linked_list_oit: Option<LinkedListOit>,
     pub profilers: MainProfilers,
 }
 
@@ -143,23 +500,37 @@ impl MainResources {
             gl,
             profiling_context,
             camera,
+            eyes,
             draw_resources_index,
             cluster_resources_index,
             dimensions,
             sample_count,
             display_viewport,
+            display_source,
             should_render,
+            foveated,
         } = parameters;
-        Self {
+        let main_resources = Self {
             camera,
+            eyes,
+            camera_buffer_name: unsafe { gl.create_buffer() },
             draw_resources_index,
             cluster_resources_index,
             display_viewport,
+            display_source,
             should_render,
+            foveated,
             depth_available: false,
             framebuffer: MainFramebuffer::new(gl, dimensions, sample_count),
+            hiz: HiZ::new(gl, dimensions),
+            cull_stats: resources::CullStatsRing::new(gl),
+            post: PostResources::new(gl, dimensions),
+            weighted_blended_oit: None,
+            linked_list_oit: None,
             profilers: MainProfilers::new(profiling_context),
-        }
+        };
+        main_resources.update_camera_buffer(gl);
+        main_resources
     }
 
     pub fn reconcile(&mut self, parameters: MainResourcesParameters) {
@@ -167,19 +538,154 @@ impl MainResources {
             gl,
             profiling_context,
             camera,
+            eyes,
             draw_resources_index,
             cluster_resources_index,
             dimensions,
             sample_count,
             display_viewport,
+            display_source,
             should_render,
+            foveated,
         } = parameters;
         self.camera = camera;
+        self.eyes = eyes;
         self.draw_resources_index = draw_resources_index;
         self.cluster_resources_index = cluster_resources_index;
         self.display_viewport = display_viewport;
+        self.display_source = display_source;
         self.depth_available = false;
         self.should_render = should_render;
+        self.foveated = foveated;
         self.framebuffer.reconcile(gl, dimensions, sample_count);
+        self.hiz.reconcile(gl, dimensions);
+        self.post.reconcile(gl, dimensions);
+        self.update_camera_buffer(gl);
+    }
+
+    /// Number of times every instance is drawn.
+    pub fn view_count(&self) -> usize {
+        match self.eyes {
+            Some(_) => 2,
+            None => 1,
+        }
+    }
+
+    /// Transforms from world space to the clip space of the whole framebuffer
+    /// for every view.
+    pub fn wld_to_ren_clp(&self) -> Vec<Matrix4<f64>> {
+        match self.eyes {
+            Some(ref eyes) => EYE_KEYS
+                .iter()
+                .map(|&eye| eye_clp_to_ren_clp(eye) * eyes[eye].wld_to_clp)
+                .collect(),
+            None => vec![self.camera.wld_to_clp],
+        }
+    }
+
+    /// Inverse of `wld_to_ren_clp`, mono rendering repeats its camera.
+    pub fn ren_clp_to_wld(&self) -> [Matrix4<f64>; rendering::MAX_VIEW_COUNT] {
+        match self.eyes {
+            Some(ref eyes) => [
+                eyes.left.clp_to_wld * ren_clp_to_eye_clp(Eye::Left),
+                eyes.right.clp_to_wld * ren_clp_to_eye_clp(Eye::Right),
+            ],
+            None => [self.camera.clp_to_wld; rendering::MAX_VIEW_COUNT],
+        }
+    }
+
+    /// The cameras of the views with the part of the framebuffer they cover,
+    /// for passes that render the views one at a time.
+    pub fn views(&self) -> Vec<(Viewport<i32>, &CameraParameters)> {
+        let dimensions = self.framebuffer.dimensions;
+        match self.eyes {
+            Some(ref eyes) => {
+                let half = dimensions.x / 2;
+                vec![
+                    (
+                        Viewport::from_coordinates(Point2::origin(), Point2::new(half, dimensions.y)),
+                        &eyes.left,
+                    ),
+                    (
+                        Viewport::from_coordinates(Point2::new(half, 0), Point2::new(dimensions.x, dimensions.y)),
+                        &eyes.right,
+                    ),
+                ]
+            }
+            None => vec![(Viewport::from_dimensions(dimensions), &self.camera)],
+        }
+    }
+
+    /// Clips the views to their half of the framebuffer while drawing
+    /// instanced, requires the vertex shader to call `stereo_view_clip`.
+    pub unsafe fn enable_view_clipping(&self, gl: &gl::Gl) {
+        if self.eyes.is_some() {
+            gl.enable(gl::CLIP_DISTANCE0);
+        }
+    }
+
+    pub unsafe fn disable_view_clipping(&self, gl: &gl::Gl) {
+        if self.eyes.is_some() {
+            gl.disable(gl::CLIP_DISTANCE0);
+        }
+    }
+
+    fn update_camera_buffer(&self, gl: &gl::Gl) {
+        let mut data = CameraBuffer {
+            wld_to_cam: [Matrix4::zero(); rendering::MAX_VIEW_COUNT],
+            cam_to_wld: [Matrix4::zero(); rendering::MAX_VIEW_COUNT],
+            cam_to_clp: [Matrix4::zero(); rendering::MAX_VIEW_COUNT],
+            clp_to_cam: [Matrix4::zero(); rendering::MAX_VIEW_COUNT],
+            cam_pos_in_lgt: [Vector4::zero(); rendering::MAX_VIEW_COUNT],
+        };
+
+        for view_index in 0..rendering::MAX_VIEW_COUNT {
+            let (camera, cam_to_ren_clp) = match self.eyes {
+                Some(ref eyes) => {
+                    let eye = EYE_KEYS[view_index];
+                    (&eyes[eye], eye_clp_to_ren_clp(eye) * eyes[eye].cam_to_clp)
+                }
+                None => (&self.camera, self.camera.cam_to_clp),
+            };
+            data.wld_to_cam[view_index] = camera.wld_to_cam.cast().unwrap();
+            data.cam_to_wld[view_index] = camera.cam_to_wld.cast().unwrap();
+            data.cam_to_clp[view_index] = cam_to_ren_clp.cast().unwrap();
+            data.clp_to_cam[view_index] = cam_to_ren_clp.invert().unwrap().cast().unwrap();
+            data.cam_pos_in_lgt[view_index] = camera.cam_pos_in_wld().to_homogeneous().cast().unwrap();
+        }
+
+        unsafe {
+            gl.named_buffer_data(self.camera_buffer_name, data.value_as_bytes(), gl::DYNAMIC_DRAW);
+        }
+    }
+
+    /// Allocates the resources needed for `configuration.mode` and frees
+    /// those of the other modes.
+    pub fn reconcile_oit(&mut self, gl: &gl::Gl, configuration: &configuration::TransparencyConfiguration) {
+        let weighted_blended = configuration.mode == configuration::TransparencyMode::WeightedBlended;
+        match self.weighted_blended_oit.take() {
+            Some(oit) if weighted_blended && oit.matches(&self.framebuffer) => {
+                self.weighted_blended_oit = Some(oit);
+            }
+            Some(oit) => oit.drop(gl),
+            None => {}
+        }
+        if weighted_blended && self.weighted_blended_oit.is_none() {
+            self.weighted_blended_oit = Some(WeightedBlendedOit::new(gl, &self.framebuffer));
+        }
+
+        let linked_list = configuration.mode == configuration::TransparencyMode::LinkedList;
+        let dimensions = self.framebuffer.dimensions;
+        let fragments_per_pixel = configuration.linked_list_fragments_per_pixel;
+        match self.linked_list_oit.take() {
+            Some(oit) if linked_list && oit.matches(dimensions, fragments_per_pixel) => {
+                self.linked_list_oit = Some(oit);
+            }
+            Some(oit) => oit.drop(gl),
+            None => {}
+        }
+        if linked_list && self.linked_list_oit.is_none() {
+            self.linked_list_oit = Some(LinkedListOit::new(gl, dimensions, fragments_per_pixel));
+        }
     }
 }
@@ -0,0 +1,439 @@
+//! Mesh simplification through quadric edge collapse, see "Surface
+//! Simplification Using Quadric Error Metrics" by Garland and Heckbert.
+//!
+//! Edges are collapsed onto one of their existing vertices so that every level
+//! of detail can share the vertex buffer of the original mesh. Vertices on
+//! attribute seams and open borders are never moved, and collapses that would
+//! make the surface non-manifold or flip a triangle are rejected.
+
+use cgmath::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+pub type Triangle = [u32; 3];
+
+/// Symmetric 4x4 matrix stored as its upper triangle.
+#[derive(Debug, Default, Copy, Clone)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(n: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let [a, b, c] = [n.x, n.y, n.z];
+        let mut q = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d];
+        for v in q.iter_mut() {
+            *v *= weight;
+        }
+        Quadric(q)
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: Point3<f64>) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = [p.x, p.y, p.z];
+        x * x * q[0]
+            + 2.0 * x * y * q[1]
+            + 2.0 * x * z * q[2]
+            + 2.0 * x * q[3]
+            + y * y * q[4]
+            + 2.0 * y * z * q[5]
+            + 2.0 * y * q[6]
+            + z * z * q[7]
+            + 2.0 * z * q[8]
+            + q[9]
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Collapse {
+    /// Bits of a non-negative `f64` which order the same as the value.
+    cost_bits: u64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+
+/// Marks the vertices that share their position with another vertex or lie on
+/// an open border. Moving these would tear the surface.
+fn locked_vertices(positions: &[Point3<f32>], triangles: &[Triangle]) -> Vec<bool> {
+    use std::collections::HashMap;
+
+    let mut locked = vec![false; positions.len()];
+
+    let mut position_to_vertex: HashMap<[u32; 3], u32> = HashMap::new();
+    for (vertex_index, p) in positions.iter().enumerate() {
+        let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+        if let Some(&other) = position_to_vertex.get(&key) {
+            locked[other as usize] = true;
+            locked[vertex_index] = true;
+        } else {
+            position_to_vertex.insert(key, vertex_index as u32);
+        }
+    }
+
+    let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+    for t in triangles.iter() {
+        for i in 0..3 {
+            let (a, b) = (t[i], t[(i + 1) % 3]);
+            *edge_counts.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    for (&(a, b), &count) in edge_counts.iter() {
+        if count == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    locked
+}
+
+fn triangle_normal(positions: &[Point3<f64>], t: Triangle) -> Vector3<f64> {
+    let [p0, p1, p2] = [
+        positions[t[0] as usize],
+        positions[t[1] as usize],
+        positions[t[2] as usize],
+    ];
+    (p1 - p0).cross(p2 - p0)
+}
+
+/// The vertices that share a remaining triangle with `v`.
+fn vertex_neighbours(triangles: &[Triangle], removed: &[bool], vertex_triangles: &[u32], v: u32) -> Vec<u32> {
+    let mut neighbours: Vec<u32> = vertex_triangles
+        .iter()
+        .filter(|&&triangle_index| !removed[triangle_index as usize])
+        .flat_map(|&triangle_index| triangles[triangle_index as usize].to_vec())
+        .filter(|&n| n != v)
+        .collect();
+    neighbours.sort();
+    neighbours.dedup();
+    neighbours
+}
+
+/// Collapses edges in order of increasing error until at most
+/// `target_triangle_count` triangles remain or no collapse is possible.
+pub fn simplify(positions: &[Point3<f32>], triangles: &[Triangle], target_triangle_count: usize) -> Vec<Triangle> {
+    let vertex_count = positions.len();
+    let locked = locked_vertices(positions, triangles);
+    let positions: Vec<Point3<f64>> = positions.iter().map(|p| p.cast().unwrap()).collect();
+
+    let mut triangles: Vec<Triangle> = triangles.to_vec();
+    let mut removed = vec![false; triangles.len()];
+    let mut triangle_count = triangles.len();
+
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    let mut vertex_triangles: Vec<Vec<u32>> = vec![Vec::new(); vertex_count];
+
+    for (triangle_index, &t) in triangles.iter().enumerate() {
+        let n = triangle_normal(&positions, t);
+        let area = n.magnitude();
+        if area > 0.0 {
+            let n = n / area;
+            let d = -n.dot(positions[t[0] as usize].to_vec());
+            let q = Quadric::from_plane(n, d, area);
+            for &v in t.iter() {
+                quadrics[v as usize].add(&q);
+            }
+        }
+        for &v in t.iter() {
+            vertex_triangles[v as usize].push(triangle_index as u32);
+        }
+    }
+
+    let mut versions = vec![0u32; vertex_count];
+    let mut heap = BinaryHeap::new();
+
+    let push_edges =
+        |heap: &mut BinaryHeap<Reverse<Collapse>>, quadrics: &[Quadric], versions: &[u32], from: u32, to: u32| {
+            for &(from, to) in [(from, to), (to, from)].iter() {
+                if locked[from as usize] {
+                    continue;
+                }
+                let mut q = quadrics[from as usize];
+                q.add(&quadrics[to as usize]);
+                heap.push(Reverse(Collapse {
+                    cost_bits: q.error(positions[to as usize]).max(0.0).to_bits(),
+                    from,
+                    to,
+                    from_version: versions[from as usize],
+                    to_version: versions[to as usize],
+                }));
+            }
+        };
+
+    for t in triangles.iter() {
+        for i in 0..3 {
+            let (a, b) = (t[i], t[(i + 1) % 3]);
+            if a < b {
+                push_edges(&mut heap, &quadrics, &versions, a, b);
+            }
+        }
+    }
+
+    while triangle_count > target_triangle_count {
+        let Collapse {
+            from,
+            to,
+            from_version,
+            to_version,
+            ..
+        } = match heap.pop() {
+            Some(Reverse(collapse)) => collapse,
+            None => break,
+        };
+
+        if versions[from as usize] != from_version || versions[to as usize] != to_version {
+            continue;
+        }
+
+        // The link condition: the vertices adjacent to both ends of the edge
+        // must be exactly the opposite vertices of the triangles on the edge.
+        // Otherwise the collapse makes the surface non-manifold. An opposite
+        // vertex with three neighbours would be left with two triangles that
+        // cover each other, which also rules out collapsing a tetrahedron.
+        let edge_opposites = {
+            let mut opposites: Vec<u32> = vertex_triangles[from as usize]
+                .iter()
+                .map(|&triangle_index| triangle_index as usize)
+                .filter(|&triangle_index| !removed[triangle_index] && triangles[triangle_index].contains(&to))
+                .flat_map(|triangle_index| triangles[triangle_index].to_vec())
+                .filter(|&v| v != from && v != to)
+                .collect();
+            opposites.sort();
+            opposites.dedup();
+            opposites
+        };
+        if edge_opposites.is_empty() {
+            continue;
+        }
+        let to_neighbours = vertex_neighbours(&triangles, &removed, &vertex_triangles[to as usize], to);
+        let common_neighbour_count = vertex_neighbours(&triangles, &removed, &vertex_triangles[from as usize], from)
+            .iter()
+            .filter(|v| to_neighbours.binary_search(v).is_ok())
+            .count();
+        if common_neighbour_count != edge_opposites.len() {
+            continue;
+        }
+        if edge_opposites
+            .iter()
+            .any(|&v| vertex_neighbours(&triangles, &removed, &vertex_triangles[v as usize], v).len() <= 3)
+        {
+            continue;
+        }
+
+        // Reject collapses that flip or degenerate a remaining triangle.
+        let flips = vertex_triangles[from as usize].iter().any(|&triangle_index| {
+            let t = triangles[triangle_index as usize];
+            if removed[triangle_index as usize] || t.contains(&to) {
+                return false;
+            }
+            let n0 = triangle_normal(&positions, t);
+            let n1 = triangle_normal(&positions, t.map_vertex(from, to));
+            n0.dot(n1) <= 0.0
+        });
+        if flips {
+            continue;
+        }
+
+        let from_triangles = std::mem::replace(&mut vertex_triangles[from as usize], Vec::new());
+        for triangle_index in from_triangles {
+            if removed[triangle_index as usize] {
+                continue;
+            }
+            let t = &mut triangles[triangle_index as usize];
+            if t.contains(&to) {
+                removed[triangle_index as usize] = true;
+                triangle_count -= 1;
+            } else {
+                *t = t.map_vertex(from, to);
+                vertex_triangles[to as usize].push(triangle_index);
+            }
+        }
+
+        let from_quadric = quadrics[from as usize];
+        quadrics[to as usize].add(&from_quadric);
+        versions[from as usize] = u32::max_value();
+        versions[to as usize] += 1;
+
+        vertex_triangles[to as usize].retain(|&triangle_index| !removed[triangle_index as usize]);
+        for neighbour in vertex_neighbours(&triangles, &removed, &vertex_triangles[to as usize], to) {
+            push_edges(&mut heap, &quadrics, &versions, to, neighbour);
+        }
+    }
+
+    triangles
+        .into_iter()
+        .zip(removed.into_iter())
+        .filter_map(|(t, removed)| if removed { None } else { Some(t) })
+        .collect()
+}
+
+trait MapVertex {
+    fn map_vertex(self, from: u32, to: u32) -> Self;
+}
+
+impl MapVertex for Triangle {
+    fn map_vertex(self, from: u32, to: u32) -> Self {
+        let f = |v: u32| if v == from { to } else { v };
+        [f(self[0]), f(self[1]), f(self[2])]
+    }
+}
+
+/// Generates up to `max_lod_count` successively halved levels of detail,
+/// excluding the original. Stops early when a level would have fewer than
+/// `min_triangle_count` triangles or simplification stalls.
+pub fn generate_lods(
+    positions: &[Point3<f32>],
+    triangles: &[Triangle],
+    max_lod_count: usize,
+    min_triangle_count: usize,
+) -> Vec<Vec<Triangle>> {
+    let mut lods: Vec<Vec<Triangle>> = Vec::new();
+    for _ in 0..max_lod_count {
+        let previous = lods.last().map(Vec::as_slice).unwrap_or(triangles);
+        let target_triangle_count = previous.len() / 2;
+        if target_triangle_count < min_triangle_count {
+            break;
+        }
+        let lod = simplify(positions, previous, target_triangle_count);
+        // Require at least a quarter fewer triangles to be worth a level.
+        if lod.len() * 4 > previous.len() * 3 {
+            break;
+        }
+        lods.push(lod);
+    }
+    lods
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A closed box made of subdivided faces with unique vertex positions.
+    fn subdivided_cube(n: u32) -> (Vec<Point3<f32>>, Vec<Triangle>) {
+        use std::collections::HashMap;
+
+        let mut positions = Vec::new();
+        let mut position_to_index: HashMap<[i32; 3], u32> = HashMap::new();
+        let mut triangles = Vec::new();
+
+        let mut index = |p: [i32; 3], positions: &mut Vec<Point3<f32>>| {
+            *position_to_index.entry(p).or_insert_with(|| {
+                positions.push(Point3::new(p[0] as f32, p[1] as f32, p[2] as f32));
+                (positions.len() - 1) as u32
+            })
+        };
+
+        let n = n as i32;
+        for &(axis, side) in [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)].iter() {
+            let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+            let point = |u: i32, v: i32| {
+                let mut p = [0; 3];
+                p[axis] = side * n;
+                p[u_axis] = u;
+                p[v_axis] = v;
+                p
+            };
+            for u in 0..n {
+                for v in 0..n {
+                    let q = [
+                        index(point(u, v), &mut positions),
+                        index(point(u + 1, v), &mut positions),
+                        index(point(u + 1, v + 1), &mut positions),
+                        index(point(u, v + 1), &mut positions),
+                    ];
+                    if side == 1 {
+                        triangles.push([q[0], q[1], q[2]]);
+                        triangles.push([q[2], q[3], q[0]]);
+                    } else {
+                        triangles.push([q[0], q[2], q[1]]);
+                        triangles.push([q[2], q[0], q[3]]);
+                    }
+                }
+            }
+        }
+
+        (positions, triangles)
+    }
+
+    #[test]
+    fn simplifies_flat_faces_without_error() {
+        let (positions, triangles) = subdivided_cube(4);
+        let simplified = simplify(&positions, &triangles, 0);
+        assert!(simplified.len() < triangles.len() / 4);
+
+        // All remaining vertices lie on the surface of the cube.
+        for t in simplified.iter() {
+            for &v in t.iter() {
+                let p = positions[v as usize];
+                assert!([p.x, p.y, p.z].iter().any(|&c| c == 0.0 || c == 4.0));
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_closed_surface_manifold() {
+        use std::collections::HashMap;
+
+        let (positions, triangles) = subdivided_cube(4);
+        let simplified = simplify(&positions, &triangles, 0);
+
+        let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+        for t in simplified.iter() {
+            assert!(t[0] != t[1] && t[1] != t[2] && t[2] != t[0]);
+            for i in 0..3 {
+                let (a, b) = (t[i], t[(i + 1) % 3]);
+                *edge_counts.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        assert!(edge_counts.values().all(|&count| count == 2));
+    }
+
+    #[test]
+    fn stops_at_tetrahedron() {
+        let positions = vec![
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(-0.5, 0.866, 0.0),
+            Point3::new(-0.5, -0.866, 0.0),
+        ];
+        let mut triangles = Vec::new();
+        for i in 0..3 {
+            let (e0, e1) = (2 + i, 2 + (i + 1) % 3);
+            triangles.push([0, e0, e1]);
+            triangles.push([1, e1, e0]);
+        }
+        assert_eq!(4, simplify(&positions, &triangles, 0).len());
+    }
+
+    #[test]
+    fn keeps_seam_vertices() {
+        let (mut positions, mut triangles) = subdivided_cube(4);
+        // Duplicate a vertex to create an attribute seam.
+        let seam = triangles[0][0];
+        positions.push(positions[seam as usize]);
+        triangles[0][0] = (positions.len() - 1) as u32;
+
+        let simplified = simplify(&positions, &triangles, 0);
+        assert!(simplified.iter().any(|t| t.contains(&seam)));
+        assert!(simplified.iter().any(|t| t.contains(&((positions.len() - 1) as u32))));
+    }
+
+    #[test]
+    fn generates_halving_lods() {
+        let (positions, triangles) = subdivided_cube(8);
+        let lods = generate_lods(&positions, &triangles, 3, 16);
+        assert!(!lods.is_empty());
+        let mut previous = triangles.len();
+        for lod in lods.iter() {
+            assert!(lod.len() <= previous / 2);
+            previous = lod.len();
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::*;
+
+pub struct Renderer {
+    pub histogram_program: rendering::Program,
+    pub exposure_program: rendering::Program,
+    pub bloom_prefilter_program: rendering::Program,
+    pub bloom_downsample_program: rendering::Program,
+    pub bloom_upsample_program: rendering::Program,
+    pub tonemap_program: rendering::Program,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {
+        HISTOGRAM_BUFFER_BINDING = 0;
+        EXPOSURE_BUFFER_BINDING = 1;
+
+        COLOR_SAMPLER_BINDING = 0;
+        BLOOM_SAMPLER_BINDING = 1;
+    },
+    uniforms: {
+        MIN_LOG_LUMINANCE_LOC = 0;
+        LOG_LUMINANCE_RANGE_LOC = 1;
+        PIXEL_COUNT_LOC = 2;
+        ADAPTATION_LOC = 3;
+        AUTO_EXPOSURE_LOC = 4;
+        EXPOSURE_LOC = 5;
+        BLOOM_THRESHOLD_LOC = 6;
+        BLOOM_INTENSITY_LOC = 7;
+    },
+});
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct ExposureBuffer {
+    pub average_luminance: f32,
+    pub exposure: f32,
+}
+
+impl Default for ExposureBuffer {
+    fn default() -> Self {
+        Self {
+            average_luminance: 0.18,
+            exposure: 1.0,
+        }
+    }
+}
+
+pub struct Parameters {
+    pub main_resources_index: usize,
+}
+
+impl Context<'_> {
+    /// Applies exposure, bloom and tonemapping to the color of a main
+    /// resources and writes the result to its display framebuffer.
+    pub fn render_post(&mut self, params: Parameters) {
+        let Context {
+            ref gl,
+            ref resources,
+            ref configuration,
+            post_renderer: ref mut renderer,
+            ..
+        } = *self;
+
+        let cfg = &configuration.post;
+        let main_resources = &self.main_resources_pool[params.main_resources_index];
+        let framebuffer = &main_resources.framebuffer;
+        let post = &main_resources.post;
+        let profilers = &main_resources.profilers;
+
+        let post_profiler_index = self.profiling_context.start(gl, profilers.post_profiler);
+
+        unsafe {
+            let color_texture_name = if framebuffer.sample_count == 0 {
+                framebuffer.color_texture_name
+            } else {
+                gl.blit_named_framebuffer(
+                    framebuffer.framebuffer_name.into(),
+                    post.resolve_framebuffer_name.into(),
+                    0,
+                    0,
+                    framebuffer.dimensions.x,
+                    framebuffer.dimensions.y,
+                    0,
+                    0,
+                    post.dimensions.x,
+                    post.dimensions.y,
+                    gl::BlitMask::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+                post.resolve_texture_name
+            };
+
+            let (auto_exposure, exposure) = if cfg.auto_exposure.enabled {
+                (1, 2.0f32.powf(cfg.auto_exposure.compensation))
+            } else {
+                (0, cfg.exposure)
+            };
+
+            gl.bind_buffer_base(
+                gl::SHADER_STORAGE_BUFFER,
+                HISTOGRAM_BUFFER_BINDING,
+                post.histogram_buffer_name,
+            );
+            gl.bind_buffer_base(
+                gl::SHADER_STORAGE_BUFFER,
+                EXPOSURE_BUFFER_BINDING,
+                post.exposure_buffer_name,
+            );
+            gl.bind_texture_unit(COLOR_SAMPLER_BINDING, color_texture_name);
+
+            if cfg.auto_exposure.enabled {
+                let profiler_index = self.profiling_context.start(gl, profilers.post_exposure_profiler);
+
+                renderer.histogram_program.update(&mut rendering_context!(self));
+                renderer.exposure_program.update(&mut rendering_context!(self));
+                if let (&ProgramName::Linked(histogram_program), &ProgramName::Linked(exposure_program)) =
+                    (&renderer.histogram_program.name, &renderer.exposure_program.name)
+                {
+                    let min_log_luminance = cfg.auto_exposure.min_log_luminance;
+                    let log_luminance_range = cfg.auto_exposure.max_log_luminance - min_log_luminance;
+
+                    gl.use_program(histogram_program);
+                    gl.uniform_1f(MIN_LOG_LUMINANCE_LOC, min_log_luminance);
+                    gl.uniform_1f(LOG_LUMINANCE_RANGE_LOC, log_luminance_range);
+                    gl.dispatch_compute(
+                        (post.dimensions.x as u32).ceiled_div(16),
+                        (post.dimensions.y as u32).ceiled_div(16),
+                        1,
+                    );
+                    gl.memory_barrier(gl::MemoryBarrierFlag::SHADER_STORAGE);
+
+                    // The simulation runs at a fixed rate so adapt per update
+                    // rather than per measured frame time.
+                    let delta_time = 1.0 / DESIRED_UPS as f32;
+
+                    gl.use_program(exposure_program);
+                    gl.uniform_1f(MIN_LOG_LUMINANCE_LOC, min_log_luminance);
+                    gl.uniform_1f(LOG_LUMINANCE_RANGE_LOC, log_luminance_range);
+                    gl.uniform_1ui(PIXEL_COUNT_LOC, (post.dimensions.x * post.dimensions.y) as u32);
+                    gl.uniform_1f(
+                        ADAPTATION_LOC,
+                        1.0 - (-delta_time * cfg.auto_exposure.adaptation_rate).exp(),
+                    );
+                    gl.dispatch_compute(1, 1, 1);
+                    gl.memory_barrier(gl::MemoryBarrierFlag::SHADER_STORAGE);
+
+                    gl.unuse_program();
+                }
+
+                self.profiling_context.stop(gl, profiler_index);
+            }
+
+            gl.disable(gl::DEPTH_TEST);
+            gl.disable(gl::CULL_FACE);
+            gl.depth_mask(gl::WriteMask::Disabled);
+            gl.bind_vertex_array(resources.full_screen_vao);
+
+            let bloom_level_count = std::cmp::max(1, std::cmp::min(cfg.bloom.levels as i32, post.bloom_level_count));
+
+            if cfg.bloom.enabled {
+                let profiler_index = self.profiling_context.start(gl, profilers.post_bloom_profiler);
+
+                renderer.bloom_prefilter_program.update(&mut rendering_context!(self));
+                renderer.bloom_downsample_program.update(&mut rendering_context!(self));
+                renderer.bloom_upsample_program.update(&mut rendering_context!(self));
+                if let (
+                    &ProgramName::Linked(prefilter_program),
+                    &ProgramName::Linked(downsample_program),
+                    &ProgramName::Linked(upsample_program),
+                ) = (
+                    &renderer.bloom_prefilter_program.name,
+                    &renderer.bloom_downsample_program.name,
+                    &renderer.bloom_upsample_program.name,
+                ) {
+                    let draw = |level: i32| {
+                        gl.named_framebuffer_texture(
+                            post.bloom_framebuffer_name,
+                            gl::COLOR_ATTACHMENT0,
+                            post.bloom_texture_name,
+                            level,
+                        );
+                        let dimensions = post.bloom_level_dimensions(level);
+                        gl.viewport(0, 0, dimensions.x, dimensions.y);
+                        gl.draw_elements(
+                            gl::TRIANGLES,
+                            (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+                            gl::UNSIGNED_INT,
+                            0,
+                        );
+                    };
+
+                    // Restrict sampling to a single level to avoid a feedback loop.
+                    let sample_level = |level: i32| {
+                        gl.texture_parameteri(post.bloom_texture_name, gl::TEXTURE_BASE_LEVEL, level as u32);
+                        gl.texture_parameteri(post.bloom_texture_name, gl::TEXTURE_MAX_LEVEL, level as u32);
+                        gl.bind_texture_unit(BLOOM_SAMPLER_BINDING, post.bloom_texture_name);
+                    };
+
+                    gl.bind_framebuffer(gl::FRAMEBUFFER, post.bloom_framebuffer_name);
+                    gl.disable(gl::BLEND);
+
+                    gl.use_program(prefilter_program);
+                    gl.uniform_1ui(AUTO_EXPOSURE_LOC, auto_exposure);
+                    gl.uniform_1f(EXPOSURE_LOC, exposure);
+                    gl.uniform_1f(BLOOM_THRESHOLD_LOC, cfg.bloom.threshold);
+                    draw(0);
+
+                    gl.use_program(downsample_program);
+                    for level in 1..bloom_level_count {
+                        sample_level(level - 1);
+                        draw(level);
+                    }
+
+                    // Accumulate the blurred lower levels back up the chain.
+                    gl.enable(gl::BLEND);
+                    gl.blend_func(gl::ONE, gl::ONE);
+                    gl.use_program(upsample_program);
+                    for level in (0..bloom_level_count - 1).rev() {
+                        sample_level(level + 1);
+                        draw(level);
+                    }
+                    gl.disable(gl::BLEND);
+
+                    gl.texture_parameteri(post.bloom_texture_name, gl::TEXTURE_BASE_LEVEL, 0u32);
+                    gl.texture_parameteri(
+                        post.bloom_texture_name,
+                        gl::TEXTURE_MAX_LEVEL,
+                        (post.bloom_level_count - 1) as u32,
+                    );
+
+                    gl.unuse_program();
+                }
+
+                self.profiling_context.stop(gl, profiler_index);
+            }
+
+            {
+                let profiler_index = self.profiling_context.start(gl, profilers.post_tonemap_profiler);
+
+                renderer.tonemap_program.update(&mut rendering_context!(self));
+                if let ProgramName::Linked(program) = renderer.tonemap_program.name {
+                    gl.bind_framebuffer(gl::FRAMEBUFFER, post.display_framebuffer_name);
+                    gl.viewport(0, 0, post.dimensions.x, post.dimensions.y);
+
+                    gl.use_program(program);
+                    gl.uniform_1ui(AUTO_EXPOSURE_LOC, auto_exposure);
+                    gl.uniform_1f(EXPOSURE_LOC, exposure);
+                    gl.uniform_1f(
+                        BLOOM_INTENSITY_LOC,
+                        if cfg.bloom.enabled { cfg.bloom.intensity } else { 0.0 },
+                    );
+                    gl.bind_texture_unit(BLOOM_SAMPLER_BINDING, post.bloom_texture_name);
+
+                    gl.draw_elements(
+                        gl::TRIANGLES,
+                        (resources::FULL_SCREEN_INDICES.len() * 3) as u32,
+                        gl::UNSIGNED_INT,
+                        0,
+                    );
+
+                    gl.unuse_program();
+                }
+
+                self.profiling_context.stop(gl, profiler_index);
+            }
+
+            gl.unbind_vertex_array();
+            gl.depth_mask(gl::WriteMask::Enabled);
+            gl.enable(gl::DEPTH_TEST);
+        }
+
+        self.profiling_context.stop(gl, post_profiler_index);
+    }
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        fn create_compute_program(context: &mut RenderingContext, path: &'static str) -> rendering::Program {
+            rendering::Program::new(
+                context.gl,
+                vec![Shader::new(
+                    context.gl,
+                    gl::COMPUTE_SHADER,
+                    EntryPoint::new(&mut shader_compilation_context!(context), path, fixed_header()),
+                )],
+            )
+        }
+
+        fn create_bloom_program(context: &mut RenderingContext, pass: &'static str) -> rendering::Program {
+            let header = format!(
+                "{}\
+                #define BLOOM_PASS_PREFILTER 1\n\
+                #define BLOOM_PASS_DOWNSAMPLE 2\n\
+                #define BLOOM_PASS_UPSAMPLE 3\n\
+                #define BLOOM_PASS {}\n\
+                ",
+                fixed_header(),
+                pass
+            );
+            vs_fs_program(context, "overlay_renderer.vert", "post_bloom.frag", header)
+        }
+
+        Renderer {
+            histogram_program: create_compute_program(context, "post_histogram.comp"),
+            exposure_program: create_compute_program(context, "post_exposure.comp"),
+            bloom_prefilter_program: create_bloom_program(context, "BLOOM_PASS_PREFILTER"),
+            bloom_downsample_program: create_bloom_program(context, "BLOOM_PASS_DOWNSAMPLE"),
+            bloom_upsample_program: create_bloom_program(context, "BLOOM_PASS_UPSAMPLE"),
+            tonemap_program: vs_fs_program(context, "overlay_renderer.vert", "post_tonemap.frag", fixed_header()),
+        }
+    }
+}
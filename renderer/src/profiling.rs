@@ -1,14 +1,23 @@
 mod alloc;
+pub mod chrome_trace;
+pub mod cluster_stats;
+mod cpu;
+pub mod events_file;
 mod indices;
+pub mod run_metadata;
+pub mod stats;
 
-use crate::ValueAsBytes;
+use crate::{ValueAsBytes, VecAsBytes};
 use alloc::{AllocBuffer, BufferView};
+pub use cpu::{CpuProfiler, CpuSpan, ThreadProfiler};
 use gl_typed as gl;
 use ProfilingConfiguration as Configuration;
 use ProfilingContext as Context;
 
+pub use events_file::{read_events, EventReader, EventsHeader};
 use indices::ProfilerIndex;
-pub use indices::{FrameIndex, RunIndex, SampleIndex};
+pub use indices::{CpuSpanIndex, FrameIndex, RunIndex, SampleIndex, ThreadIndex};
+pub use run_metadata::RunMetadata;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub struct ProfilingConfiguration {
@@ -16,14 +25,23 @@ pub struct ProfilingConfiguration {
     pub record_events: bool,
     pub record_frames: bool,
     pub display: bool,
+    /// Draw a rolling CPU and GPU time graph next to every displayed sample.
+    pub graphs: bool,
     pub hide: Vec<String>,
+    /// Samples whose children are not displayed.
+    pub collapse: Vec<String>,
+    /// Samples at this depth or deeper are collapsed, the frame is at depth
+    /// zero. Cycled with the 8 key.
+    pub collapse_depth: Option<usize>,
+    /// Number of bins of each of the cluster histograms.
+    pub histogram_bin_count: u32,
 }
 
 pub enum FrameEvent {
     BeginTimeSpan(SampleIndex),
     EndTimeSpan,
     RecordClusterBuffer { byte_offset: usize },
-    RecordBasicBuffer { byte_offset: usize },
+    RecordBasicBuffer { byte_offset: usize, pixel_count: u32 },
 }
 
 struct FrameContext {
@@ -34,13 +52,13 @@ struct FrameContext {
 }
 
 impl FrameContext {
-    pub fn new(gl: &gl::Gl) -> Self {
+    pub fn new(gl: &gl::Gl, histogram_bin_count: u32) -> Self {
         Self {
             events: Vec::new(),
             profilers_used: 0,
             profilers: Vec::new(),
             // TODO: Maybe someday this should be changed.
-            buffer: AllocBuffer::with_capacity(gl, std::mem::size_of::<[ClusterBuffer; 2]>()),
+            buffer: AllocBuffer::with_capacity(gl, 2 * ClusterBuffer::byte_count(histogram_bin_count)),
         }
     }
 
@@ -56,8 +74,12 @@ struct FrameContextRing([FrameContext; FrameContextRing::CAPACITY]);
 impl FrameContextRing {
     const CAPACITY: usize = 3;
 
-    pub fn new(gl: &gl::Gl) -> Self {
-        Self([FrameContext::new(gl), FrameContext::new(gl), FrameContext::new(gl)])
+    pub fn new(gl: &gl::Gl, histogram_bin_count: u32) -> Self {
+        Self([
+            FrameContext::new(gl, histogram_bin_count),
+            FrameContext::new(gl, histogram_bin_count),
+            FrameContext::new(gl, histogram_bin_count),
+        ])
     }
 
     pub fn reset(&mut self) {
@@ -81,14 +103,26 @@ impl std::ops::IndexMut<FrameIndex> for FrameContextRing {
     }
 }
 
-#[derive(Default)]
+/// Number of frames the overlay statistics are computed over.
+const STATS_FRAME_COUNT: usize = 9;
+
 struct SamplesRing {
     sample_count: usize,
-    ring: [Vec<Option<GpuCpuTimeSpan>>; SamplesRing::CAPACITY],
+    ring: Vec<Vec<Option<GpuCpuTimeSpan>>>,
+}
+
+impl Default for SamplesRing {
+    fn default() -> Self {
+        Self {
+            sample_count: 0,
+            ring: vec![Vec::new(); Self::CAPACITY],
+        }
+    }
 }
 
 impl SamplesRing {
-    const CAPACITY: usize = 9;
+    /// Enough frames for the overlay graphs.
+    const CAPACITY: usize = 120;
 
     pub fn add_sample(&mut self) -> SampleIndex {
         let index = SampleIndex::from_usize(self.sample_count);
@@ -133,21 +167,116 @@ pub struct ProfilingContext {
     frame_started: bool,
     frame_context_ring: FrameContextRing,
     samples_ring: SamplesRing,
+    cluster_buffers: Vec<ClusterBuffer>,
+    histogram_bin_count: u32,
     pub sample_names: Vec<&'static str>,
     thread: ProfilingThread,
+    main_thread: ThreadProfiler,
+}
+
+enum Record {
+    Header(EventsHeader),
+    Event(MeasurementEvent),
+}
+
+fn write_event(file: &mut impl std::io::Write, event: &MeasurementEvent) {
+    bincode::serialize_into(file, event).unwrap();
 }
 
 struct ProfilingThreadInner {
     handle: std::thread::JoinHandle<()>,
-    tx: std::sync::mpsc::Sender<Option<MeasurementEvent>>,
+    tx: std::sync::mpsc::Sender<Option<Record>>,
+    /// Configuration snapshot for the header, taken when the header is
+    /// written.
+    pending_configuration: Option<String>,
+    cpu_profiler: CpuProfiler,
 }
 
 pub struct ProfilingThread(Option<ProfilingThreadInner>);
 
 impl ProfilingThread {
+    /// Spawns the thread that writes the events file. CPU time spans are
+    /// measured relative to `epoch`.
+    pub fn spawn(events_path: &std::path::Path, epoch: Epoch, configuration_snapshot: String) -> Self {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(events_path).unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cpu_profiler = CpuProfiler::new(epoch, tx.clone());
+        let handle = std::thread::Builder::new()
+            .name("profiling".to_string())
+            .spawn(move || {
+                let mut header_written = false;
+                // Events that arrive before the header, like the CPU time
+                // spans recorded while loading.
+                let mut early_events: Vec<MeasurementEvent> = Vec::new();
+
+                while let Some(record) = rx.recv().unwrap() {
+                    match record {
+                        Record::Header(header) => {
+                            events_file::write_header(&mut file, &header).unwrap();
+                            header_written = true;
+                            for event in early_events.drain(..) {
+                                write_event(&mut file, &event);
+                            }
+                        }
+                        Record::Event(event) => {
+                            if header_written {
+                                write_event(&mut file, &event);
+                            } else {
+                                early_events.push(event);
+                            }
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        ProfilingThread(Some(ProfilingThreadInner {
+            handle,
+            tx,
+            pending_configuration: Some(configuration_snapshot),
+            cpu_profiler,
+        }))
+    }
+
+    pub fn disabled() -> Self {
+        ProfilingThread(None)
+    }
+
+    pub fn cpu_profiler(&self) -> CpuProfiler {
+        match self.0 {
+            Some(ref thread) => thread.cpu_profiler.clone(),
+            None => CpuProfiler::disabled(),
+        }
+    }
+
     fn emit(&mut self, event: MeasurementEvent) {
         if let Some(thread) = self.0.as_mut() {
-            thread.tx.send(Some(event)).unwrap();
+            debug_assert!(
+                thread.pending_configuration.is_none(),
+                "Emitted an event before the header."
+            );
+            thread.tx.send(Some(Record::Event(event))).unwrap();
+        }
+    }
+
+    fn header_written(&self) -> bool {
+        match self.0 {
+            Some(ref thread) => thread.pending_configuration.is_none(),
+            None => true,
+        }
+    }
+
+    /// Writes the header once, the sample names registered so far are stored
+    /// in it.
+    pub fn write_header(&mut self, sample_names: &[&'static str]) {
+        if let Some(thread) = self.0.as_mut() {
+            if let Some(configuration) = thread.pending_configuration.take() {
+                let header = EventsHeader {
+                    sample_names: sample_names.iter().map(|&name| name.to_string()).collect(),
+                    configuration,
+                };
+                thread.tx.send(Some(Record::Header(header))).unwrap();
+            }
         }
     }
 }
@@ -162,33 +291,42 @@ impl Drop for ProfilingThread {
 }
 
 impl Context {
-    pub fn new(gl: &gl::Gl, profiling_dir: &std::path::Path, configuration: &Configuration) -> Self {
-        let thread = ProfilingThread(if configuration.record_events {
-            let mut file = std::io::BufWriter::new(std::fs::File::create(profiling_dir.join("events.bin")).unwrap());
-            let (tx, rx) = std::sync::mpsc::channel();
-            let handle = std::thread::Builder::new()
-                .name("profiling".to_string())
-                .spawn(move || {
-                    while let Some(event) = rx.recv().unwrap() {
-                        bincode::serialize_into(&mut file, &event).unwrap();
-                    }
-                })
-                .unwrap();
-            Some(ProfilingThreadInner { handle, tx })
+    /// The `configuration_snapshot` is stored in the header of the events
+    /// file.
+    pub fn new(
+        gl: &gl::Gl,
+        profiling_dir: &std::path::Path,
+        configuration: &Configuration,
+        configuration_snapshot: String,
+    ) -> Self {
+        assert!(
+            configuration.histogram_bin_count > 0,
+            "The histogram bin count must be positive."
+        );
+
+        let epoch = std::time::Instant::now();
+
+        let thread = if configuration.record_events {
+            ProfilingThread::spawn(&profiling_dir.join("events.bin"), epoch, configuration_snapshot)
         } else {
-            None
-        });
+            ProfilingThread::disabled()
+        };
+
+        let main_thread = thread.cpu_profiler().thread("main");
 
         Self {
-            epoch: std::time::Instant::now(),
-            frame_context_ring: FrameContextRing::new(gl),
+            epoch,
+            frame_context_ring: FrameContextRing::new(gl, configuration.histogram_bin_count),
             run_index: RunIndex::from_usize(0),
             run_started: false,
             frame_index: FrameIndex::from_usize(0),
             frame_started: false,
             samples_ring: Default::default(),
+            cluster_buffers: Vec::new(),
+            histogram_bin_count: configuration.histogram_bin_count,
             sample_names: Default::default(),
             thread,
+            main_thread,
         }
     }
 
@@ -202,8 +340,11 @@ impl Context {
     pub fn add_sample(&mut self, sample: &'static str) -> SampleIndex {
         let sample_index = self.samples_ring.add_sample();
         self.sample_names.push(sample);
-        self.thread
-            .emit(MeasurementEvent::SampleName(sample_index, sample.to_string()));
+        // Samples added before the header is written are stored in it.
+        if self.thread.header_written() {
+            self.thread
+                .emit(MeasurementEvent::SampleName(sample_index, sample.to_string()));
+        }
         sample_index
     }
 
@@ -212,6 +353,7 @@ impl Context {
         assert_eq!(false, self.run_started);
         assert_eq!(self.run_index, run_index);
         self.run_started = true;
+        self.thread.write_header(&self.sample_names);
         self.thread.emit(MeasurementEvent::BeginRun(run_index));
     }
 
@@ -228,6 +370,7 @@ impl Context {
         self.frame_index = FrameIndex::from_usize(0);
         self.frame_context_ring.reset();
         self.samples_ring.clear();
+        self.cluster_buffers.clear();
     }
 
     #[inline]
@@ -253,6 +396,8 @@ impl Context {
             }
 
             // Read back data from the GPU.
+            let time_sensitive = self.time_sensitive();
+            self.cluster_buffers.clear();
             let mut profilers_used = 0;
             for event in context.events.iter() {
                 match *event {
@@ -271,13 +416,22 @@ impl Context {
                     }
                     FrameEvent::EndTimeSpan => self.thread.emit(MeasurementEvent::EndTimeSpan),
                     FrameEvent::RecordClusterBuffer { byte_offset } => unsafe {
-                        let mut buffer = ClusterBuffer::default();
-                        context.buffer.read(gl, byte_offset, buffer.value_as_bytes_mut());
-                        self.thread.emit(MeasurementEvent::RecordClusterBuffer(buffer));
+                        let mut buffer = ClusterBuffer::new(self.histogram_bin_count);
+                        context.buffer.read(gl, byte_offset, buffer.data.vec_as_bytes_mut());
+                        // Cluster buffers are always read back for overflow
+                        // detection but only recorded in the first run.
+                        if !time_sensitive {
+                            self.thread.emit(MeasurementEvent::RecordClusterBuffer(buffer.clone()));
+                        }
+                        self.cluster_buffers.push(buffer);
                     },
-                    FrameEvent::RecordBasicBuffer { byte_offset } => unsafe {
+                    FrameEvent::RecordBasicBuffer {
+                        byte_offset,
+                        pixel_count,
+                    } => unsafe {
                         let mut buffer = BasicBuffer::default();
                         context.buffer.read(gl, byte_offset, buffer.value_as_bytes_mut());
+                        buffer.pixel_count = pixel_count;
                         self.thread.emit(MeasurementEvent::RecordBasicBuffer(buffer));
                     },
                 }
@@ -313,7 +467,7 @@ impl Context {
     }
 
     #[inline]
-    pub fn start(&mut self, gl: &gl::Gl, sample_index: SampleIndex) -> ProfilerIndex {
+    fn next_profiler(&mut self, sample_index: SampleIndex) -> (&mut TimeSpanProfiler, ProfilerIndex) {
         assert!(true, self.run_started);
         assert!(true, self.frame_started);
         let context = &mut self.frame_context_ring[self.frame_index];
@@ -321,9 +475,26 @@ impl Context {
         let profiler_index = ProfilerIndex(context.profilers_used);
         context.profilers_used += 1;
         while context.profilers.len() < profiler_index.0 + 1 {
-            context.profilers.push(TimeSpanProfiler::new(gl));
+            context.profilers.push(TimeSpanProfiler::new());
         }
-        context.profilers[profiler_index.0].start(gl, self.epoch);
+        (&mut context.profilers[profiler_index.0], profiler_index)
+    }
+
+    #[inline]
+    pub fn start(&mut self, gl: &gl::Gl, sample_index: SampleIndex) -> ProfilerIndex {
+        let epoch = self.epoch;
+        let (profiler, profiler_index) = self.next_profiler(sample_index);
+        profiler.start(gl, epoch);
+        profiler_index
+    }
+
+    /// Like `start` but only measures CPU time, the GPU time of the sample
+    /// will be zero.
+    #[inline]
+    pub fn start_cpu(&mut self, sample_index: SampleIndex) -> ProfilerIndex {
+        let epoch = self.epoch;
+        let (profiler, profiler_index) = self.next_profiler(sample_index);
+        profiler.start_cpu(epoch);
         profiler_index
     }
 
@@ -336,12 +507,35 @@ impl Context {
         context.profilers[profiler_index.0].stop(gl, self.epoch);
     }
 
+    #[inline]
+    pub fn stop_cpu(&mut self, profiler_index: ProfilerIndex) {
+        assert!(true, self.run_started);
+        assert!(true, self.frame_started);
+        let context = &mut self.frame_context_ring[self.frame_index];
+        context.events.push(FrameEvent::EndTimeSpan);
+        context.profilers[profiler_index.0].stop_cpu(self.epoch);
+    }
+
+    /// Profiler for CPU work on the main thread outside of frames.
+    #[inline]
+    pub fn main_thread(&self) -> &ThreadProfiler {
+        &self.main_thread
+    }
+
+    /// Use `CpuProfiler::thread` to profile work on other threads.
+    #[inline]
+    pub fn cpu_profiler(&self) -> CpuProfiler {
+        self.thread.cpu_profiler()
+    }
+
     #[inline]
     pub unsafe fn record_cluster_buffer(&mut self, gl: &gl::Gl, name: &gl::BufferName, byte_offset: usize) {
         assert!(true, self.run_started);
         assert!(true, self.frame_started);
         let context = &mut self.frame_context_ring[self.frame_index];
-        let view = context.buffer.alloc::<ClusterBuffer>(gl, 1);
+        let view = context
+            .buffer
+            .alloc::<u32>(gl, ClusterBuffer::len(self.histogram_bin_count));
         gl.copy_named_buffer_sub_data(name, view.name, byte_offset, view.byte_offset, view.byte_count);
         context.events.push(FrameEvent::RecordClusterBuffer {
             byte_offset: view.byte_offset,
@@ -359,17 +553,37 @@ impl Context {
         view
     }
 
+    /// The `pixel_count` of the render target is stored with the counters.
     #[inline]
-    pub unsafe fn end_basic_buffer(&mut self, _gl: &gl::Gl, view: BufferView) {
+    pub unsafe fn end_basic_buffer(&mut self, _gl: &gl::Gl, view: BufferView, pixel_count: u32) {
         assert!(true, self.run_started);
         assert!(true, self.frame_started);
         let context = &mut self.frame_context_ring[self.frame_index];
 
         context.events.push(FrameEvent::RecordBasicBuffer {
             byte_offset: view.byte_offset,
+            pixel_count,
         });
     }
 
+    #[inline]
+    pub fn histogram_bin_count(&self) -> u32 {
+        self.histogram_bin_count
+    }
+
+    /// Size of the buffer the clustered light shading passes write their
+    /// counters and histograms to.
+    #[inline]
+    pub fn cluster_buffer_byte_count(&self) -> usize {
+        ClusterBuffer::byte_count(self.histogram_bin_count)
+    }
+
+    /// Cluster buffers read back at the start of the current frame.
+    #[inline]
+    pub fn cluster_buffers(&self) -> &[ClusterBuffer] {
+        &self.cluster_buffers
+    }
+
     #[inline]
     pub fn sample(&self, sample_index: SampleIndex) -> Option<GpuCpuTimeSpan> {
         assert!(true, self.run_started);
@@ -382,20 +596,42 @@ impl Context {
         }
     }
 
+    /// The samples of the last `count` frames that have been read back,
+    /// oldest first. Yields `None` for frames without the sample.
+    pub fn history(
+        &self,
+        sample_index: SampleIndex,
+        count: usize,
+    ) -> impl Iterator<Item = Option<GpuCpuTimeSpan>> + '_ {
+        let count = std::cmp::min(count, SamplesRing::CAPACITY);
+        // The frame that was read back most recently plus one.
+        let end = self
+            .frame_index
+            .to_usize()
+            .saturating_sub(FrameContextRing::CAPACITY - 1);
+        (0..count).rev().map(move |age| {
+            if end > age {
+                self.samples_ring[FrameIndex::from_usize(end - 1 - age)][sample_index.to_usize()]
+            } else {
+                None
+            }
+        })
+    }
+
     #[inline]
     pub fn stats(&self, sample_index: SampleIndex) -> Option<GpuCpuStats> {
-        let mut cpu_elapsed = [0u64; SamplesRing::CAPACITY];
-        let mut gpu_elapsed = [0u64; SamplesRing::CAPACITY];
-        for index in 0..SamplesRing::CAPACITY {
-            let span = self.samples_ring[FrameIndex::from_usize(index)][sample_index.to_usize()]?;
+        let mut cpu_elapsed = [0u64; STATS_FRAME_COUNT];
+        let mut gpu_elapsed = [0u64; STATS_FRAME_COUNT];
+        for (index, span) in self.history(sample_index, STATS_FRAME_COUNT).enumerate() {
+            let span = span?;
             cpu_elapsed[index] = span.cpu.delta();
             gpu_elapsed[index] = span.gpu.delta();
         }
         Some(GpuCpuStats {
-            cpu_elapsed_avg: cpu_elapsed.iter().copied().sum::<u64>() / SamplesRing::CAPACITY as u64,
+            cpu_elapsed_avg: cpu_elapsed.iter().copied().sum::<u64>() / STATS_FRAME_COUNT as u64,
             cpu_elapsed_min: cpu_elapsed.iter().copied().min().unwrap(),
             cpu_elapsed_max: cpu_elapsed.iter().copied().max().unwrap(),
-            gpu_elapsed_avg: gpu_elapsed.iter().copied().sum::<u64>() / SamplesRing::CAPACITY as u64,
+            gpu_elapsed_avg: gpu_elapsed.iter().copied().sum::<u64>() / STATS_FRAME_COUNT as u64,
             gpu_elapsed_min: gpu_elapsed.iter().copied().min().unwrap(),
             gpu_elapsed_max: gpu_elapsed.iter().copied().max().unwrap(),
         })
@@ -428,41 +664,43 @@ pub struct GpuCpuTimeSpan {
     pub cpu: TimeSpan,
 }
 
+impl GpuCpuTimeSpan {
+    /// Spans recorded with `ProfilingContext::start_cpu` have no GPU time.
+    pub fn has_gpu(&self) -> bool {
+        self.gpu.begin != 0 || self.gpu.end != 0
+    }
+}
+
 #[derive(Debug)]
 pub struct TimeSpanProfiler {
-    begin_query_name: gl::QueryName,
-    end_query_name: gl::QueryName,
+    /// Created when the profiler first measures GPU time.
+    query_names: Option<(gl::QueryName, gl::QueryName)>,
     state: State,
 }
 
 #[derive(Debug)]
 enum State {
     Empty,
-    Started { cpu_begin: u64 },
-    Stopped { cpu_begin: u64, cpu_end: u64 },
+    Started { cpu_begin: u64, gpu: bool },
+    Stopped { cpu_begin: u64, cpu_end: u64, gpu: bool },
 }
 
 impl TimeSpanProfiler {
     #[inline]
-    pub fn new(gl: &gl::Gl) -> Self {
+    pub fn new() -> Self {
         Self {
-            begin_query_name: unsafe { gl.create_query(gl::TIMESTAMP) },
-            end_query_name: unsafe { gl.create_query(gl::TIMESTAMP) },
+            query_names: None,
             state: State::Empty,
         }
     }
 
     #[inline]
-    pub fn start(&mut self, gl: &gl::Gl, epoch: Epoch) {
+    fn start_impl(&mut self, epoch: Epoch, gpu: bool) {
         self.state = match self.state {
-            State::Empty | State::Stopped { .. } => {
-                unsafe {
-                    gl.query_counter(self.begin_query_name);
-                }
-                State::Started {
-                    cpu_begin: epoch.elapsed().as_nanos() as u64,
-                }
-            }
+            State::Empty | State::Stopped { .. } => State::Started {
+                cpu_begin: epoch.elapsed().as_nanos() as u64,
+                gpu,
+            },
             State::Started { .. } => {
                 panic!("Tried to start a profiler that had already been started!");
             }
@@ -470,26 +708,56 @@ impl TimeSpanProfiler {
     }
 
     #[inline]
-    pub fn stop(&mut self, gl: &gl::Gl, epoch: Epoch) {
-        self.state = match self.state {
+    pub fn start(&mut self, gl: &gl::Gl, epoch: Epoch) {
+        let (begin_query_name, _) = *self
+            .query_names
+            .get_or_insert_with(|| unsafe { (gl.create_query(gl::TIMESTAMP), gl.create_query(gl::TIMESTAMP)) });
+        unsafe {
+            gl.query_counter(begin_query_name);
+        }
+        self.start_impl(epoch, true);
+    }
+
+    #[inline]
+    pub fn start_cpu(&mut self, epoch: Epoch) {
+        self.start_impl(epoch, false);
+    }
+
+    #[inline]
+    fn stop_impl(&mut self, epoch: Epoch) -> bool {
+        let (cpu_begin, gpu) = match self.state {
             State::Empty => {
                 panic!("Tried to stop a profiler that was never started!");
             }
-            State::Started { cpu_begin } => {
-                unsafe {
-                    gl.query_counter(self.end_query_name);
-                }
-                State::Stopped {
-                    cpu_begin,
-                    cpu_end: epoch.elapsed().as_nanos() as u64,
-                }
-            }
+            State::Started { cpu_begin, gpu } => (cpu_begin, gpu),
             State::Stopped { .. } => {
                 panic!("Tried to stop a profiler that had already been stopped!");
             }
+        };
+        self.state = State::Stopped {
+            cpu_begin,
+            cpu_end: epoch.elapsed().as_nanos() as u64,
+            gpu,
+        };
+        gpu
+    }
+
+    #[inline]
+    pub fn stop(&mut self, gl: &gl::Gl, epoch: Epoch) {
+        if self.stop_impl(epoch) {
+            let (_, end_query_name) = self.query_names.unwrap();
+            unsafe {
+                gl.query_counter(end_query_name);
+            }
         }
     }
 
+    #[inline]
+    pub fn stop_cpu(&mut self, epoch: Epoch) {
+        let gpu = self.stop_impl(epoch);
+        assert!(!gpu, "Tried to stop a GPU profiler without a GL context!");
+    }
+
     #[inline]
     pub fn read(&mut self, gl: &gl::Gl) -> Option<GpuCpuTimeSpan> {
         match self.state {
@@ -497,24 +765,34 @@ impl TimeSpanProfiler {
             State::Started { .. } => {
                 panic!("Tried to read a profiler that was started but never stopped!");
             }
-            State::Stopped { cpu_begin, cpu_end } => {
+            State::Stopped {
+                cpu_begin,
+                cpu_end,
+                gpu,
+            } => {
                 // Not really necessary but I wan't to catch double reads.
                 self.state = State::Empty;
 
-                let (gpu_begin, gpu_end) = unsafe {
-                    (
-                        gl.try_query_result_u64(self.begin_query_name)
-                            .expect("Query result was not ready!"),
-                        gl.try_query_result_u64(self.end_query_name)
-                            .expect("Query result was not ready!"),
-                    )
+                let gpu = if gpu {
+                    let (begin_query_name, end_query_name) = self.query_names.unwrap();
+                    let (gpu_begin, gpu_end) = unsafe {
+                        (
+                            gl.try_query_result_u64(begin_query_name)
+                                .expect("Query result was not ready!"),
+                            gl.try_query_result_u64(end_query_name)
+                                .expect("Query result was not ready!"),
+                        )
+                    };
+                    TimeSpan {
+                        begin: gpu_begin.get(),
+                        end: gpu_end.get(),
+                    }
+                } else {
+                    TimeSpan::default()
                 };
 
                 Some(GpuCpuTimeSpan {
-                    gpu: TimeSpan {
-                        begin: gpu_begin.get(),
-                        end: gpu_end.get(),
-                    },
+                    gpu,
                     cpu: TimeSpan {
                         begin: cpu_begin,
                         end: cpu_end,
@@ -545,12 +823,86 @@ pub enum MeasurementEvent {
     EndTimeSpan,
     RecordClusterBuffer(ClusterBuffer),
     RecordBasicBuffer(BasicBuffer),
+    ThreadName(ThreadIndex, String),
+    /// Precedes the first [`MeasurementEvent::CpuTimeSpan`] with the name.
+    CpuSpanName(CpuSpanIndex, String),
+    /// A span recorded with a [`ThreadProfiler`]. These are not part of the
+    /// frame hierarchy and may occur outside of runs.
+    CpuTimeSpan(ThreadIndex, CpuSpanIndex, TimeSpan),
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
-#[repr(C)]
+/// The counters and histograms in `resources/cls/profiling_cluster_buffer.glsl`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ClusterBuffer {
-    data: [[u32; 32]; 32],
+    data: Vec<u32>,
+}
+
+impl ClusterBuffer {
+    /// Number of `u32`s in front of the histograms, most of it is padding.
+    const HEADER_LEN: usize = 256;
+
+    pub fn new(histogram_bin_count: u32) -> Self {
+        Self {
+            data: vec![0; Self::len(histogram_bin_count)],
+        }
+    }
+
+    fn len(histogram_bin_count: u32) -> usize {
+        Self::HEADER_LEN + 3 * histogram_bin_count as usize
+    }
+
+    pub fn byte_count(histogram_bin_count: u32) -> usize {
+        Self::len(histogram_bin_count) * std::mem::size_of::<u32>()
+    }
+
+    pub fn histogram_bin_count(&self) -> usize {
+        (self.data.len() - Self::HEADER_LEN) / 3
+    }
+
+    fn histogram(&self, index: usize) -> &[u32] {
+        let bin_count = self.histogram_bin_count();
+        let begin = Self::HEADER_LEN + index * bin_count;
+        &self.data[begin..begin + bin_count]
+    }
+
+    /// Number of clusters by their fragment count. The bins are logarithmic,
+    /// a count `n` ends up in bin `floor(bin_count / 32 * log2(n))`.
+    pub fn fragments_per_cluster_histogram(&self) -> &[u32] {
+        self.histogram(0)
+    }
+
+    /// Number of active clusters by their light count. The last bin also
+    /// holds the clusters with more lights.
+    pub fn lights_per_cluster_histogram(&self) -> &[u32] {
+        self.histogram(1)
+    }
+
+    /// Number of fragments by the light count of their cluster. The last bin
+    /// also holds the fragments in clusters with more lights.
+    pub fn lights_per_fragment_histogram(&self) -> &[u32] {
+        self.histogram(2)
+    }
+
+    /// All `u32`s in the layout of the GPU buffer.
+    pub fn as_slice(&self) -> &[u32] {
+        &self.data
+    }
+
+    pub fn active_cluster_count(&self) -> u32 {
+        self.data[0]
+    }
+
+    pub fn light_indices_count(&self) -> u32 {
+        self.data[1]
+    }
+
+    pub fn active_cluster_overflow(&self) -> u32 {
+        self.data[2]
+    }
+
+    pub fn light_indices_overflow(&self) -> u32 {
+        self.data[3]
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
@@ -558,4 +910,20 @@ pub struct ClusterBuffer {
 pub struct BasicBuffer {
     shading_ops: u32,
     lighting_ops: u32,
+    /// Not written by the GPU, filled in when the buffer is read back.
+    pixel_count: u32,
+}
+
+impl BasicBuffer {
+    pub fn shading_ops(&self) -> u32 {
+        self.shading_ops
+    }
+
+    pub fn lighting_ops(&self) -> u32 {
+        self.lighting_ops
+    }
+
+    pub fn pixel_count(&self) -> u32 {
+        self.pixel_count
+    }
 }
@@ -0,0 +1,281 @@
+//! Converts recorded measurement events into the Chrome trace event format,
+//! which can be opened in chrome://tracing and Perfetto. Every run becomes a
+//! process with a CPU and a GPU thread. Frames and time spans become nested
+//! slices on both threads. CPU time spans of named threads get a thread of
+//! their own, the ones recorded outside of runs end up in a separate process.
+
+use super::*;
+use std::io::{self, Write};
+
+const CPU_TID: u32 = 0;
+const GPU_TID: u32 = 1;
+/// Named threads start at this thread id.
+const THREAD_TID_OFFSET: u32 = 2;
+const OUTSIDE_RUNS_PID: usize = std::u32::MAX as usize;
+
+fn write_escaped(w: &mut impl Write, s: &str) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => w.write_all(b"\\\"")?,
+            '\\' => w.write_all(b"\\\\")?,
+            '\n' => w.write_all(b"\\n")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => write!(w, "{}", c)?,
+        }
+    }
+    w.write_all(b"\"")
+}
+
+struct Writer<W: Write> {
+    inner: W,
+    first: bool,
+}
+
+impl<W: Write> Writer<W> {
+    fn separator(&mut self) -> io::Result<()> {
+        if self.first {
+            self.first = false;
+            Ok(())
+        } else {
+            self.inner.write_all(b",\n")
+        }
+    }
+
+    fn metadata(&mut self, kind: &str, pid: usize, tid: u32, name: &str) -> io::Result<()> {
+        self.separator()?;
+        write!(
+            self.inner,
+            "{{\"ph\":\"M\",\"name\":\"{}\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":",
+            kind, pid, tid
+        )?;
+        write_escaped(&mut self.inner, name)?;
+        self.inner.write_all(b"}}")
+    }
+
+    fn slice(&mut self, name: &str, category: &str, pid: usize, tid: u32, span: TimeSpan) -> io::Result<()> {
+        self.separator()?;
+        self.inner.write_all(b"{\"ph\":\"X\",\"name\":")?;
+        write_escaped(&mut self.inner, name)?;
+        // Timestamps are in microseconds.
+        write!(
+            self.inner,
+            ",\"cat\":\"{}\",\"pid\":{},\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+            category,
+            pid,
+            tid,
+            span.begin as f64 / 1000.0,
+            span.delta() as f64 / 1000.0
+        )
+    }
+}
+
+/// Extends `frame` so that it covers `span`.
+fn cover(frame: &mut Option<TimeSpan>, span: TimeSpan) {
+    *frame = Some(match *frame {
+        Some(frame) => TimeSpan {
+            begin: frame.begin.min(span.begin),
+            end: frame.end.max(span.end),
+        },
+        None => span,
+    });
+}
+
+/// The GPU timestamps use a different clock than the CPU timestamps. The GPU
+/// timeline of a run is shifted by the smallest offset that makes every GPU
+/// span begin at or after the CPU issued it.
+fn gpu_offsets(events: &[MeasurementEvent]) -> Vec<i64> {
+    let mut offsets = Vec::new();
+    let mut run_index = None;
+    for event in events.iter() {
+        match *event {
+            MeasurementEvent::BeginRun(index) => {
+                run_index = Some(index.to_usize());
+                if offsets.len() <= index.to_usize() {
+                    offsets.resize(index.to_usize() + 1, std::i64::MIN);
+                }
+            }
+            MeasurementEvent::EndRun => {
+                run_index = None;
+            }
+            MeasurementEvent::BeginTimeSpan(_, span) if span.has_gpu() => {
+                if let Some(run_index) = run_index {
+                    let offset = span.cpu.begin as i64 - span.gpu.begin as i64;
+                    offsets[run_index] = offsets[run_index].max(offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    offsets
+}
+
+/// Writes the events as a JSON object with a `traceEvents` array.
+pub fn write(events: &[MeasurementEvent], writer: impl Write) -> io::Result<()> {
+    let gpu_offsets = gpu_offsets(events);
+
+    let mut w = Writer {
+        inner: writer,
+        first: true,
+    };
+
+    w.inner.write_all(b"{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n")?;
+
+    let mut sample_names: Vec<&str> = Vec::new();
+    let mut thread_names: Vec<&str> = Vec::new();
+    let mut span_names: Vec<&str> = Vec::new();
+    let mut named_threads: std::collections::HashSet<(usize, u32)> = Default::default();
+    let mut outside_runs_named = false;
+    let mut run_index: Option<usize> = None;
+    let mut frame_index: Option<usize> = None;
+    let mut cpu_frame: Option<TimeSpan> = None;
+    let mut gpu_frame: Option<TimeSpan> = None;
+
+    for event in events.iter() {
+        match *event {
+            MeasurementEvent::SampleName(index, ref name) => {
+                assert_eq!(index.to_usize(), sample_names.len());
+                sample_names.push(name);
+            }
+            MeasurementEvent::BeginRun(index) => {
+                let pid = index.to_usize();
+                run_index = Some(pid);
+                w.metadata("process_name", pid, 0, &format!("run {}", pid))?;
+                w.metadata("thread_name", pid, CPU_TID, "CPU")?;
+                w.metadata("thread_name", pid, GPU_TID, "GPU")?;
+            }
+            MeasurementEvent::EndRun => {
+                run_index = None;
+            }
+            MeasurementEvent::BeginFrame(index) => {
+                frame_index = Some(index.to_usize());
+                cpu_frame = None;
+                gpu_frame = None;
+            }
+            MeasurementEvent::EndFrame => {
+                let pid = run_index.unwrap();
+                let name = format!("frame {}", frame_index.unwrap());
+                if let Some(span) = cpu_frame {
+                    w.slice(&name, "frame", pid, CPU_TID, span)?;
+                }
+                if let Some(span) = gpu_frame {
+                    w.slice(&name, "frame", pid, GPU_TID, span)?;
+                }
+                frame_index = None;
+            }
+            MeasurementEvent::BeginTimeSpan(sample_index, span) => {
+                let pid = run_index.unwrap();
+                let name = sample_names[sample_index.to_usize()];
+                let offset = gpu_offsets[pid];
+                let gpu = TimeSpan {
+                    begin: (span.gpu.begin as i64 + offset) as u64,
+                    end: (span.gpu.end as i64 + offset) as u64,
+                };
+                w.slice(name, "sample", pid, CPU_TID, span.cpu)?;
+                cover(&mut cpu_frame, span.cpu);
+                if span.has_gpu() {
+                    w.slice(name, "sample", pid, GPU_TID, gpu)?;
+                    cover(&mut gpu_frame, gpu);
+                }
+            }
+            MeasurementEvent::ThreadName(index, ref name) => {
+                assert_eq!(index.to_usize(), thread_names.len());
+                thread_names.push(name);
+            }
+            MeasurementEvent::CpuSpanName(index, ref name) => {
+                assert_eq!(index.to_usize(), span_names.len());
+                span_names.push(name);
+            }
+            MeasurementEvent::CpuTimeSpan(thread_index, span_index, span) => {
+                let pid = run_index.unwrap_or(OUTSIDE_RUNS_PID);
+                let tid = THREAD_TID_OFFSET + thread_index.to_usize() as u32;
+                if pid == OUTSIDE_RUNS_PID && !outside_runs_named {
+                    w.metadata("process_name", pid, 0, "outside runs")?;
+                    outside_runs_named = true;
+                }
+                if named_threads.insert((pid, tid)) {
+                    w.metadata("thread_name", pid, tid, thread_names[thread_index.to_usize()])?;
+                }
+                w.slice(span_names[span_index.to_usize()], "cpu", pid, tid, span)?;
+            }
+            MeasurementEvent::EndTimeSpan
+            | MeasurementEvent::RecordClusterBuffer(_)
+            | MeasurementEvent::RecordBasicBuffer(_) => {}
+        }
+    }
+
+    w.inner.write_all(b"\n]}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(cpu: (u64, u64), gpu: (u64, u64)) -> GpuCpuTimeSpan {
+        GpuCpuTimeSpan {
+            cpu: TimeSpan {
+                begin: cpu.0,
+                end: cpu.1,
+            },
+            gpu: TimeSpan {
+                begin: gpu.0,
+                end: gpu.1,
+            },
+        }
+    }
+
+    #[test]
+    fn writes_slices_on_both_threads() {
+        let events = vec![
+            MeasurementEvent::SampleName(SampleIndex::from_usize(0), "frame \"a\"".to_string()),
+            MeasurementEvent::SampleName(SampleIndex::from_usize(1), "basic".to_string()),
+            MeasurementEvent::BeginRun(RunIndex::from_usize(0)),
+            MeasurementEvent::BeginFrame(FrameIndex::from_usize(0)),
+            MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(0), span((1000, 5000), (100_000, 103_000))),
+            MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(1), span((2000, 3000), (101_000, 102_000))),
+            MeasurementEvent::EndTimeSpan,
+            MeasurementEvent::EndTimeSpan,
+            MeasurementEvent::EndFrame,
+            MeasurementEvent::EndRun,
+        ];
+
+        let mut bytes = Vec::new();
+        write(&events, &mut bytes).unwrap();
+        let json = String::from_utf8(bytes).unwrap();
+
+        assert!(json.contains("\"name\":\"frame \\\"a\\\"\""));
+        // The GPU is shifted so that the inner span starts when it was issued.
+        assert!(json.contains("\"name\":\"basic\",\"cat\":\"sample\",\"pid\":0,\"tid\":1,\"ts\":2.000,\"dur\":1.000"));
+        assert!(json.contains("\"name\":\"frame 0\",\"cat\":\"frame\",\"pid\":0,\"tid\":0,\"ts\":1.000,\"dur\":4.000"));
+        assert_eq!(json.matches("\"ph\":\"X\"").count(), 6);
+    }
+
+    #[test]
+    fn writes_cpu_spans_on_named_threads() {
+        let events = vec![
+            MeasurementEvent::SampleName(SampleIndex::from_usize(0), "world transforms".to_string()),
+            MeasurementEvent::ThreadName(ThreadIndex::from_usize(0), "main".to_string()),
+            MeasurementEvent::CpuSpanName(CpuSpanIndex::from_usize(0), "load scene".to_string()),
+            MeasurementEvent::CpuTimeSpan(
+                ThreadIndex::from_usize(0),
+                CpuSpanIndex::from_usize(0),
+                TimeSpan { begin: 0, end: 1000 },
+            ),
+            MeasurementEvent::BeginRun(RunIndex::from_usize(0)),
+            MeasurementEvent::BeginFrame(FrameIndex::from_usize(0)),
+            MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(0), span((2000, 3000), (0, 0))),
+            MeasurementEvent::EndTimeSpan,
+            MeasurementEvent::EndFrame,
+            MeasurementEvent::EndRun,
+        ];
+
+        let mut bytes = Vec::new();
+        write(&events, &mut bytes).unwrap();
+        let json = String::from_utf8(bytes).unwrap();
+
+        assert!(json.contains("\"name\":\"outside runs\""));
+        assert!(json.contains("\"name\":\"load scene\",\"cat\":\"cpu\",\"pid\":4294967295,\"tid\":2"));
+        // Spans without GPU time only show up on the CPU thread.
+        assert_eq!(json.matches("\"pid\":0,\"tid\":1,\"ts\"").count(), 0);
+    }
+}
@@ -0,0 +1,313 @@
+//! Per-frame clustered light shading statistics derived from the cluster and
+//! basic buffers. These buffers are only recorded in the first run. The
+//! statistics can be written as CSV or JSON for analysis in other tools.
+
+use super::*;
+use std::io::{self, Write};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            other => Err(format!("Unknown format {:?}, expected csv or json.", other)),
+        }
+    }
+}
+
+/// The counters and histograms of all buffers recorded in a frame, summed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameClusterStats {
+    pub frame_index: usize,
+    pub active_cluster_count: u64,
+    pub light_indices_count: u64,
+    pub active_cluster_overflow: u64,
+    pub light_indices_overflow: u64,
+    pub shading_ops: u64,
+    pub lighting_ops: u64,
+    pub pixel_count: u64,
+    pub fragments_per_cluster: Vec<u64>,
+    pub lights_per_cluster: Vec<u64>,
+    pub lights_per_fragment: Vec<u64>,
+}
+
+fn ratio(numerator: u64, denominator: u64) -> Option<f64> {
+    if denominator == 0 {
+        None
+    } else {
+        Some(numerator as f64 / denominator as f64)
+    }
+}
+
+fn accumulate(histogram: &mut Vec<u64>, values: &[u32]) {
+    if histogram.len() < values.len() {
+        histogram.resize(values.len(), 0);
+    }
+    for (count, &value) in histogram.iter_mut().zip(values.iter()) {
+        *count += value as u64;
+    }
+}
+
+impl FrameClusterStats {
+    /// Number of fragments in active clusters.
+    pub fn fragment_count(&self) -> u64 {
+        self.lights_per_fragment.iter().sum()
+    }
+
+    pub fn mean_lights_per_cluster(&self) -> Option<f64> {
+        ratio(self.light_indices_count, self.active_cluster_count)
+    }
+
+    /// Computed from the histogram, so clusters with more lights than the
+    /// last bin are counted as having that many.
+    pub fn mean_lights_per_fragment(&self) -> Option<f64> {
+        let light_count = self
+            .lights_per_fragment
+            .iter()
+            .enumerate()
+            .map(|(light_count, &fragment_count)| light_count as u64 * fragment_count)
+            .sum();
+        ratio(light_count, self.fragment_count())
+    }
+
+    pub fn shading_ops_per_pixel(&self) -> Option<f64> {
+        ratio(self.shading_ops, self.pixel_count)
+    }
+
+    pub fn lighting_ops_per_pixel(&self) -> Option<f64> {
+        ratio(self.lighting_ops, self.pixel_count)
+    }
+
+    fn histograms(&self) -> [(&'static str, &[u64]); 3] {
+        [
+            ("fragments_per_cluster", &self.fragments_per_cluster[..]),
+            ("lights_per_cluster", &self.lights_per_cluster[..]),
+            ("lights_per_fragment", &self.lights_per_fragment[..]),
+        ]
+    }
+}
+
+/// Collects the statistics of every frame of the first run.
+pub fn collect(events: &[MeasurementEvent]) -> Vec<FrameClusterStats> {
+    let mut frames = Vec::new();
+    let mut run_index: Option<usize> = None;
+    let mut frame: Option<FrameClusterStats> = None;
+
+    for event in events.iter() {
+        match *event {
+            MeasurementEvent::BeginRun(index) => {
+                run_index = Some(index.to_usize());
+            }
+            MeasurementEvent::EndRun => {
+                run_index = None;
+            }
+            MeasurementEvent::BeginFrame(index) => {
+                if run_index == Some(0) {
+                    frame = Some(FrameClusterStats {
+                        frame_index: index.to_usize(),
+                        ..Default::default()
+                    });
+                }
+            }
+            MeasurementEvent::EndFrame => {
+                frames.extend(frame.take());
+            }
+            MeasurementEvent::RecordClusterBuffer(ref buffer) => {
+                if let Some(frame) = frame.as_mut() {
+                    frame.active_cluster_count += buffer.active_cluster_count() as u64;
+                    frame.light_indices_count += buffer.light_indices_count() as u64;
+                    frame.active_cluster_overflow += buffer.active_cluster_overflow() as u64;
+                    frame.light_indices_overflow += buffer.light_indices_overflow() as u64;
+                    accumulate(
+                        &mut frame.fragments_per_cluster,
+                        buffer.fragments_per_cluster_histogram(),
+                    );
+                    accumulate(&mut frame.lights_per_cluster, buffer.lights_per_cluster_histogram());
+                    accumulate(&mut frame.lights_per_fragment, buffer.lights_per_fragment_histogram());
+                }
+            }
+            MeasurementEvent::RecordBasicBuffer(ref buffer) => {
+                if let Some(frame) = frame.as_mut() {
+                    frame.shading_ops += buffer.shading_ops() as u64;
+                    frame.lighting_ops += buffer.lighting_ops() as u64;
+                    frame.pixel_count += buffer.pixel_count() as u64;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    frames
+}
+
+fn write_ratio(w: &mut impl Write, value: Option<f64>, missing: &str) -> io::Result<()> {
+    match value {
+        Some(value) => write!(w, "{}", value),
+        None => write!(w, "{}", missing),
+    }
+}
+
+/// Writes one row of metrics per frame to `metrics` and one row per frame and
+/// histogram to `histograms`. Missing ratios are left empty.
+pub fn write_csv(frames: &[FrameClusterStats], mut metrics: impl Write, mut histograms: impl Write) -> io::Result<()> {
+    writeln!(
+        metrics,
+        "frame,active_clusters,light_indices,active_cluster_overflow,light_indices_overflow,fragments,\
+         mean_lights_per_cluster,mean_lights_per_fragment,shading_ops,lighting_ops,pixels,\
+         shading_ops_per_pixel,lighting_ops_per_pixel"
+    )?;
+    for frame in frames.iter() {
+        write!(
+            metrics,
+            "{},{},{},{},{},{},",
+            frame.frame_index,
+            frame.active_cluster_count,
+            frame.light_indices_count,
+            frame.active_cluster_overflow,
+            frame.light_indices_overflow,
+            frame.fragment_count()
+        )?;
+        write_ratio(&mut metrics, frame.mean_lights_per_cluster(), "")?;
+        metrics.write_all(b",")?;
+        write_ratio(&mut metrics, frame.mean_lights_per_fragment(), "")?;
+        write!(
+            metrics,
+            ",{},{},{},",
+            frame.shading_ops, frame.lighting_ops, frame.pixel_count
+        )?;
+        write_ratio(&mut metrics, frame.shading_ops_per_pixel(), "")?;
+        metrics.write_all(b",")?;
+        write_ratio(&mut metrics, frame.lighting_ops_per_pixel(), "")?;
+        metrics.write_all(b"\n")?;
+    }
+
+    let bin_count = frames
+        .iter()
+        .map(|frame| frame.lights_per_fragment.len())
+        .max()
+        .unwrap_or(0);
+    histograms.write_all(b"frame,histogram")?;
+    for bin in 0..bin_count {
+        write!(histograms, ",{}", bin)?;
+    }
+    histograms.write_all(b"\n")?;
+    for frame in frames.iter() {
+        for &(name, histogram) in frame.histograms().iter() {
+            write!(histograms, "{},{}", frame.frame_index, name)?;
+            for bin in 0..bin_count {
+                write!(histograms, ",{}", histogram.get(bin).copied().unwrap_or(0))?;
+            }
+            histograms.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes an array with an object per frame. Missing ratios are `null`.
+pub fn write_json(frames: &[FrameClusterStats], mut w: impl Write) -> io::Result<()> {
+    w.write_all(b"[")?;
+    for (index, frame) in frames.iter().enumerate() {
+        if index > 0 {
+            w.write_all(b",")?;
+        }
+        write!(
+            w,
+            "\n{{\"frame\":{},\"active_clusters\":{},\"light_indices\":{},\"active_cluster_overflow\":{},\
+             \"light_indices_overflow\":{},\"fragments\":{},\"mean_lights_per_cluster\":",
+            frame.frame_index,
+            frame.active_cluster_count,
+            frame.light_indices_count,
+            frame.active_cluster_overflow,
+            frame.light_indices_overflow,
+            frame.fragment_count()
+        )?;
+        write_ratio(&mut w, frame.mean_lights_per_cluster(), "null")?;
+        w.write_all(b",\"mean_lights_per_fragment\":")?;
+        write_ratio(&mut w, frame.mean_lights_per_fragment(), "null")?;
+        write!(
+            w,
+            ",\"shading_ops\":{},\"lighting_ops\":{},\"pixels\":{},\"shading_ops_per_pixel\":",
+            frame.shading_ops, frame.lighting_ops, frame.pixel_count
+        )?;
+        write_ratio(&mut w, frame.shading_ops_per_pixel(), "null")?;
+        w.write_all(b",\"lighting_ops_per_pixel\":")?;
+        write_ratio(&mut w, frame.lighting_ops_per_pixel(), "null")?;
+        for &(name, histogram) in frame.histograms().iter() {
+            write!(w, ",\"{}\":[", name)?;
+            for (bin, count) in histogram.iter().enumerate() {
+                if bin > 0 {
+                    w.write_all(b",")?;
+                }
+                write!(w, "{}", count)?;
+            }
+            w.write_all(b"]")?;
+        }
+        w.write_all(b"}")?;
+    }
+    w.write_all(b"\n]\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cluster_buffer(
+        active_cluster_count: u32,
+        light_indices_count: u32,
+        lights_per_fragment: &[u32],
+    ) -> ClusterBuffer {
+        let mut buffer = ClusterBuffer::new(4);
+        buffer.data[0] = active_cluster_count;
+        buffer.data[1] = light_indices_count;
+        let begin = ClusterBuffer::HEADER_LEN + 2 * 4;
+        buffer.data[begin..begin + lights_per_fragment.len()].copy_from_slice(lights_per_fragment);
+        buffer
+    }
+
+    #[test]
+    fn sums_buffers_of_the_first_run() {
+        let events = vec![
+            MeasurementEvent::BeginRun(RunIndex::from_usize(0)),
+            MeasurementEvent::BeginFrame(FrameIndex::from_usize(0)),
+            MeasurementEvent::RecordClusterBuffer(cluster_buffer(2, 6, &[0, 10, 0, 10])),
+            MeasurementEvent::RecordClusterBuffer(cluster_buffer(2, 2, &[0, 20, 0, 0])),
+            MeasurementEvent::RecordBasicBuffer(BasicBuffer {
+                shading_ops: 150,
+                lighting_ops: 300,
+                pixel_count: 100,
+            }),
+            MeasurementEvent::EndFrame,
+            MeasurementEvent::BeginFrame(FrameIndex::from_usize(1)),
+            MeasurementEvent::EndFrame,
+            MeasurementEvent::EndRun,
+            MeasurementEvent::BeginRun(RunIndex::from_usize(1)),
+            MeasurementEvent::BeginFrame(FrameIndex::from_usize(0)),
+            MeasurementEvent::EndFrame,
+            MeasurementEvent::EndRun,
+        ];
+
+        let frames = collect(&events);
+        assert_eq!(2, frames.len());
+
+        let frame = &frames[0];
+        assert_eq!(4, frame.active_cluster_count);
+        assert_eq!(vec![0, 30, 0, 10], frame.lights_per_fragment);
+        assert_eq!(40, frame.fragment_count());
+        assert_eq!(Some(2.0), frame.mean_lights_per_cluster());
+        assert_eq!(Some(1.5), frame.mean_lights_per_fragment());
+        assert_eq!(Some(1.5), frame.shading_ops_per_pixel());
+        assert_eq!(Some(3.0), frame.lighting_ops_per_pixel());
+
+        assert_eq!(None, frames[1].mean_lights_per_fragment());
+        assert_eq!(None, frames[1].shading_ops_per_pixel());
+    }
+}
@@ -0,0 +1,168 @@
+//! Time spans of CPU work that happens outside of the frame hierarchy or on
+//! other threads, like loading the scene or encoding frames. They don't need
+//! a GL context and end up as [`MeasurementEvent::CpuTimeSpan`] in the events
+//! file. Span names are announced once with [`MeasurementEvent::CpuSpanName`].
+
+use super::*;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+struct Shared {
+    epoch: Epoch,
+    tx: Mutex<mpsc::Sender<Option<Record>>>,
+    thread_count: AtomicUsize,
+    span_indices: Mutex<HashMap<String, CpuSpanIndex>>,
+}
+
+/// Hands out a [`ThreadProfiler`] per thread. Does nothing when events are
+/// not being recorded.
+#[derive(Clone)]
+pub struct CpuProfiler(Option<Arc<Shared>>);
+
+impl CpuProfiler {
+    pub(super) fn new(epoch: Epoch, tx: mpsc::Sender<Option<Record>>) -> Self {
+        CpuProfiler(Some(Arc::new(Shared {
+            epoch,
+            tx: Mutex::new(tx),
+            thread_count: AtomicUsize::new(0),
+            span_indices: Mutex::new(HashMap::new()),
+        })))
+    }
+
+    pub fn disabled() -> Self {
+        CpuProfiler(None)
+    }
+
+    /// Registers a thread, the spans recorded with the returned profiler are
+    /// attributed to it.
+    pub fn thread(&self, name: impl Into<String>) -> ThreadProfiler {
+        ThreadProfiler(self.0.as_ref().map(|shared| {
+            let thread_index = ThreadIndex::from_usize(shared.thread_count.fetch_add(1, Ordering::Relaxed));
+            shared.send(MeasurementEvent::ThreadName(thread_index, name.into()));
+            (Arc::clone(shared), thread_index)
+        }))
+    }
+}
+
+impl Shared {
+    fn send(&self, event: MeasurementEvent) {
+        // The profiling thread may have stopped already while we are shutting down.
+        let _ = self.tx.lock().unwrap().send(Some(Record::Event(event)));
+    }
+
+    /// The name is sent before the first span that uses it. The lock is held
+    /// while sending so other threads can't send a span with the index first.
+    fn span_index(&self, name: Cow<'static, str>) -> CpuSpanIndex {
+        let mut span_indices = self.span_indices.lock().unwrap();
+        if let Some(&span_index) = span_indices.get(name.as_ref()) {
+            return span_index;
+        }
+        let span_index = CpuSpanIndex::from_usize(span_indices.len());
+        let name = name.into_owned();
+        self.send(MeasurementEvent::CpuSpanName(span_index, name.clone()));
+        span_indices.insert(name, span_index);
+        span_index
+    }
+}
+
+/// The name is `None` when the profiler is disabled.
+pub struct CpuSpan {
+    name: Option<Cow<'static, str>>,
+    begin: u64,
+}
+
+#[derive(Clone)]
+pub struct ThreadProfiler(Option<(Arc<Shared>, ThreadIndex)>);
+
+impl ThreadProfiler {
+    pub fn disabled() -> Self {
+        ThreadProfiler(None)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// The name is only converted when the profiler is enabled.
+    #[inline]
+    pub fn start(&self, name: impl Into<Cow<'static, str>>) -> CpuSpan {
+        self.start_with(|| name)
+    }
+
+    /// Like [`ThreadProfiler::start`] for names that have to be formatted,
+    /// which only happens when the profiler is enabled.
+    #[inline]
+    pub fn start_with<N: Into<Cow<'static, str>>>(&self, name: impl FnOnce() -> N) -> CpuSpan {
+        match self.0 {
+            Some((ref shared, _)) => CpuSpan {
+                name: Some(name().into()),
+                begin: shared.epoch.elapsed().as_nanos() as u64,
+            },
+            None => CpuSpan { name: None, begin: 0 },
+        }
+    }
+
+    #[inline]
+    pub fn stop(&self, span: CpuSpan) {
+        if let (Some((ref shared, thread_index)), Some(name)) = (&self.0, span.name) {
+            let end = shared.epoch.elapsed().as_nanos() as u64;
+            let span_index = shared.span_index(name);
+            shared.send(MeasurementEvent::CpuTimeSpan(
+                *thread_index,
+                span_index,
+                TimeSpan { begin: span.begin, end },
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_events_until_the_header_is_written() {
+        let path = std::env::temp_dir().join(format!("cpu_profiler_events_{}.bin", std::process::id()));
+        {
+            let mut thread = ProfilingThread::spawn(&path, Epoch::now(), "[global]".to_string());
+            let profiler = thread.cpu_profiler().thread("loader");
+            for _ in 0..2 {
+                let span = profiler.start("read scene");
+                profiler.stop(span);
+            }
+            thread.write_header(&["frame"]);
+            thread.emit(MeasurementEvent::BeginRun(RunIndex::from_usize(0)));
+        }
+        let events = read_events(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(6, events.len());
+        match events[0] {
+            MeasurementEvent::SampleName(_, ref name) => assert_eq!("frame", name),
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        match events[1] {
+            MeasurementEvent::ThreadName(_, ref name) => assert_eq!("loader", name),
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        match events[2] {
+            MeasurementEvent::CpuSpanName(index, ref name) => {
+                assert_eq!(0, index.to_usize());
+                assert_eq!("read scene", name);
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        for event in events[3..5].iter() {
+            match *event {
+                MeasurementEvent::CpuTimeSpan(_, index, _) => assert_eq!(0, index.to_usize()),
+                ref other => panic!("Unexpected event {:?}", other),
+            }
+        }
+        match events[5] {
+            MeasurementEvent::BeginRun(_) => {}
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! The `events.bin` file format.
+//!
+//! Version 0 files are a bare sequence of bincode encoded
+//! [`MeasurementEvent`]s. Later versions start with [`EVENTS_MAGIC`], the
+//! format version as a little endian `u32` and a bincode encoded
+//! [`EventsHeader`], followed by the events.
+//!
+//! When the encoding of the events changes, bump [`EVENTS_VERSION`] and keep
+//! the previous layout around to decode older files. Version 2 only added
+//! [`MeasurementEvent`] variants, so it shares the encoding with version 1.
+//! Version 3 made the size of [`ClusterBuffer`] depend on the histogram bin
+//! count and added the pixel count to [`BasicBuffer`]. Version 4 replaced the
+//! names in CPU time spans with indices announced by
+//! [`MeasurementEvent::CpuSpanName`].
+
+use super::*;
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::{self, Read, Write};
+
+pub const EVENTS_MAGIC: [u8; 8] = *b"PROFEVTS";
+pub const EVENTS_VERSION: u32 = 4;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct EventsHeader {
+    /// Samples that were registered before recording started. Samples added
+    /// later are announced with [`MeasurementEvent::SampleName`].
+    pub sample_names: Vec<String>,
+    /// The configuration of the renderer in TOML.
+    pub configuration: String,
+}
+
+pub fn write_header(writer: &mut impl Write, header: &EventsHeader) -> io::Result<()> {
+    writer.write_all(&EVENTS_MAGIC)?;
+    writer.write_all(&EVENTS_VERSION.to_le_bytes())?;
+    bincode::serialize_into(writer, header).map_err(bincode_to_io_error)
+}
+
+fn bincode_to_io_error(error: bincode::Error) -> io::Error {
+    match *error {
+        bincode::ErrorKind::Io(error) => error,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
+
+/// The encoding of versions 0 to 2.
+mod v2 {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    pub struct ClusterBuffer {
+        data: [[u32; 32]; 32],
+    }
+
+    #[derive(serde::Deserialize)]
+    pub struct BasicBuffer {
+        shading_ops: u32,
+        lighting_ops: u32,
+    }
+
+    #[derive(serde::Deserialize)]
+    pub enum MeasurementEvent {
+        SampleName(SampleIndex, String),
+        BeginRun(RunIndex),
+        EndRun,
+        BeginFrame(FrameIndex),
+        EndFrame,
+        BeginTimeSpan(SampleIndex, GpuCpuTimeSpan),
+        EndTimeSpan,
+        RecordClusterBuffer(ClusterBuffer),
+        RecordBasicBuffer(BasicBuffer),
+        ThreadName(ThreadIndex, String),
+        CpuTimeSpan(ThreadIndex, String, TimeSpan),
+    }
+
+    impl From<MeasurementEvent> for v3::MeasurementEvent {
+        fn from(event: MeasurementEvent) -> Self {
+            use v3::MeasurementEvent as E;
+            match event {
+                MeasurementEvent::SampleName(index, name) => E::SampleName(index, name),
+                MeasurementEvent::BeginRun(index) => E::BeginRun(index),
+                MeasurementEvent::EndRun => E::EndRun,
+                MeasurementEvent::BeginFrame(index) => E::BeginFrame(index),
+                MeasurementEvent::EndFrame => E::EndFrame,
+                MeasurementEvent::BeginTimeSpan(index, span) => E::BeginTimeSpan(index, span),
+                MeasurementEvent::EndTimeSpan => E::EndTimeSpan,
+                // The fixed layout has 256 bins per histogram.
+                MeasurementEvent::RecordClusterBuffer(buffer) => E::RecordClusterBuffer(super::ClusterBuffer {
+                    data: buffer.data.iter().flat_map(|row| row.iter().copied()).collect(),
+                }),
+                // The pixel count is unknown.
+                MeasurementEvent::RecordBasicBuffer(buffer) => E::RecordBasicBuffer(super::BasicBuffer {
+                    shading_ops: buffer.shading_ops,
+                    lighting_ops: buffer.lighting_ops,
+                    pixel_count: 0,
+                }),
+                MeasurementEvent::ThreadName(index, name) => E::ThreadName(index, name),
+                MeasurementEvent::CpuTimeSpan(index, name, span) => E::CpuTimeSpan(index, name, span),
+            }
+        }
+    }
+}
+
+/// The encoding of version 3.
+mod v3 {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    pub enum MeasurementEvent {
+        SampleName(SampleIndex, String),
+        BeginRun(RunIndex),
+        EndRun,
+        BeginFrame(FrameIndex),
+        EndFrame,
+        BeginTimeSpan(SampleIndex, GpuCpuTimeSpan),
+        EndTimeSpan,
+        RecordClusterBuffer(ClusterBuffer),
+        RecordBasicBuffer(BasicBuffer),
+        ThreadName(ThreadIndex, String),
+        CpuTimeSpan(ThreadIndex, String, TimeSpan),
+    }
+}
+
+/// Reads as many bytes as are available up to the length of `buf`.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(len)
+}
+
+/// Streams the events from a file of any version. The sample names in the
+/// header are yielded as [`MeasurementEvent::SampleName`] events first, so
+/// consumers see the same event sequence regardless of the version.
+pub struct EventReader<R: Read> {
+    reader: io::Chain<io::Cursor<Vec<u8>>, R>,
+    pub version: u32,
+    pub header: EventsHeader,
+    header_sample_index: usize,
+    /// Span names of files before version 4, which are interned as they are
+    /// read.
+    legacy_span_indices: HashMap<String, CpuSpanIndex>,
+    /// A span that follows the name we just yielded for it.
+    pending: Option<MeasurementEvent>,
+    done: bool,
+}
+
+impl EventReader<io::BufReader<std::fs::File>> {
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Self::new(io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        let len = read_up_to(&mut reader, &mut magic)?;
+
+        if magic == EVENTS_MAGIC {
+            let mut version = [0u8; 4];
+            reader.read_exact(&mut version)?;
+            let version = u32::from_le_bytes(version);
+            if version > EVENTS_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Events file version {} is newer than the supported version {}.",
+                        version, EVENTS_VERSION
+                    ),
+                ));
+            }
+            let header = bincode::deserialize_from(&mut reader).map_err(bincode_to_io_error)?;
+            Ok(Self {
+                reader: io::Cursor::new(Vec::new()).chain(reader),
+                version,
+                header,
+                header_sample_index: 0,
+                legacy_span_indices: HashMap::new(),
+                pending: None,
+                done: false,
+            })
+        } else {
+            // Version 0 has no header, put back what we read.
+            Ok(Self {
+                reader: io::Cursor::new(magic[0..len].to_vec()).chain(reader),
+                version: 0,
+                header: Default::default(),
+                header_sample_index: 0,
+                legacy_span_indices: HashMap::new(),
+                pending: None,
+                done: false,
+            })
+        }
+    }
+
+    fn upgrade(&mut self, event: v3::MeasurementEvent) -> MeasurementEvent {
+        use v3::MeasurementEvent as E;
+        match event {
+            E::SampleName(index, name) => MeasurementEvent::SampleName(index, name),
+            E::BeginRun(index) => MeasurementEvent::BeginRun(index),
+            E::EndRun => MeasurementEvent::EndRun,
+            E::BeginFrame(index) => MeasurementEvent::BeginFrame(index),
+            E::EndFrame => MeasurementEvent::EndFrame,
+            E::BeginTimeSpan(index, span) => MeasurementEvent::BeginTimeSpan(index, span),
+            E::EndTimeSpan => MeasurementEvent::EndTimeSpan,
+            E::RecordClusterBuffer(buffer) => MeasurementEvent::RecordClusterBuffer(buffer),
+            E::RecordBasicBuffer(buffer) => MeasurementEvent::RecordBasicBuffer(buffer),
+            E::ThreadName(index, name) => MeasurementEvent::ThreadName(index, name),
+            E::CpuTimeSpan(thread_index, name, span) => {
+                let next_index = CpuSpanIndex::from_usize(self.legacy_span_indices.len());
+                match self.legacy_span_indices.entry(name) {
+                    Entry::Occupied(entry) => MeasurementEvent::CpuTimeSpan(thread_index, *entry.get(), span),
+                    Entry::Vacant(entry) => {
+                        let name = entry.key().clone();
+                        entry.insert(next_index);
+                        self.pending = Some(MeasurementEvent::CpuTimeSpan(thread_index, next_index, span));
+                        MeasurementEvent::CpuSpanName(next_index, name)
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = io::Result<MeasurementEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if let Some(name) = self.header.sample_names.get(self.header_sample_index) {
+            let event = MeasurementEvent::SampleName(SampleIndex::from_usize(self.header_sample_index), name.clone());
+            self.header_sample_index += 1;
+            return Some(Ok(event));
+        }
+
+        if let Some(event) = self.pending.take() {
+            return Some(Ok(event));
+        }
+
+        let event = match self.version {
+            0..=2 => bincode::deserialize_from::<_, v2::MeasurementEvent>(&mut self.reader)
+                .map(|event| self.upgrade(v3::MeasurementEvent::from(event))),
+            3 => {
+                bincode::deserialize_from::<_, v3::MeasurementEvent>(&mut self.reader).map(|event| self.upgrade(event))
+            }
+            _ => bincode::deserialize_from::<_, MeasurementEvent>(&mut self.reader),
+        };
+
+        match event {
+            Ok(event) => Some(Ok(event)),
+            Err(error) => {
+                self.done = true;
+                match bincode_to_io_error(error) {
+                    ref error if error.kind() == io::ErrorKind::UnexpectedEof => None,
+                    error => Some(Err(error)),
+                }
+            }
+        }
+    }
+}
+
+/// Reads all events from an `events.bin` file.
+pub fn read_events(path: impl AsRef<std::path::Path>) -> io::Result<Vec<MeasurementEvent>> {
+    EventReader::open(path)?.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events() -> Vec<MeasurementEvent> {
+        vec![
+            MeasurementEvent::SampleName(SampleIndex::from_usize(1), "late".to_string()),
+            MeasurementEvent::BeginRun(RunIndex::from_usize(0)),
+            MeasurementEvent::EndRun,
+        ]
+    }
+
+    fn encode(events: &[MeasurementEvent], bytes: &mut Vec<u8>) {
+        for event in events.iter() {
+            bincode::serialize_into(&mut *bytes, event).unwrap();
+        }
+    }
+
+    #[test]
+    fn reads_version_0() {
+        let mut bytes = Vec::new();
+        encode(&events(), &mut bytes);
+
+        let reader = EventReader::new(&bytes[..]).unwrap();
+        assert_eq!(0, reader.version);
+        let read: Vec<MeasurementEvent> = reader.collect::<io::Result<_>>().unwrap();
+        assert_eq!(3, read.len());
+    }
+
+    #[test]
+    fn reads_header_sample_names_as_events() {
+        let mut bytes = Vec::new();
+        write_header(
+            &mut bytes,
+            &EventsHeader {
+                sample_names: vec!["early".to_string()],
+                configuration: "[global]".to_string(),
+            },
+        )
+        .unwrap();
+        encode(&events(), &mut bytes);
+
+        let reader = EventReader::new(&bytes[..]).unwrap();
+        assert_eq!(EVENTS_VERSION, reader.version);
+        assert_eq!("[global]", reader.header.configuration);
+        let read: Vec<MeasurementEvent> = reader.collect::<io::Result<_>>().unwrap();
+        assert_eq!(4, read.len());
+        match read[0] {
+            MeasurementEvent::SampleName(index, ref name) => {
+                assert_eq!(0, index.to_usize());
+                assert_eq!("early", name);
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_version_2_cluster_buffers() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&EVENTS_MAGIC);
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &EventsHeader::default()).unwrap();
+        // A RecordClusterBuffer event with the fixed size layout.
+        bytes.extend_from_slice(&7u32.to_le_bytes());
+        for value in 0..1024u32 {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let read: Vec<MeasurementEvent> = EventReader::new(&bytes[..])
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        match read[0] {
+            MeasurementEvent::RecordClusterBuffer(ref buffer) => {
+                assert_eq!(256, buffer.histogram_bin_count());
+                assert_eq!(1, buffer.light_indices_count());
+                assert_eq!(512, buffer.lights_per_cluster_histogram()[0]);
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_version_3_cpu_span_names() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&EVENTS_MAGIC);
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &EventsHeader::default()).unwrap();
+        // Two CpuTimeSpan events with the name inline.
+        for &begin in &[1u64, 2] {
+            bytes.extend_from_slice(&10u32.to_le_bytes());
+            bytes.extend_from_slice(&0u64.to_le_bytes());
+            bincode::serialize_into(&mut bytes, "load scene").unwrap();
+            bytes.extend_from_slice(&begin.to_le_bytes());
+            bytes.extend_from_slice(&(begin + 1).to_le_bytes());
+        }
+
+        let read: Vec<MeasurementEvent> = EventReader::new(&bytes[..])
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(3, read.len());
+        match read[0] {
+            MeasurementEvent::CpuSpanName(index, ref name) => {
+                assert_eq!(0, index.to_usize());
+                assert_eq!("load scene", name);
+            }
+            ref other => panic!("Unexpected event {:?}", other),
+        }
+        for event in read[1..].iter() {
+            match *event {
+                MeasurementEvent::CpuTimeSpan(_, index, _) => assert_eq!(0, index.to_usize()),
+                ref other => panic!("Unexpected event {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_truncated_trailing_event() {
+        let mut bytes = Vec::new();
+        encode(&events(), &mut bytes);
+        bytes.extend_from_slice(&[3, 0]);
+
+        let read: io::Result<Vec<MeasurementEvent>> = EventReader::new(&bytes[..]).unwrap().collect();
+        // A partially written trailing event looks like the end of the file.
+        assert_eq!(3, read.unwrap().len());
+    }
+}
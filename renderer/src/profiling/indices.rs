@@ -25,4 +25,6 @@ macro_rules! impl_index {
 impl_index!(pub RunIndex(usize), from_usize, to_usize);
 impl_index!(pub FrameIndex(usize), from_usize, to_usize);
 impl_index!(pub SampleIndex(usize), from_usize, to_usize);
+impl_index!(pub ThreadIndex(usize), from_usize, to_usize);
+impl_index!(pub CpuSpanIndex(usize), from_usize, to_usize);
 impl_index!(pub ProfilerIndex(usize), from_usize, to_usize);
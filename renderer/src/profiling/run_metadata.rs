@@ -0,0 +1,74 @@
+//! Describes the environment a profiling run was recorded in, so that results
+//! can be interpreted and reproduced without knowing how they were produced.
+
+use std::path::{Path, PathBuf};
+
+pub const RUN_METADATA_FILE_NAME: &'static str = "run.toml";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct RunMetadata {
+    pub started_at: String,
+    /// Output of `git describe --always --dirty` in the working directory.
+    pub git_revision: Option<String>,
+    pub gl_vendor: String,
+    pub gl_renderer: String,
+    pub gl_version: String,
+    pub scene_path: PathBuf,
+    pub replay_path: Option<PathBuf>,
+    /// FNV-1a hash of the replay file in hexadecimal.
+    pub replay_hash: Option<String>,
+    /// The effective configuration. Kept as a TOML value so that runs made
+    /// with older configurations can still be read.
+    pub configuration: toml::Value,
+}
+
+impl RunMetadata {
+    pub fn write(&self, profiling_dir: impl AsRef<Path>) -> std::io::Result<()> {
+        let contents = toml::Value::try_from(self).unwrap().to_string();
+        std::fs::write(profiling_dir.as_ref().join(RUN_METADATA_FILE_NAME), contents)
+    }
+
+    pub fn read(profiling_dir: impl AsRef<Path>) -> std::io::Result<Self> {
+        let contents = std::fs::read_to_string(profiling_dir.as_ref().join(RUN_METADATA_FILE_NAME))?;
+        toml::from_str(&contents).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+}
+
+pub fn git_revision() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(&["describe", "--always", "--dirty"])
+        .output()
+        .ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok().map(|s| s.trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// 64-bit FNV-1a, a simple hash that is stable across platforms and
+/// compiler versions.
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn file_hash(path: impl AsRef<Path>) -> std::io::Result<String> {
+    Ok(format!("{:016x}", fnv1a_64(&std::fs::read(path)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_64_reference_values() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a_64(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a_64(b"a"));
+        assert_eq!(0x8594_4171_f739_67e8, fnv1a_64(b"foobar"));
+    }
+}
@@ -0,0 +1,399 @@
+//! Summary statistics of recorded time spans and comparison of two
+//! recordings. Time spans are grouped by their sample path, for example
+//! `/frame/basic`, and spans that share a path within a frame are summed.
+
+use super::*;
+use std::collections::HashMap;
+
+/// Orders NaN after all other values so sorting never panics.
+fn cmp_f64(a: f64, b: f64) -> std::cmp::Ordering {
+    a.partial_cmp(&b).unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+/// Statistics of a set of durations in milliseconds.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub stddev: f64,
+}
+
+impl Summary {
+    pub fn from_values(values: &mut [f64]) -> Self {
+        if values.is_empty() {
+            return Default::default();
+        }
+
+        values.sort_by(|&a, &b| cmp_f64(a, b));
+
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+        let median = if count % 2 == 0 {
+            (values[count / 2 - 1] + values[count / 2]) / 2.0
+        } else {
+            values[count / 2]
+        };
+
+        // Nearest rank.
+        let percentile = |p: f64| values[((p * count as f64).ceil() as usize).max(1) - 1];
+
+        Summary {
+            count,
+            mean,
+            median,
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            stddev: variance.sqrt(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Timer {
+    Cpu,
+    Gpu,
+}
+
+impl std::str::FromStr for Timer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cpu" => Ok(Timer::Cpu),
+            "gpu" => Ok(Timer::Gpu),
+            other => Err(format!("Unknown timer {:?}, expected cpu or gpu.", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SampleStats {
+    pub path: String,
+    pub cpu: Summary,
+    pub gpu: Summary,
+    /// Sorted durations per frame, kept for significance tests.
+    pub cpu_values: Vec<f64>,
+    pub gpu_values: Vec<f64>,
+}
+
+impl SampleStats {
+    pub fn summary(&self, timer: Timer) -> &Summary {
+        match timer {
+            Timer::Cpu => &self.cpu,
+            Timer::Gpu => &self.gpu,
+        }
+    }
+
+    pub fn values(&self, timer: Timer) -> &[f64] {
+        match timer {
+            Timer::Cpu => &self.cpu_values,
+            Timer::Gpu => &self.gpu_values,
+        }
+    }
+}
+
+/// Computes statistics per sample path over all frames of all time sensitive
+/// runs. The first run records GPU counters which distort the timings, so it
+/// is only used when it is the only run.
+pub fn sample_stats(events: &[MeasurementEvent]) -> Vec<SampleStats> {
+    let run_count = events
+        .iter()
+        .filter(|event| match event {
+            MeasurementEvent::BeginRun(_) => true,
+            _ => false,
+        })
+        .count();
+
+    let mut sample_names: Vec<&str> = Vec::new();
+    let mut path_stack: Vec<String> = Vec::new();
+    let mut path_indices: HashMap<String, usize> = HashMap::new();
+    let mut paths: Vec<String> = Vec::new();
+    let mut values: Vec<(Vec<f64>, Vec<f64>)> = Vec::new();
+    // GPU time is `None` while only CPU-only spans were recorded for a path.
+    let mut frame: HashMap<usize, (u64, Option<u64>)> = HashMap::new();
+    let mut skip_run = false;
+
+    for event in events.iter() {
+        match *event {
+            MeasurementEvent::SampleName(index, ref name) => {
+                assert_eq!(index.to_usize(), sample_names.len());
+                sample_names.push(name);
+            }
+            MeasurementEvent::BeginRun(index) => {
+                skip_run = index.to_usize() == 0 && run_count > 1;
+            }
+            MeasurementEvent::BeginFrame(_) => {
+                frame.clear();
+            }
+            MeasurementEvent::BeginTimeSpan(sample_index, span) => {
+                let path = format!(
+                    "{}/{}",
+                    path_stack.last().map(String::as_str).unwrap_or(""),
+                    sample_names[sample_index.to_usize()]
+                );
+                let path_index = *path_indices.entry(path.clone()).or_insert_with(|| {
+                    paths.push(path.clone());
+                    values.push(Default::default());
+                    paths.len() - 1
+                });
+                path_stack.push(path);
+
+                let entry = frame.entry(path_index).or_default();
+                entry.0 += span.cpu.delta();
+                if span.has_gpu() {
+                    *entry.1.get_or_insert(0) += span.gpu.delta();
+                }
+            }
+            MeasurementEvent::EndTimeSpan => {
+                path_stack.pop();
+            }
+            MeasurementEvent::EndFrame => {
+                if !skip_run {
+                    for (&path_index, &(cpu, gpu)) in frame.iter() {
+                        let (ref mut cpu_values, ref mut gpu_values) = values[path_index];
+                        cpu_values.push(cpu as f64 / 1_000_000.0);
+                        if let Some(gpu) = gpu {
+                            gpu_values.push(gpu as f64 / 1_000_000.0);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    paths
+        .into_iter()
+        .zip(values.into_iter())
+        .map(|(path, (mut cpu_values, mut gpu_values))| SampleStats {
+            path,
+            cpu: Summary::from_values(&mut cpu_values),
+            gpu: Summary::from_values(&mut gpu_values),
+            cpu_values,
+            gpu_values,
+        })
+        .collect()
+}
+
+/// Complementary error function with a fractional error below 1.2e-7, see
+/// `erfcc` in "Numerical Recipes in C".
+fn erfc(x: f64) -> f64 {
+    const C: [f64; 10] = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ];
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z + C.iter().rev().fold(0.0, |acc, &c| c + t * acc)).exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+/// One sided Mann-Whitney U test with the normal approximation and a
+/// correction for ties. Returns the probability of seeing values in `new` at
+/// least this much larger than those in `base` when both come from the same
+/// distribution.
+pub fn mann_whitney_p(base: &[f64], new: &[f64]) -> f64 {
+    let (n1, n2) = (base.len() as f64, new.len() as f64);
+    if base.is_empty() || new.is_empty() {
+        return 1.0;
+    }
+
+    let mut values: Vec<(f64, bool)> = base
+        .iter()
+        .map(|&v| (v, false))
+        .chain(new.iter().map(|&v| (v, true)))
+        .collect();
+    values.sort_by(|a, b| cmp_f64(a.0, b.0));
+
+    // Assign the average rank to runs of equal values.
+    let mut new_rank_sum = 0.0;
+    let mut tie_sum = 0.0;
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i + 1;
+        while j < values.len() && values[j].0 == values[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 / 2.0;
+        new_rank_sum += rank * values[i..j].iter().filter(|&&(_, is_new)| is_new).count() as f64;
+        let t = (j - i) as f64;
+        tie_sum += t * t * t - t;
+        i = j;
+    }
+
+    let n = n1 + n2;
+    let u = new_rank_sum - n2 * (n2 + 1.0) / 2.0;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_sum / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    // Continuity correction.
+    let z = (u - mean - 0.5) / variance.sqrt();
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub path: String,
+    pub base: f64,
+    pub new: f64,
+    /// See [`mann_whitney_p`].
+    pub p: f64,
+}
+
+impl Comparison {
+    /// Relative change of the new median with respect to the base median.
+    pub fn change(&self) -> f64 {
+        (self.new - self.base) / self.base
+    }
+
+    /// The median has to increase by more than `threshold` and the increase
+    /// has to be significant at level `alpha`.
+    pub fn is_regression(&self, threshold: f64, alpha: f64) -> bool {
+        self.change() > threshold && self.p < alpha
+    }
+}
+
+/// Compares the medians of the sample paths present in both recordings.
+/// Paths with a base median below `min_median` milliseconds are skipped
+/// because their relative change is dominated by noise. Paths with a base
+/// median of zero have no relative change and are always skipped.
+pub fn compare(base: &[SampleStats], new: &[SampleStats], timer: Timer, min_median: f64) -> Vec<Comparison> {
+    base.iter()
+        .filter_map(|base| {
+            let new = new.iter().find(|new| new.path == base.path)?;
+            let base_median = base.summary(timer).median;
+            if base_median <= 0.0 || base_median < min_median || new.summary(timer).count == 0 {
+                return None;
+            }
+            Some(Comparison {
+                path: base.path.clone(),
+                base: base_median,
+                new: new.summary(timer).median,
+                p: mann_whitney_p(base.values(timer), new.values(timer)),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_uniform_values() {
+        let mut values: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        let summary = Summary::from_values(&mut values);
+        assert_eq!(100, summary.count);
+        assert_eq!(50.5, summary.mean);
+        assert_eq!(50.5, summary.median);
+        assert_eq!(95.0, summary.p95);
+        assert_eq!(99.0, summary.p99);
+        assert!((summary.stddev - 28.866).abs() < 0.001);
+    }
+
+    #[test]
+    fn summary_sorts_nan_last() {
+        let mut values = vec![2.0, std::f64::NAN, 1.0];
+        let summary = Summary::from_values(&mut values);
+        assert_eq!(1.0, values[0]);
+        assert_eq!(2.0, summary.median);
+        assert!(values[2].is_nan());
+    }
+
+    #[test]
+    fn groups_by_path_and_skips_first_run() {
+        fn span(cpu: u64, gpu: u64) -> GpuCpuTimeSpan {
+            GpuCpuTimeSpan {
+                cpu: TimeSpan { begin: 0, end: cpu },
+                gpu: TimeSpan { begin: 0, end: gpu },
+            }
+        }
+
+        let mut events = vec![
+            MeasurementEvent::SampleName(SampleIndex::from_usize(0), "frame".to_string()),
+            MeasurementEvent::SampleName(SampleIndex::from_usize(1), "basic".to_string()),
+            MeasurementEvent::SampleName(SampleIndex::from_usize(2), "cpu".to_string()),
+        ];
+        for run_index in 0..2 {
+            let ms = (run_index + 1) * 1_000_000;
+            events.extend(vec![
+                MeasurementEvent::BeginRun(RunIndex::from_usize(run_index as usize)),
+                MeasurementEvent::BeginFrame(FrameIndex::from_usize(0)),
+                MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(0), span(4 * ms, 4 * ms)),
+                MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(1), span(ms, 2 * ms)),
+                MeasurementEvent::EndTimeSpan,
+                MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(1), span(ms, ms)),
+                MeasurementEvent::EndTimeSpan,
+                MeasurementEvent::BeginTimeSpan(SampleIndex::from_usize(2), span(ms, 0)),
+                MeasurementEvent::EndTimeSpan,
+                MeasurementEvent::EndTimeSpan,
+                MeasurementEvent::EndFrame,
+                MeasurementEvent::EndRun,
+            ]);
+        }
+
+        let stats = sample_stats(&events);
+        assert_eq!(
+            vec!["/frame", "/frame/basic", "/frame/cpu"],
+            stats.iter().map(|s| s.path.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(1, stats[1].gpu.count);
+        assert_eq!(4.0, stats[1].cpu.median);
+        assert_eq!(6.0, stats[1].gpu.median);
+        assert_eq!(1, stats[2].cpu.count);
+        assert_eq!(0, stats[2].gpu.count);
+    }
+
+    #[test]
+    fn regressions_must_be_significant() {
+        fn stats(values: Vec<f64>) -> SampleStats {
+            let mut gpu_values = values;
+            SampleStats {
+                path: "/frame".to_string(),
+                cpu: Summary::default(),
+                gpu: Summary::from_values(&mut gpu_values),
+                cpu_values: Vec::new(),
+                gpu_values,
+            }
+        }
+
+        let ramp = |offset: f64| (0..50).map(|i| offset + 0.004 * i as f64).collect::<Vec<f64>>();
+
+        let base = [stats(ramp(1.0))];
+        let slower = [stats(ramp(1.1))];
+        assert!(compare(&base, &slower, Timer::Gpu, 0.0)[0].is_regression(0.05, 0.01));
+        assert!(!compare(&base, &base, Timer::Gpu, 0.0)[0].is_regression(0.0, 0.01));
+
+        // The median increases by 10% but three frames are too few to tell.
+        let few = [stats(vec![1.0, 1.2, 0.8])];
+        let few_slower = [stats(vec![1.3, 0.9, 1.1])];
+        let comparison = &compare(&few, &few_slower, Timer::Gpu, 0.0)[0];
+        assert!(comparison.change() > 0.05);
+        assert!(!comparison.is_regression(0.05, 0.01));
+
+        // Without a base duration there is no relative change.
+        let zero = [stats(vec![0.0; 3])];
+        assert!(compare(&zero, &few_slower, Timer::Gpu, 0.0).is_empty());
+
+        assert!(mann_whitney_p(&ramp(1.0), &ramp(1.0)) > 0.4);
+        assert!(mann_whitney_p(&ramp(1.1), &ramp(1.0)) > 0.99);
+    }
+}
@@ -0,0 +1,414 @@
+//! The file format of record and replay mode.
+//!
+//! A recording starts with [`RECORDING_MAGIC`], the format version as a little
+//! endian `u32` and a bincode encoded [`RecordingHeader`]. The bincode encoded
+//! frames follow, and the file ends with the index: the bincode encoded byte
+//! offsets of the frames.
+//!
+//! The index and the frame count are only known when the recording is
+//! finished, at which point the header is written again. Frames are flushed
+//! as they are written, so the frames of a recording that was never finished,
+//! for example because the application crashed, are found by decoding them in
+//! order. A trailing frame that does not decode is dropped.
+//!
+//! Version 0 recordings have no magic and no header. They start with the
+//! cameras as raw bytes, followed by the frames.
+
+use crate::camera;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+pub const RECORDING_MAGIC: [u8; 8] = *b"RECORDNG";
+pub const RECORDING_VERSION: u32 = 1;
+
+/// The recorded state of a `camera::Camera`. It is spelled out rather than
+/// serializing the camera itself so that changes to the camera don't silently
+/// change the format. Changing this struct requires a new
+/// [`RECORDING_VERSION`].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct RecordedCamera {
+    pub z0: f32,
+    pub z1: f32,
+    pub positional_velocity: f32,
+    pub angular_velocity: f32,
+    pub zoom_velocity: f32,
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+}
+
+impl From<camera::Camera> for RecordedCamera {
+    fn from(camera: camera::Camera) -> Self {
+        let camera::Camera { properties, transform } = camera;
+        RecordedCamera {
+            z0: properties.z0,
+            z1: properties.z1,
+            positional_velocity: properties.positional_velocity,
+            angular_velocity: properties.angular_velocity,
+            zoom_velocity: properties.zoom_velocity,
+            position: transform.position.into(),
+            yaw: transform.yaw.0,
+            pitch: transform.pitch.0,
+            fov: transform.fov.0,
+        }
+    }
+}
+
+impl From<RecordedCamera> for camera::Camera {
+    fn from(camera: RecordedCamera) -> Self {
+        camera::Camera {
+            properties: camera::CameraProperties {
+                z0: camera.z0,
+                z1: camera.z1,
+                positional_velocity: camera.positional_velocity,
+                angular_velocity: camera.angular_velocity,
+                zoom_velocity: camera.zoom_velocity,
+            },
+            transform: camera::CameraTransform {
+                position: camera.position.into(),
+                yaw: cgmath::Rad(camera.yaw),
+                pitch: cgmath::Rad(camera.pitch),
+                fov: cgmath::Rad(camera.fov),
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordingHeader {
+    /// Relative to the resource directory. The remaining fields that are
+    /// optional are unknown for version 0 recordings.
+    pub scene_path: Option<PathBuf>,
+    /// FNV-1a hash of the configuration in TOML without the mode and the
+    /// record and replay sections.
+    pub configuration_hash: Option<u64>,
+    pub seed: Option<[u8; 32]>,
+    /// The cameras at the start of the recording.
+    pub cameras: Vec<RecordedCamera>,
+    pub frame_count: u64,
+    /// Zero if the recording was not finished.
+    pub index_offset: u64,
+}
+
+fn bincode_to_io_error(error: bincode::Error) -> io::Error {
+    match *error {
+        bincode::ErrorKind::Io(error) => error,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
+
+pub struct RecordingWriter<W: Write + Seek> {
+    writer: W,
+    header: RecordingHeader,
+    header_offset: u64,
+    index: Vec<u64>,
+}
+
+impl RecordingWriter<io::BufWriter<std::fs::File>> {
+    pub fn create(path: impl AsRef<std::path::Path>, header: RecordingHeader) -> io::Result<Self> {
+        Self::new(io::BufWriter::new(std::fs::File::create(path)?), header)
+    }
+}
+
+impl<W: Write + Seek> RecordingWriter<W> {
+    pub fn new(mut writer: W, header: RecordingHeader) -> io::Result<Self> {
+        let header = RecordingHeader {
+            frame_count: 0,
+            index_offset: 0,
+            ..header
+        };
+        writer.write_all(&RECORDING_MAGIC)?;
+        writer.write_all(&RECORDING_VERSION.to_le_bytes())?;
+        let header_offset = writer.seek(SeekFrom::Current(0))?;
+        bincode::serialize_into(&mut writer, &header).map_err(bincode_to_io_error)?;
+        Ok(Self {
+            writer,
+            header,
+            header_offset,
+            index: Vec::new(),
+        })
+    }
+
+    /// Flushes the frame so that it survives the application crashing.
+    pub fn write_frame(&mut self, frame: &impl Serialize) -> io::Result<()> {
+        self.index.push(self.writer.seek(SeekFrom::Current(0))?);
+        bincode::serialize_into(&mut self.writer, frame).map_err(bincode_to_io_error)?;
+        self.writer.flush()
+    }
+
+    /// Writes the index and completes the header.
+    pub fn finish(mut self) -> io::Result<W> {
+        let index_offset = self.writer.seek(SeekFrom::Current(0))?;
+        bincode::serialize_into(&mut self.writer, &self.index).map_err(bincode_to_io_error)?;
+
+        // The header has the same size as before because only fixed size fields changed.
+        self.header.frame_count = self.index.len() as u64;
+        self.header.index_offset = index_offset;
+        self.writer.seek(SeekFrom::Start(self.header_offset))?;
+        bincode::serialize_into(&mut self.writer, &self.header).map_err(bincode_to_io_error)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The cameras of version 0 recordings are the memory representation of
+/// `camera::Camera` at the time, which is 11 `f32`s.
+fn read_legacy_camera(reader: &mut impl Read) -> io::Result<RecordedCamera> {
+    let mut values = [0f32; 11];
+    for value in values.iter_mut() {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        *value = f32::from_le_bytes(bytes);
+    }
+    let [z0, z1, positional_velocity, angular_velocity, zoom_velocity, x, y, z, yaw, pitch, fov] = values;
+    Ok(RecordedCamera {
+        z0,
+        z1,
+        positional_velocity,
+        angular_velocity,
+        zoom_velocity,
+        position: [x, y, z],
+        yaw,
+        pitch,
+        fov,
+    })
+}
+
+/// Reads recordings of any version with random access to the frames, which
+/// are decoded as `F`.
+pub struct RecordingReader<R: Read + Seek, F> {
+    reader: R,
+    pub version: u32,
+    pub header: RecordingHeader,
+    index: Vec<u64>,
+    _frame: PhantomData<fn() -> F>,
+}
+
+impl<F: DeserializeOwned> RecordingReader<io::BufReader<std::fs::File>, F> {
+    pub fn open(path: impl AsRef<std::path::Path>, legacy_camera_count: usize) -> io::Result<Self> {
+        Self::new(io::BufReader::new(std::fs::File::open(path)?), legacy_camera_count)
+    }
+}
+
+impl<R: Read + Seek, F: DeserializeOwned> RecordingReader<R, F> {
+    /// Version 0 recordings don't store how many cameras they contain, so it
+    /// has to be passed in.
+    pub fn new(mut reader: R, legacy_camera_count: usize) -> io::Result<Self> {
+        let start = reader.seek(SeekFrom::Current(0))?;
+        let mut magic = [0u8; 8];
+        let has_magic = match reader.read_exact(&mut magic) {
+            Ok(()) => magic == RECORDING_MAGIC,
+            Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => false,
+            Err(error) => return Err(error),
+        };
+
+        let (version, header) = if has_magic {
+            let mut version = [0u8; 4];
+            reader.read_exact(&mut version)?;
+            let version = u32::from_le_bytes(version);
+            if version > RECORDING_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Recording version {} is newer than the supported version {}.",
+                        version, RECORDING_VERSION
+                    ),
+                ));
+            }
+            let header: RecordingHeader = bincode::deserialize_from(&mut reader).map_err(bincode_to_io_error)?;
+            (version, header)
+        } else {
+            reader.seek(SeekFrom::Start(start))?;
+            let cameras = (0..legacy_camera_count)
+                .map(|_| read_legacy_camera(&mut reader))
+                .collect::<io::Result<_>>()?;
+            let header = RecordingHeader {
+                scene_path: None,
+                configuration_hash: None,
+                seed: None,
+                cameras,
+                frame_count: 0,
+                index_offset: 0,
+            };
+            (0, header)
+        };
+
+        let index = if header.index_offset == 0 {
+            Self::scan(&mut reader)?
+        } else {
+            reader.seek(SeekFrom::Start(header.index_offset))?;
+            bincode::deserialize_from(&mut reader).map_err(bincode_to_io_error)?
+        };
+
+        Ok(Self {
+            reader,
+            version,
+            header,
+            index,
+            _frame: PhantomData,
+        })
+    }
+
+    /// Builds the index by decoding the frames that follow the current
+    /// position. Decoding stops at the first trailing bytes that are not a
+    /// complete frame, which a crash can leave behind. Only errors of the
+    /// reader itself are returned.
+    fn scan(reader: &mut R) -> io::Result<Vec<u64>> {
+        let start = reader.seek(SeekFrom::Current(0))?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut index = Vec::new();
+        loop {
+            let offset = reader.seek(SeekFrom::Current(0))?;
+            if offset >= end {
+                return Ok(index);
+            }
+            match bincode::deserialize_from::<_, F>(&mut *reader) {
+                Ok(_) => index.push(offset),
+                Err(error) => {
+                    let reader_failed = match *error {
+                        bincode::ErrorKind::Io(ref error) => error.kind() != io::ErrorKind::UnexpectedEof,
+                        _ => false,
+                    };
+                    if reader_failed {
+                        return Err(bincode_to_io_error(error));
+                    }
+                    log::warn!(
+                        "Ignoring the last {} bytes of the recording after {} frames: {}",
+                        end - offset,
+                        index.len(),
+                        error
+                    );
+                    return Ok(index);
+                }
+            }
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn read_frame(&mut self, frame_index: usize) -> io::Result<F> {
+        self.reader.seek(SeekFrom::Start(self.index[frame_index]))?;
+        bincode::deserialize_from(&mut self.reader).map_err(bincode_to_io_error)
+    }
+
+    pub fn read_frames(&mut self) -> io::Result<Vec<F>> {
+        (0..self.frame_count()).map(|i| self.read_frame(i)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ValueAsBytes;
+
+    fn camera(x: f32) -> camera::Camera {
+        camera::Camera {
+            properties: camera::CameraProperties {
+                z0: -0.1,
+                z1: -100.0,
+                positional_velocity: 2.0,
+                angular_velocity: 0.4,
+                zoom_velocity: 1.0,
+            },
+            transform: camera::CameraTransform {
+                position: cgmath::Point3::new(x, 1.0, 1.5),
+                yaw: cgmath::Rad(0.5),
+                pitch: cgmath::Rad(-0.25),
+                fov: cgmath::Rad(1.5),
+            },
+        }
+    }
+
+    fn header() -> RecordingHeader {
+        RecordingHeader {
+            scene_path: Some(PathBuf::from("bistro/Bistro_Exterior.bin")),
+            configuration_hash: Some(7),
+            seed: Some([3; 32]),
+            cameras: vec![camera(0.0).into(), camera(1.0).into()],
+            frame_count: 0,
+            index_offset: 0,
+        }
+    }
+
+    fn frames() -> Vec<Vec<u32>> {
+        vec![vec![], vec![1, 2], vec![3]]
+    }
+
+    #[test]
+    fn reads_finished_recording_in_any_order() {
+        let mut writer = RecordingWriter::new(io::Cursor::new(Vec::new()), header()).unwrap();
+        for frame in frames().iter() {
+            writer.write_frame(frame).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut reader = RecordingReader::<_, Vec<u32>>::new(io::Cursor::new(bytes), 0).unwrap();
+        assert_eq!(RECORDING_VERSION, reader.version);
+        assert_eq!(3, reader.header.frame_count);
+        assert_ne!(0, reader.header.index_offset);
+        assert_eq!(header().cameras, reader.header.cameras);
+        assert_eq!(vec![3], reader.read_frame(2).unwrap());
+        assert_eq!(vec![1, 2], reader.read_frame(1).unwrap());
+        assert_eq!(frames(), reader.read_frames().unwrap());
+    }
+
+    #[test]
+    fn reads_unfinished_recording() {
+        let mut writer = RecordingWriter::new(io::Cursor::new(Vec::new()), header()).unwrap();
+        for frame in frames().iter() {
+            writer.write_frame(frame).unwrap();
+        }
+        let mut bytes = writer.writer.into_inner();
+        bytes.extend_from_slice(&[5, 0]);
+
+        let mut reader = RecordingReader::<_, Vec<u32>>::new(io::Cursor::new(bytes), 0).unwrap();
+        assert_eq!(0, reader.header.index_offset);
+        assert_eq!(frames(), reader.read_frames().unwrap());
+    }
+
+    #[test]
+    fn ignores_undecodable_tail() {
+        let frames: Vec<Option<u32>> = vec![Some(1), None];
+        let mut writer = RecordingWriter::new(io::Cursor::new(Vec::new()), header()).unwrap();
+        for frame in frames.iter() {
+            writer.write_frame(frame).unwrap();
+        }
+        let mut bytes = writer.writer.into_inner();
+        // Not a valid tag of an `Option`.
+        bytes.extend_from_slice(&[7, 1, 2]);
+
+        let mut reader = RecordingReader::<_, Option<u32>>::new(io::Cursor::new(bytes), 0).unwrap();
+        assert_eq!(frames, reader.read_frames().unwrap());
+    }
+
+    #[test]
+    fn converts_cameras() {
+        assert_eq!(camera(2.0), camera::Camera::from(RecordedCamera::from(camera(2.0))));
+    }
+
+    #[test]
+    fn reads_version_0() {
+        let mut bytes = Vec::new();
+        for camera in [camera(0.0), camera(1.0)].iter() {
+            bytes.extend_from_slice(camera.value_as_bytes());
+        }
+        for frame in frames().iter() {
+            bincode::serialize_into(&mut bytes, frame).unwrap();
+        }
+
+        let mut reader = RecordingReader::<_, Vec<u32>>::new(io::Cursor::new(bytes), 2).unwrap();
+        assert_eq!(0, reader.version);
+        assert_eq!(None, reader.header.scene_path);
+        assert_eq!(header().cameras, reader.header.cameras);
+        assert_eq!(frames(), reader.read_frames().unwrap());
+    }
+}
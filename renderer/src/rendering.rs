@@ -39,16 +39,39 @@ pub const COMMON_DECLARATION: &'static str = concat!(
     attribute_location_declaration!(),
 );
 
+/// Maximum number of views rendered by a single draw, one per eye for
+/// instanced stereo. Matches the array sizes in `camera_buffer.glsl` and
+/// `instance_matrices_buffer.glsl`.
+pub const MAX_VIEW_COUNT: usize = 2;
+
+/// Matches `CameraBuffer` in `camera_buffer.glsl`. Mono rendering repeats its
+/// camera for every view.
 #[derive(Debug)]
 #[repr(C, align(256))]
 pub struct CameraBuffer {
-    pub wld_to_cam: Matrix4<f32>,
-    pub cam_to_wld: Matrix4<f32>,
+    pub wld_to_cam: [Matrix4<f32>; MAX_VIEW_COUNT],
+    pub cam_to_wld: [Matrix4<f32>; MAX_VIEW_COUNT],
+
+    /// Projects into the clip space of the whole framebuffer.
+    pub cam_to_clp: [Matrix4<f32>; MAX_VIEW_COUNT],
+    pub clp_to_cam: [Matrix4<f32>; MAX_VIEW_COUNT],
 
-    pub cam_to_clp: Matrix4<f32>,
-    pub clp_to_cam: Matrix4<f32>,
+    pub cam_pos_in_lgt: [Vector4<f32>; MAX_VIEW_COUNT],
+}
 
-    pub cam_pos_in_lgt: Vector4<f32>,
+/// Sets a `mat4[MAX_VIEW_COUNT]` uniform which occupies consecutive locations.
+pub unsafe fn uniform_view_matrices(
+    gl: &gl::Gl,
+    location: gl::UniformLocation,
+    matrices: &[Matrix4<f64>; MAX_VIEW_COUNT],
+) {
+    for (view_index, matrix) in matrices.iter().enumerate() {
+        gl.uniform_matrix4f(
+            gl::UniformLocation::from_i32_unchecked(location.to_i32() + view_index as i32),
+            gl::MajorAxis::Column,
+            matrix.cast::<f32>().unwrap().as_ref(),
+        );
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, EnumNext)]
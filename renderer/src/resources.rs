@@ -25,6 +25,8 @@ pub struct Material {
     pub ambient_texture_index: usize,
     pub diffuse_texture_index: usize,
     pub specular_texture_index: usize,
+    pub metallic_roughness_texture_index: usize,
+    pub occlusion_texture_index: usize,
 }
 
 pub struct Texture {
@@ -42,7 +44,15 @@ pub struct Resources {
 
     pub scene_file: renderer::scene_file::SceneFile,
 
-    pub point_lights: Vec<PointLight>,
+    /// Static per-instance data for culling on the GPU.
+    pub instance_cull_buffer: gl::BufferName,
+    /// The levels of detail of all meshes for culling on the GPU.
+    pub mesh_lod_buffer: gl::BufferName,
+    /// Number of instances per material, an upper bound on the draw counts.
+    pub material_instance_counts: Vec<usize>,
+    pub material_instance_offsets: Vec<usize>,
+
+    pub area_lights: Vec<AreaLight>,
     pub quad_vao: gl::VertexArrayName,
     pub quad_vb: gl::BufferName,
     pub quad_eb: gl::BufferName,
@@ -147,15 +157,24 @@ fn load_dds_texture(gl: &gl::Gl, file_path: impl AsRef<Path>, srgb: bool) -> io:
 }
 
 impl Resources {
-    pub fn new<P: AsRef<Path>>(gl: &gl::Gl, resource_dir: P, configuration: &Configuration) -> Self {
+    pub fn new<P: AsRef<Path>>(
+        gl: &gl::Gl,
+        resource_dir: P,
+        configuration: &Configuration,
+        profiler: &profiling::ThreadProfiler,
+    ) -> Self {
         let resource_dir = resource_dir.as_ref();
 
         let scene_file_path = std::fs::canonicalize(resource_dir.join(&configuration.global.scene_path)).unwrap();
         let scene_dir = scene_file_path.parent().unwrap();
 
         let scene_file = {
+            let span = profiler.start("read scene");
             let mut file = std::fs::File::open(&scene_file_path).unwrap();
-            renderer::scene_file::SceneFile::read(&mut file).unwrap()
+            let scene_file = renderer::scene_file::SceneFile::read(&mut file)
+                .unwrap_or_else(|error| panic!("Failed to read {:?}: {}", &scene_file_path, error));
+            profiler.stop(span);
+            scene_file
         };
 
         {
@@ -178,6 +197,8 @@ impl Resources {
         }
 
         let (textures, materials) = {
+            let span = profiler.start("load textures");
+
             // NOTE(mickvangelderen): This is a bit silly, should determine this in the scene file.
             let mut textures: Vec<Texture> = scene_file
                 .textures
@@ -252,13 +273,70 @@ impl Resources {
                             color_texture_index(f32_3_to_unorm([r, g, b]))
                         }
                     },
+                    metallic_roughness_texture_index: match material.metallic_roughness_texture_index {
+                        Some(file_texture_index) => file_texture_index.get() as usize,
+                        None => color_texture_index(f32_3_to_unorm([0.0, material.roughness, material.metallic])),
+                    },
+                    occlusion_texture_index: match material.occlusion_texture_index {
+                        Some(file_texture_index) => file_texture_index.get() as usize,
+                        None => color_texture_index([255, 255, 255]),
+                    },
                 })
                 .collect();
 
+            profiler.stop(span);
+
             (textures, materials)
         };
 
+        let instance_cull_buffer = unsafe {
+            let instance_cull_data: Vec<InstanceCullData> = scene_file
+                .instances
+                .iter()
+                .map(|instance| {
+                    let mesh_description = &scene_file.mesh_descriptions[instance.mesh_index as usize];
+                    let sphere = mesh_description.bounding_sphere;
+                    InstanceCullData {
+                        sphere_in_obj: [sphere.p.x, sphere.p.y, sphere.p.z, sphere.r],
+                        lod_offset: mesh_description.lod_offset,
+                        lod_count: mesh_description.lod_count,
+                        vertex_offset: mesh_description.vertex_offset,
+                        material_index: instance.material_index,
+                    }
+                })
+                .collect();
+
+            let name = gl.create_buffer();
+            gl.named_buffer_data(name, instance_cull_data.vec_as_bytes(), gl::STATIC_DRAW);
+            name
+        };
+
+        let mesh_lod_buffer = unsafe {
+            let name = gl.create_buffer();
+            gl.named_buffer_data(name, scene_file.mesh_lods.vec_as_bytes(), gl::STATIC_DRAW);
+            name
+        };
+
+        let material_instance_counts: Vec<usize> = {
+            let mut counts = vec![0; materials.len()];
+            for instance in scene_file.instances.iter() {
+                counts[instance.material_index as usize] += 1;
+            }
+            counts
+        };
+
+        let material_instance_offsets: Vec<usize> = material_instance_counts
+            .iter()
+            .scan(0, |offset, &count| {
+                let result = Some(*offset);
+                *offset += count;
+                result
+            })
+            .collect();
+
         let (scene_vao, scene_vb, scene_eb) = unsafe {
+            let span = profiler.start("upload meshes");
+
             let vao = gl.create_vertex_array();
             let vb = gl.create_buffer();
             let eb = gl.create_buffer();
@@ -313,7 +391,8 @@ impl Resources {
                 (
                     rendering::VS_INSTANCE_INDEX_LOC,
                     U32_1,
-                    Some(1),
+                    // One instance per view, see `stereo_view.glsl`.
+                    Some(rendering::MAX_VIEW_COUNT as u32),
                     BBI_05,
                     instance_index_buffer.vec_as_bytes(),
                 ),
@@ -347,6 +426,8 @@ impl Resources {
             gl.named_buffer_data(eb, scene_file.triangle_buffer.vec_as_bytes(), gl::STATIC_DRAW);
             gl.vertex_array_element_buffer(vao, eb);
 
+            profiler.stop(span);
+
             (vao, vb, eb)
         };
 
@@ -422,7 +503,7 @@ impl Resources {
             (vao, vb, eb)
         };
 
-        let point_lights: Vec<PointLight> = scene_file
+        let area_lights: Vec<AreaLight> = scene_file
             .instances
             .iter()
             .flat_map(|instance| {
@@ -443,18 +524,65 @@ impl Resources {
                                 pos_in_obj[2].get() as f64,
                             )
                         });
-                    let center = vertex_iter.fold(Point3::origin(), |mut acc, p| {
-                        acc += p.to_vec();
-                        acc
-                    }) * (1.0 / vertex_count as f64);
 
+                    let range = Range3::from_points(vertex_iter)?;
                     let transform = &scene_file.transforms[instance.transform_index as usize];
                     let pos_from_obj_to_wld = transform.to_parent();
-                    let pos_in_wld = pos_from_obj_to_wld.transform_point(center).cast().unwrap();
 
-                    Some(PointLight {
+                    // The average vertex normal tells which side of a flat mesh
+                    // emits. It is short for closed meshes, which emit both ways.
+                    let nor_in_obj = scene_file.nor_in_obj_buffer[vertex_offset..(vertex_offset + vertex_count)]
+                        .iter()
+                        .fold(Vector3::zero(), |sum, nor_in_obj| {
+                            sum + Vector3::new(
+                                nor_in_obj[0].get() as f64,
+                                nor_in_obj[1].get() as f64,
+                                nor_in_obj[2].get() as f64,
+                            )
+                        })
+                        / vertex_count as f64;
+                    let nor_in_wld = pos_from_obj_to_wld
+                        .invert()
+                        .map(|nor_from_obj_to_wld| nor_from_obj_to_wld.transpose().transform_vector(nor_in_obj))
+                        .unwrap_or(Vector3::zero());
+
+                    // Half extents of the bounding box along each object axis,
+                    // in world space and sorted from large to small.
+                    let mut axes = [
+                        pos_from_obj_to_wld.transform_vector(Vector3::unit_x() * (range.dx() * 0.5)),
+                        pos_from_obj_to_wld.transform_vector(Vector3::unit_y() * (range.dy() * 0.5)),
+                        pos_from_obj_to_wld.transform_vector(Vector3::unit_z() * (range.dz() * 0.5)),
+                    ];
+                    axes.sort_by(|a, b| b.magnitude2().partial_cmp(&a.magnitude2()).unwrap());
+
+                    // Long thin strips become tubes, everything else is flattened
+                    // to a rectangle. Meshes without extent become tubes of
+                    // length zero, which shade like point lights.
+                    let rectangle_normal = axes[0].cross(axes[1]);
+                    let shape = if axes[1].magnitude() < 0.25 * axes[0].magnitude()
+                        || rectangle_normal.magnitude2() == 0.0
+                    {
+                        AreaLightShape::Tube {
+                            axis: axes[0].cast().unwrap(),
+                        }
+                    } else {
+                        let rectangle_normal = rectangle_normal.normalize();
+                        let facing = rectangle_normal.dot(nor_in_wld);
+                        AreaLightShape::Rectangle {
+                            axis_0: axes[0].cast().unwrap(),
+                            axis_1: axes[1].cast().unwrap(),
+                            normal: if nor_in_obj.magnitude() < 0.5 || facing == 0.0 {
+                                None
+                            } else {
+                                Some((rectangle_normal * facing.signum()).cast().unwrap())
+                            },
+                        }
+                    };
+
+                    Some(AreaLight {
                         tint: Vector3::from(emissive_color).normalize().into(),
-                        position: pos_in_wld,
+                        position: pos_from_obj_to_wld.transform_point(range.center()).cast().unwrap(),
+                        shape,
                         attenuation: light::AttenCoefs::from(configuration.light.attenuation).cast().unwrap(),
                     })
                 } else {
@@ -468,6 +596,10 @@ impl Resources {
             scene_vb,
             scene_eb,
             scene_file,
+            instance_cull_buffer,
+            mesh_lod_buffer,
+            material_instance_counts,
+            material_instance_offsets,
             materials,
             textures,
             quad_vao,
@@ -481,7 +613,7 @@ impl Resources {
             cluster_vb,
             cluster_eb,
             cluster_element_count,
-            point_lights,
+            area_lights,
             draw_resources_pool: Default::default(),
         }
     }
@@ -510,13 +642,8 @@ impl WorldTransforms {
         }
     }
 
-    pub fn recompute(
-        &mut self,
-        gl: &gl::Gl,
-        profiling_context: &mut ProfilingContext,
-        scene_file: &scene_file::SceneFile,
-    ) {
-        let profiler_index = profiling_context.start(gl, self.compute_world_transforms_profiler);
+    pub fn recompute(&mut self, profiling_context: &mut ProfilingContext, scene_file: &scene_file::SceneFile) {
+        let profiler_index = profiling_context.start_cpu(self.compute_world_transforms_profiler);
 
         let scene_file::SceneFile {
             ref instances,
@@ -535,18 +662,30 @@ impl WorldTransforms {
         self.wld_to_obj
             .extend(self.obj_to_wld.iter().map(|obj_to_wld| obj_to_wld.invert().unwrap()));
 
-        profiling_context.stop(gl, profiler_index);
+        profiling_context.stop_cpu(profiler_index);
     }
 }
 
 #[derive(Debug)]
 #[repr(C)]
 pub struct InstanceMatrices {
-    pub obj_to_ren_clp: Matrix4<f32>,
+    pub obj_to_ren_clp: [Matrix4<f32>; rendering::MAX_VIEW_COUNT],
     pub obj_to_lgt: Matrix4<f32>,
     pub obj_to_lgt_inv_tra: Matrix4<f32>,
 }
 
+/// Matches `InstanceCullData` in `cull_instances.comp`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct InstanceCullData {
+    pub sphere_in_obj: [f32; 4],
+    pub lod_offset: u32,
+    pub lod_count: u32,
+    pub vertex_offset: u32,
+    pub material_index: u32,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ProjectionKind {
     Perspective,
     Orthographic,
@@ -558,6 +697,36 @@ pub struct CullingCamera {
     pub projection_kind: ProjectionKind,
 }
 
+/// Levels of detail are selected from a single point per frame so every
+/// culling view, including the eyes and the shadow maps, draws the same
+/// meshes. See `configuration::LodConfiguration`.
+#[derive(Debug, Copy, Clone)]
+pub struct LodCamera {
+    pub pos_in_wld: Point3<f64>,
+    pub threshold: f64,
+}
+
+impl LodCamera {
+    /// Selects a level of detail for a sphere in world space.
+    pub fn select_lod(&self, sphere_wld: scene_file::Sphere3<f64>, lod_count: u32) -> u32 {
+        let distance = (sphere_wld.p - self.pos_in_wld).magnitude();
+        if distance <= sphere_wld.r {
+            return 0;
+        }
+        let size = sphere_wld.r / distance;
+
+        if size >= self.threshold {
+            0
+        } else if size <= 0.0 {
+            // Degenerate spheres have no size.
+            lod_count - 1
+        } else {
+            let lod = 1.0 + (self.threshold / size).log2().floor();
+            f64::min(lod, f64::from(lod_count - 1)) as u32
+        }
+    }
+}
+
 fn intersect_sphere_enlarged_frustum(sphere: scene_file::Sphere3<f64>, frustum: Frustum<f64>) -> bool {
     let nx0 = Vector2::new(-1.0, -frustum.x0).normalize();
     let nx1 = Vector2::new(1.0, frustum.x1).normalize();
@@ -586,16 +755,68 @@ fn intersect_sphere_box(sphere: scene_file::Sphere3<f64>, box3: scene_file::Box3
     r_sq_acc < sphere.r * sphere.r
 }
 
+/// Matches `CullStatsBuffer` in `cull_instances.comp`.
+#[derive(Debug, Default, Copy, Clone)]
+#[repr(C)]
+pub struct CullStats {
+    pub frustum_culled: u32,
+    pub occlusion_culled: u32,
+}
+
+/// Reads back the culling counts of a view. There is a buffer per frame in
+/// flight so the counts can be read back without waiting for the GPU.
+pub struct CullStatsRing {
+    buffer_ring: Ring3<gl::BufferName>,
+    pending: Ring3<bool>,
+    /// Counts of the last completed culling pass. Lags three frames behind
+    /// when culling on the GPU.
+    pub stats: CullStats,
+}
+
+impl CullStatsRing {
+    pub fn new(gl: &gl::Gl) -> Self {
+        Self {
+            buffer_ring: Ring3::new(|| unsafe { gl.create_buffer() }),
+            pending: Ring3::new(|| false),
+            stats: Default::default(),
+        }
+    }
+
+    /// Reads back the counts written three frames ago and returns the cleared
+    /// buffer for the culling shader of this frame. Like the profiling data,
+    /// the counts are assumed to be available by now.
+    pub unsafe fn begin(&mut self, gl: &gl::Gl, frame_index: FrameIndex) -> gl::BufferName {
+        let frame_index = frame_index.to_usize();
+        let buffer = self.buffer_ring[frame_index];
+        if self.pending[frame_index] {
+            gl.get_named_buffer_sub_data(buffer, 0, self.stats.value_as_bytes_mut());
+        }
+        gl.named_buffer_data(buffer, CullStats::default().value_as_bytes(), gl::DYNAMIC_DRAW);
+        self.pending[frame_index] = true;
+        buffer
+    }
+}
+
 pub struct DrawResources {
     // GPU
     pub instance_matrices_buffer: gl::BufferName,
     pub draw_command_buffer: gl::BufferName,
+    /// Per material offset and count of the draw commands written on the GPU.
+    pub draw_range_buffer: gl::BufferName,
+    /// Written by the culling shader for views that do not read the counts
+    /// back.
+    pub cull_stats_buffer: gl::BufferName,
 
     // CPU
     pub instance_matrices_data: Vec<InstanceMatrices>,
     pub draw_command_data: Vec<DrawCommand>,
     pub draw_counts: Vec<usize>,
     pub draw_offsets: Vec<usize>,
+    pub draw_range_data: Vec<[u32; 2]>,
+    pub supports_indirect_draw_count: bool,
+    /// Set when the draw counts were written by the culling shader and can be
+    /// read from the draw range buffer.
+    pub draw_counts_on_gpu: bool,
 
     // Profiling
     pub compute_instance_matrices_profiler: profiling::SampleIndex,
@@ -607,65 +828,94 @@ impl DrawResources {
         Self {
             instance_matrices_buffer: unsafe { gl.create_buffer() },
             draw_command_buffer: unsafe { gl.create_buffer() },
+            draw_range_buffer: unsafe { gl.create_buffer() },
+            cull_stats_buffer: unsafe {
+                let name = gl.create_buffer();
+                gl.named_buffer_data(name, CullStats::default().value_as_bytes(), gl::DYNAMIC_DRAW);
+                name
+            },
 
             instance_matrices_data: Vec::new(),
             draw_command_data: Vec::new(),
             draw_offsets: Vec::new(),
             draw_counts: Vec::new(),
+            draw_range_data: Vec::new(),
+            supports_indirect_draw_count: unsafe { supports_indirect_draw_count(gl) },
+            draw_counts_on_gpu: false,
 
             compute_instance_matrices_profiler: profiling_context.add_sample("inst matrs"),
             compute_draw_commands_profiler: profiling_context.add_sample("draw cmds"),
         }
     }
 
-    pub fn recompute(
+    /// Computes the instance matrices for every view in `wld_to_ren_clp`. The
+    /// last view is repeated to fill up the unused views.
+    pub fn recompute_instance_matrices(
+        &mut self,
+        gl: &gl::Gl,
+        profiling_context: &mut ProfilingContext,
+        wld_to_ren_clp: &[Matrix4<f64>],
+        world_transforms: &WorldTransforms,
+    ) {
+        debug_assert!(!wld_to_ren_clp.is_empty() && wld_to_ren_clp.len() <= rendering::MAX_VIEW_COUNT);
+
+        let profiler_index = profiling_context.start(gl, self.compute_instance_matrices_profiler);
+
+        let instance_count = world_transforms.obj_to_wld.len();
+
+        clear_and_reserve(&mut self.instance_matrices_data, instance_count);
+        self.instance_matrices_data
+            .extend((0..instance_count).into_iter().map(|instance_index| {
+                let obj_to_wld = world_transforms.obj_to_wld[instance_index];
+                let wld_to_obj = world_transforms.wld_to_obj[instance_index];
+                let obj_to_ren_clp = |view_index: usize| -> Matrix4<f32> {
+                    let wld_to_ren_clp = wld_to_ren_clp[std::cmp::min(view_index, wld_to_ren_clp.len() - 1)];
+                    (wld_to_ren_clp * obj_to_wld).cast().unwrap()
+                };
+                InstanceMatrices {
+                    obj_to_ren_clp: [obj_to_ren_clp(0), obj_to_ren_clp(1)],
+                    obj_to_lgt: obj_to_wld.cast().unwrap(),
+                    obj_to_lgt_inv_tra: wld_to_obj.transpose().cast().unwrap(),
+                }
+            }));
+
+        unsafe {
+            gl.named_buffer_data(
+                self.instance_matrices_buffer,
+                self.instance_matrices_data.vec_as_bytes(),
+                gl::DYNAMIC_DRAW,
+            );
+        }
+
+        profiling_context.stop(gl, profiler_index);
+    }
+
+    /// Culls all instances on the CPU and uploads the compacted draw commands.
+    /// Selects a level of detail per instance when `lod_camera` is given.
+    /// Every command draws an instance once per view. Returns the counts of
+    /// the culled instances.
+    pub fn recompute_draw_commands(
         &mut self,
         gl: &gl::Gl,
         profiling_context: &mut ProfilingContext,
         culling_camera: CullingCamera,
-        wld_to_ren_clp: Matrix4<f64>,
+        lod_camera: Option<LodCamera>,
+        view_count: u32,
         world_transforms: &WorldTransforms,
         materials: &[Material],
         scene_file: &scene_file::SceneFile,
-    ) {
+    ) -> CullStats {
         let scene_file::SceneFile {
             ref instances,
             ref mesh_descriptions,
+            ref mesh_lods,
             ..
         } = *scene_file;
 
-        {
-            let profiler_index = profiling_context.start(gl, self.compute_instance_matrices_profiler);
-
-            let instance_count = instances.len();
-
-            clear_and_reserve(&mut self.instance_matrices_data, instance_count);
-            self.instance_matrices_data
-                .extend((0..instance_count).into_iter().map(|instance_index| {
-                    let obj_to_wld = world_transforms.obj_to_wld[instance_index];
-                    let wld_to_obj = world_transforms.wld_to_obj[instance_index];
-                    InstanceMatrices {
-                        obj_to_ren_clp: (wld_to_ren_clp * obj_to_wld).cast().unwrap(),
-                        obj_to_lgt: obj_to_wld.cast().unwrap(),
-                        obj_to_lgt_inv_tra: wld_to_obj.transpose().cast().unwrap(),
-                    }
-                }));
-
-            unsafe {
-                gl.named_buffer_data(
-                    self.instance_matrices_buffer,
-                    self.instance_matrices_data.vec_as_bytes(),
-                    gl::DYNAMIC_DRAW,
-                );
-            }
-
-            profiling_context.stop(gl, profiler_index);
-        }
-
         {
             let profiler_index = profiling_context.start(gl, self.compute_draw_commands_profiler);
 
-            let visible_instance_indices: Vec<usize> = instances
+            let visible_instances: Vec<(usize, u32)> = instances
                 .iter()
                 .enumerate()
                 .filter_map(|(instance_index, instance)| {
@@ -697,18 +947,40 @@ impl DrawResources {
                             intersect_sphere_enlarged_frustum(sphere_cam, culling_camera.frustum)
                         }
                     } {
-                        Some(instance_index)
+                        let lod_index = match lod_camera {
+                            Some(lod_camera) => {
+                                let obj_to_wld = world_transforms.obj_to_wld[instance_index];
+                                let sphere_wld = scene_file::Sphere3 {
+                                    p: obj_to_wld.transform_point(sphere_obj.p),
+                                    r: {
+                                        let r_wld = obj_to_wld
+                                            .transform_vector(Vector3::from_value(sphere_obj.r))
+                                            .map(f64::abs);
+                                        r_wld[r_wld.dominant_axis()]
+                                    },
+                                };
+                                lod_camera.select_lod(sphere_wld, mesh_description.lod_count)
+                            }
+                            None => 0,
+                        };
+                        Some((instance_index, lod_index))
                     } else {
                         None
                     }
                 })
                 .collect();
 
+            let cull_stats = CullStats {
+                frustum_culled: (instances.len() - visible_instances.len()) as u32,
+                occlusion_culled: 0,
+            };
+            self.draw_counts_on_gpu = false;
+
             // Prefix sum draw counts per material.
             clear_and_reserve(&mut self.draw_counts, materials.len());
             self.draw_counts.extend(std::iter::repeat(0).take(materials.len()));
 
-            for &instance_index in visible_instance_indices.iter() {
+            for &(instance_index, _) in visible_instances.iter() {
                 let material_index = instances[instance_index].material_index;
                 self.draw_counts[material_index as usize] += 1;
             }
@@ -726,7 +998,7 @@ impl DrawResources {
                 *draw_count = 0;
             }
 
-            clear_and_reserve(&mut self.draw_command_data, visible_instance_indices.len());
+            clear_and_reserve(&mut self.draw_command_data, visible_instances.len());
             self.draw_command_data.extend(
                 std::iter::repeat(DrawCommand {
                     count: 0,
@@ -735,20 +1007,21 @@ impl DrawResources {
                     base_vertex: 0,
                     base_instance: 0,
                 })
-                .take(visible_instance_indices.len()),
+                .take(visible_instances.len()),
             );
 
             // Fill out the buffer.
 
-            for &instance_index in visible_instance_indices.iter() {
+            for &(instance_index, lod_index) in visible_instances.iter() {
                 let instance = &instances[instance_index];
                 let material_index = instance.material_index as usize;
                 let mesh_description = &mesh_descriptions[instance.mesh_index as usize];
+                let mesh_lod = &mesh_description.lods(mesh_lods)[lod_index as usize];
                 let command_index = self.draw_offsets[material_index] + self.draw_counts[material_index];
                 self.draw_command_data[command_index] = DrawCommand {
-                    count: mesh_description.element_count(),
-                    prim_count: 1,
-                    first_index: mesh_description.element_offset(),
+                    count: mesh_lod.element_count(),
+                    prim_count: view_count,
+                    first_index: mesh_lod.element_offset(),
                     base_vertex: mesh_description.vertex_offset,
                     base_instance: instance_index as u32,
                 };
@@ -764,6 +1037,81 @@ impl DrawResources {
             }
 
             profiling_context.stop(gl, profiler_index);
+
+            cull_stats
+        }
+    }
+
+    /// Prepares the buffers for the culling compute shader. Every material
+    /// gets a range of commands large enough for all of its instances. The
+    /// shader compacts the visible instances to the front of each range and
+    /// counts them. Without indirect draw counts the zeroed remainder of the
+    /// range is drawn as well, which draws nothing.
+    pub fn reset_draw_commands(
+        &mut self,
+        gl: &gl::Gl,
+        material_instance_counts: &[usize],
+        material_instance_offsets: &[usize],
+    ) {
+        self.draw_counts_on_gpu = self.supports_indirect_draw_count;
+
+        clear_and_reserve(&mut self.draw_counts, material_instance_counts.len());
+        self.draw_counts.extend(material_instance_counts.iter().copied());
+
+        clear_and_reserve(&mut self.draw_offsets, material_instance_offsets.len());
+        self.draw_offsets.extend(material_instance_offsets.iter().copied());
+
+        clear_and_reserve(&mut self.draw_range_data, material_instance_offsets.len());
+        self.draw_range_data
+            .extend(material_instance_offsets.iter().map(|&offset| [offset as u32, 0]));
+
+        let command_count: usize = material_instance_counts.iter().sum();
+        let byte_count = command_count * std::mem::size_of::<DrawCommand>();
+
+        unsafe {
+            gl.named_buffer_reserve(self.draw_command_buffer, byte_count, gl::DYNAMIC_DRAW);
+            gl.clear_named_buffer_sub_data(
+                self.draw_command_buffer,
+                gl::R32UI,
+                0,
+                byte_count,
+                gl::RED,
+                gl::UNSIGNED_INT,
+                None,
+            );
+
+            gl.named_buffer_data(
+                self.draw_range_buffer,
+                self.draw_range_data.vec_as_bytes(),
+                gl::DYNAMIC_DRAW,
+            );
+        }
+    }
+
+    /// Draws the commands of a material from the bound draw indirect buffer.
+    /// `draw_counts` is the upper bound of the count on the GPU.
+    pub unsafe fn multi_draw(&self, gl: &gl::Gl, material_index: usize) {
+        let command_offset = self.draw_offsets[material_index] * std::mem::size_of::<DrawCommand>();
+        let stride = std::mem::size_of::<DrawCommand>() as i32;
+        if self.draw_counts_on_gpu {
+            gl.bind_buffer(gl::PARAMETER_BUFFER, self.draw_range_buffer);
+            gl.multi_draw_elements_indirect_count(
+                gl::TRIANGLES,
+                gl::UNSIGNED_INT,
+                command_offset,
+                // The count is the second element of the material's range.
+                (2 * material_index + 1) * std::mem::size_of::<u32>(),
+                self.draw_counts[material_index] as i32,
+                stride,
+            );
+        } else {
+            gl.multi_draw_elements_indirect(
+                gl::TRIANGLES,
+                gl::UNSIGNED_INT,
+                command_offset,
+                self.draw_counts[material_index] as i32,
+                stride,
+            );
         }
     }
 }
@@ -0,0 +1,79 @@
+use crate::*;
+
+/// Number of frames displayed in a graph.
+pub const FRAMES: usize = 120;
+
+/// Width of a graph in pixels.
+const WIDTH: f32 = 240.0;
+
+/// Graphs are scaled to their largest sample, but never below 0.1ms so that
+/// noise in cheap samples doesn't look like a spike.
+const MIN_SCALE: u64 = 100_000;
+
+/// Space between the end of the longest text row and the graphs in pixels.
+const MARGIN: f32 = 16.0;
+
+/// Line strips for the CPU and GPU time graphs of the overlay in pixels.
+#[derive(Default)]
+pub struct SampleGraphs {
+    pub vertices: Vec<[f32; 3]>,
+    pub cpu_indices: Vec<[u32; 2]>,
+    pub gpu_indices: Vec<[u32; 2]>,
+    /// The right end of the longest text row the graphs belong to.
+    pub text_right: f32,
+}
+
+impl SampleGraphs {
+    /// Adds the graphs for a text row with the given top and right end. The
+    /// graphs are laid out from x = 0, see `graphs_to_pix`.
+    pub fn push(&mut self, history: impl Iterator<Item = Option<GpuCpuTimeSpan>>, top: f32, right: f32, height: f32) {
+        self.text_right = self.text_right.max(right);
+
+        let history: Vec<Option<GpuCpuTimeSpan>> = history.collect();
+
+        let scale = history
+            .iter()
+            .flatten()
+            .map(|span| std::cmp::max(span.cpu.delta(), span.gpu.delta()))
+            .max()
+            .unwrap_or(0)
+            .max(MIN_SCALE) as f32;
+
+        let step = WIDTH / FRAMES as f32;
+        let x0 = WIDTH - step * history.len() as f32;
+        // Keep a pixel between the graphs of adjacent rows.
+        let y0 = top - height + 1.0;
+        let h = height - 2.0;
+
+        for &gpu in [false, true].iter() {
+            let mut previous: Option<u32> = None;
+            for (i, span) in history.iter().enumerate() {
+                let span = match *span {
+                    Some(span) => span,
+                    None => {
+                        // Missing samples break the line.
+                        previous = None;
+                        continue;
+                    }
+                };
+                let delta = if gpu { span.gpu.delta() } else { span.cpu.delta() };
+                let index = self.vertices.len() as u32;
+                self.vertices
+                    .push([x0 + step * i as f32, y0 + h * delta as f32 / scale, 0.0]);
+                if let Some(previous) = previous {
+                    if gpu {
+                        self.gpu_indices.push([previous, index]);
+                    } else {
+                        self.cpu_indices.push([previous, index]);
+                    }
+                }
+                previous = Some(index);
+            }
+        }
+    }
+
+    /// Moves the graphs to the right of all text rows so they never overlap.
+    pub fn graphs_to_pix(&self) -> Matrix4<f64> {
+        Matrix4::from_translation(Vector3::new(f64::from(self.text_right + MARGIN), 0.0, 0.0))
+    }
+}
@@ -85,6 +85,10 @@ pub struct MeshDescription {
     pub vertex_count: u32,
     pub bounding_box: Box3<f32>,
     pub bounding_sphere: Sphere3<f32>,
+    /// Range into `SceneFile::mesh_lods`. The first level of detail is the
+    /// full mesh, every next one has about half the triangles.
+    pub lod_offset: u32,
+    pub lod_count: u32,
 }
 
 impl MeshDescription {
@@ -98,6 +102,28 @@ impl MeshDescription {
     pub fn element_count(&self) -> u32 {
         self.triangle_count * 3
     }
+
+    pub fn lods<'a>(&self, mesh_lods: &'a [MeshLod]) -> &'a [MeshLod] {
+        &mesh_lods[self.lod_offset as usize..(self.lod_offset + self.lod_count) as usize]
+    }
+}
+
+/// A triangle range that indexes the vertices of its mesh.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct MeshLod {
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+}
+
+impl MeshLod {
+    pub fn element_offset(&self) -> u32 {
+        self.triangle_offset * 3
+    }
+
+    pub fn element_count(&self) -> u32 {
+        self.triangle_count * 3
+    }
 }
 
 #[derive(Debug)]
@@ -185,6 +211,14 @@ pub struct RawMaterial {
     pub opacity: f32,
     pub masked: bool,
     pub transparent: bool,
+    /// Used when there is no metallic roughness texture.
+    pub metallic: f32,
+    /// Used when there is no metallic roughness texture.
+    pub roughness: f32,
+    /// Stores roughness in the green and metalness in the blue channel.
+    pub metallic_roughness_texture_index: Option<NonMaxU32>,
+    /// Stores ambient occlusion in the red channel.
+    pub occlusion_texture_index: Option<NonMaxU32>,
 }
 
 #[derive(Debug)]
@@ -199,10 +233,23 @@ pub struct Texture {
     pub file_path: PathBuf,
 }
 
+/// Files without the magic were written before levels of detail were added.
+pub const SCENE_FILE_MAGIC: [u8; 8] = *b"SCENEBIN";
+
+/// The layout is the memory representation of the types in this module, bump
+/// the version whenever one of them changes.
+///
+/// Version 1 added the magic, the version and mesh levels of detail. Version 2
+/// added the metallic roughness parameters and textures of materials.
+pub const SCENE_FILE_VERSION: u64 = 2;
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct FileHeader {
+    pub magic: [u8; 8],
+    pub version: u64,
     pub mesh_count: u64,
+    pub mesh_lod_count: u64,
     pub vertex_count: u64,
     pub triangle_count: u64,
     pub transform_count: u64,
@@ -218,6 +265,7 @@ type Triangle = [u32; 3];
 #[derive(Debug)]
 pub struct SceneFile {
     pub mesh_descriptions: Vec<MeshDescription>,
+    pub mesh_lods: Vec<MeshLod>,
     pub pos_in_obj_buffer: Vec<[FiniteF32; 3]>,
     pub nor_in_obj_buffer: Vec<[FiniteF32; 3]>,
     pub bin_in_obj_buffer: Vec<[FiniteF32; 3]>,
@@ -275,7 +323,10 @@ impl SceneFile {
             .collect();
 
         let header = FileHeader {
+            magic: SCENE_FILE_MAGIC,
+            version: SCENE_FILE_VERSION,
             mesh_count: self.mesh_descriptions.len() as u64,
+            mesh_lod_count: self.mesh_lods.len() as u64,
             vertex_count: vertex_count as u64,
             triangle_count: self.triangle_buffer.len() as u64,
             transform_count: self.transforms.len() as u64,
@@ -292,6 +343,7 @@ impl SceneFile {
                 std::mem::size_of::<FileHeader>(),
             ))?;
             write_vec(&self.mesh_descriptions, writer)?;
+            write_vec(&self.mesh_lods, writer)?;
             write_vec(&self.pos_in_obj_buffer, writer)?;
             write_vec(&self.nor_in_obj_buffer, writer)?;
             write_vec(&self.bin_in_obj_buffer, writer)?;
@@ -320,7 +372,25 @@ impl SceneFile {
 
             let header = header.assume_init();
 
+            if header.magic != SCENE_FILE_MAGIC || header.version != SCENE_FILE_VERSION {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    if header.magic != SCENE_FILE_MAGIC {
+                        format!(
+                            "The scene file has no header, it predates version {}. Convert the scene again.",
+                            SCENE_FILE_VERSION
+                        )
+                    } else {
+                        format!(
+                            "The scene file has version {} but version {} is required. Convert the scene again.",
+                            header.version, SCENE_FILE_VERSION
+                        )
+                    },
+                ));
+            }
+
             let mesh_descriptions = read_vec::<MeshDescription, _>(header.mesh_count as usize, reader)?;
+            let mesh_lods = read_vec::<MeshLod, _>(header.mesh_lod_count as usize, reader)?;
             let pos_in_obj_buffer = read_vec::<[FiniteF32; 3], _>(header.vertex_count as usize, reader)?;
             let nor_in_obj_buffer = read_vec::<[FiniteF32; 3], _>(header.vertex_count as usize, reader)?;
             let bin_in_obj_buffer = read_vec::<[FiniteF32; 3], _>(header.vertex_count as usize, reader)?;
@@ -348,6 +418,7 @@ impl SceneFile {
 
             Ok(SceneFile {
                 mesh_descriptions,
+                mesh_lods,
                 pos_in_obj_buffer,
                 nor_in_obj_buffer,
                 bin_in_obj_buffer,
@@ -1,7 +1,7 @@
 use crate::*;
 
 use incremental::{Current, LastComputed, LastModified, LastVerified};
-use renderer::configuration::ClusteringProjection;
+use renderer::configuration::{ClusteringProjection, Tonemapper};
 use renderer::*;
 
 use std::collections::HashMap;
@@ -88,6 +88,7 @@ pub enum SourceReader {
     Profiling,
     SampleCount,
     DepthPrepass,
+    Tonemapper,
 }
 
 impl SourceReader {
@@ -177,6 +178,7 @@ impl SourceReader {
                 let clustering_projection = match vars.clustered_light_shading.projection {
                     ClusteringProjection::Orthographic => "CLUSTERING_PROJECTION_ORTHOGRAPHIC",
                     ClusteringProjection::Perspective => "CLUSTERING_PROJECTION_PERSPECTIVE",
+                    ClusteringProjection::Logarithmic => "CLUSTERING_PROJECTION_LOGARITHMIC",
                 };
 
                 tokens.push(Token::Literal(format!(
@@ -184,13 +186,14 @@ impl SourceReader {
                      #line {} {}\n\
                      #define CLUSTERING_PROJECTION_ORTHOGRAPHIC 1\n\
                      #define CLUSTERING_PROJECTION_PERSPECTIVE 2\n\
+                     #define CLUSTERING_PROJECTION_LOGARITHMIC 3\n\
                      #define CLUSTERING_PROJECTION {}\n\
                      \n\
                      #define CLUSTERED_LIGHT_SHADING_MAX_CLUSTERS {}\n\
                      #define CLUSTERED_LIGHT_SHADING_MAX_ACTIVE_CLUSTERS {}\n\
                      #define CLUSTERED_LIGHT_SHADING_MAX_LIGHT_INDICES {}\n\
                      ",
-                    line!() - 7,
+                    line!() - 8,
                     source_index,
                     clustering_projection,
                     vars.clustered_light_shading.max_clusters,
@@ -203,13 +206,15 @@ impl SourceReader {
                     "\
                      #line {} {}\n\
                      #define PROFILING_TIME_SENSITIVE {}\n\
+                     #define PROFILING_HISTOGRAM_BIN_COUNT {}\n\
                      ",
-                    line!() - 2,
+                    line!() - 3,
                     source_index,
                     match vars.profiling.time_sensitive {
                         true => 1,
                         false => 0,
-                    }
+                    },
+                    vars.profiling.histogram_bin_count,
                 )));
             }
             SourceReader::SampleCount => {
@@ -237,6 +242,23 @@ impl SourceReader {
                     }
                 )));
             }
+            SourceReader::Tonemapper => {
+                let define = match vars.tonemapper {
+                    Tonemapper::Aces => "TONEMAPPER_ACES",
+                    Tonemapper::Reinhard => "TONEMAPPER_REINHARD",
+                    Tonemapper::Uncharted2 => "TONEMAPPER_UNCHARTED_2",
+                };
+
+                tokens.push(Token::Literal(format!(
+                    "\
+                     #line {line} {source_index}\n\
+                     #define {define}\n\
+                     ",
+                    line = line!() - 2,
+                    source_index = source_index,
+                    define = define,
+                )));
+            }
         }
     }
 }
@@ -368,7 +390,14 @@ impl EntryPoint {
             self.contents.clear();
             self.included.clear();
 
+            let span = context.shader_compiler.profiler.start_with(|| {
+                format!(
+                    "preprocess {}",
+                    context.shader_compiler.memory.sources[self.source_index].name.display()
+                )
+            });
             process(self, context, self.source_index);
+            context.shader_compiler.profiler.stop(span);
 
             true
         } else {
@@ -459,11 +488,13 @@ pub struct Variables {
     pub profiling: ProfilingVariables,
     pub sample_count: u32,
     pub depth_prepass: bool,
+    pub tonemapper: Tonemapper,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct ProfilingVariables {
     pub time_sensitive: bool,
+    pub histogram_bin_count: u32,
 }
 
 pub struct NativeSourceIndices {
@@ -475,6 +506,7 @@ pub struct NativeSourceIndices {
     pub profiling: SourceIndex,
     pub sample_count: SourceIndex,
     pub depth_prepass: SourceIndex,
+    pub tonemapper: SourceIndex,
 }
 
 pub struct ShaderCompilationContext<'a> {
@@ -489,10 +521,11 @@ pub struct ShaderCompiler {
     pub parser: Parser,
     pub variables: Variables,
     pub indices: NativeSourceIndices,
+    pub profiler: profiling::ThreadProfiler,
 }
 
 impl ShaderCompiler {
-    pub fn new(current: &Current, variables: Variables) -> Self {
+    pub fn new(current: &Current, variables: Variables, profiler: profiling::ThreadProfiler) -> Self {
         let parser = Parser::new();
         let mut memory = Memory::new();
         let indices = NativeSourceIndices {
@@ -560,6 +593,14 @@ impl ShaderCompiler {
                     PathBuf::from(concat!(file!(), "DEPTH_PREPASS")),
                 ),
             ),
+            tonemapper: memory.add_source(
+                PathBuf::from("native/TONEMAPPER"),
+                Source::new(
+                    current,
+                    SourceReader::Tonemapper,
+                    PathBuf::from(concat!(file!(), "TONEMAPPER")),
+                ),
+            ),
         };
 
         Self {
@@ -568,6 +609,7 @@ impl ShaderCompiler {
             parser,
             variables,
             indices,
+            profiler,
         }
     }
 
@@ -687,4 +729,16 @@ impl ShaderCompiler {
         }
         std::mem::replace(&mut self.variables.depth_prepass, value)
     }
+
+    pub fn tonemapper(&self) -> Tonemapper {
+        self.variables.tonemapper
+    }
+
+    pub fn replace_tonemapper(&mut self, current: &mut Current, tonemapper: Tonemapper) -> Tonemapper {
+        let old = std::mem::replace(&mut self.variables.tonemapper, tonemapper);
+        if old != tonemapper {
+            self.source_mut(self.indices.tonemapper).last_modified.modify(current);
+        }
+        old
+    }
 }
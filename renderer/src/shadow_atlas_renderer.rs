@@ -0,0 +1,165 @@
+use crate::*;
+
+pub struct Renderer {
+    pub opaque_program: rendering::Program,
+    pub masked_program: rendering::Program,
+}
+
+glsl_defines!(fixed_header {
+    bindings: {
+        INSTANCE_MATRICES_BUFFER_BINDING = 10;
+
+        DIFFUSE_SAMPLER_BINDING = 4;
+    },
+    uniforms: {
+        WLD_TO_CLP_ARRAY_LOC = 0;
+        // WLD_TO_CLP_ARRAY_LOC = 1;
+        // WLD_TO_CLP_ARRAY_LOC = 2;
+        // WLD_TO_CLP_ARRAY_LOC = 3;
+        // WLD_TO_CLP_ARRAY_LOC = 4;
+        // WLD_TO_CLP_ARRAY_LOC = 5;
+        SHADOW_INDEX_LOC = 6;
+        LIGHT_POS_IN_LGT_LOC = 7;
+    },
+});
+
+pub struct Parameters {
+    pub draw_resources_index: usize,
+    pub light_index: usize,
+    pub shadow_index: u32,
+}
+
+impl Context<'_> {
+    /// Renders the distance cube map of a single light into its tile of the
+    /// shadow atlas. Expects the shadow atlas framebuffer to be bound.
+    pub fn render_shadow_atlas_tile(&mut self, params: Parameters) {
+        let Context {
+            ref gl,
+            ref resources,
+            shadow_atlas_renderer: ref mut renderer,
+            ..
+        } = *self;
+
+        let draw_resources = &self.resources.draw_resources_pool[params.draw_resources_index];
+
+        unsafe {
+            renderer.opaque_program.update(&mut rendering_context!(self));
+            renderer.masked_program.update(&mut rendering_context!(self));
+            if let (&ProgramName::Linked(opaque_program), &ProgramName::Linked(masked_program)) =
+                (&renderer.opaque_program.name, &renderer.masked_program.name)
+            {
+                gl.bind_buffer_base(
+                    gl::SHADER_STORAGE_BUFFER,
+                    INSTANCE_MATRICES_BUFFER_BINDING,
+                    draw_resources.instance_matrices_buffer,
+                );
+
+                gl.bind_buffer(gl::DRAW_INDIRECT_BUFFER, draw_resources.draw_command_buffer);
+
+                gl.bind_vertex_array(resources.scene_vao);
+
+                let light = &self.light_resources.body[params.light_index];
+                let position = Point3::from(light.position);
+
+                let wld_to_clp_array = light_depth_renderer::cube_map_wld_to_clp_array(
+                    position,
+                    light::AttenCoefs {
+                        r1: light.radius(),
+                        ..light.attenuation
+                    },
+                );
+
+                for &(program, material_kind) in [
+                    (opaque_program, resources::MaterialKind::Opaque),
+                    (masked_program, resources::MaterialKind::Masked),
+                ]
+                .iter()
+                {
+                    gl.use_program(program);
+
+                    gl.uniform_matrix4fv(WLD_TO_CLP_ARRAY_LOC, gl::MajorAxis::Column, &wld_to_clp_array);
+                    gl.uniform_1ui(SHADOW_INDEX_LOC, params.shadow_index);
+                    gl.uniform_3f(LIGHT_POS_IN_LGT_LOC, position.into());
+
+                    for (material_index, material) in self
+                        .resources
+                        .materials
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, material)| material.kind == material_kind)
+                    {
+                        let textures = &self.resources.textures;
+                        gl.bind_texture_unit(DIFFUSE_SAMPLER_BINDING, textures[material.diffuse_texture_index].name);
+
+                        draw_resources.multi_draw(gl, material_index);
+                    }
+                }
+
+                gl.unuse_program();
+
+                gl.unbind_vertex_array();
+            }
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new(context: &mut RenderingContext) -> Self {
+        fn create_header(kind: resources::MaterialKind) -> String {
+            format!(
+                "\
+                #define BASIC_PASS_OPAQUE 1\n\
+                #define BASIC_PASS_MASKED 2\n\
+                #define BASIC_PASS {}\n\
+                ",
+                match kind {
+                    resources::MaterialKind::Opaque => "BASIC_PASS_OPAQUE",
+                    resources::MaterialKind::Masked => "BASIC_PASS_MASKED",
+                    resources::MaterialKind::Transparent => panic!("Transparent materials do not cast shadows."),
+                }
+            )
+        }
+
+        let mut create_program = |kind: resources::MaterialKind| -> rendering::Program {
+            let header = format!("{}{}", fixed_header(), create_header(kind));
+
+            rendering::Program::new(
+                context.gl,
+                vec![
+                    Shader::new(
+                        context.gl,
+                        gl::VERTEX_SHADER,
+                        EntryPoint::new(
+                            &mut shader_compilation_context!(context),
+                            "light_depth_renderer.vert",
+                            header.clone(),
+                        ),
+                    ),
+                    Shader::new(
+                        context.gl,
+                        gl::GEOMETRY_SHADER,
+                        EntryPoint::new(
+                            &mut shader_compilation_context!(context),
+                            "shadow_atlas_renderer.geom",
+                            header.clone(),
+                        ),
+                    ),
+                    Shader::new(
+                        context.gl,
+                        gl::FRAGMENT_SHADER,
+                        EntryPoint::new(
+                            &mut shader_compilation_context!(context),
+                            "shadow_atlas_renderer.frag",
+                            header,
+                        ),
+                    ),
+                ],
+            )
+        };
+
+        Renderer {
+            opaque_program: create_program(resources::MaterialKind::Opaque),
+            masked_program: create_program(resources::MaterialKind::Masked),
+        }
+    }
+}
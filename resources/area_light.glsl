@@ -0,0 +1,28 @@
+// Representative point shading for area lights, see "Real Shading in Unreal
+// Engine 4" (Karis 2013). Returns the point on the light closest to the
+// reflection ray R from frag_pos. Energy normalization is not applied.
+vec3 light_representative_point(PointLight light, vec3 frag_pos, vec3 R) {
+  if (light.kind == LIGHT_KIND_TUBE) {
+    vec3 l0 = light.position - light.axis_0 - frag_pos;
+    vec3 l1 = light.position + light.axis_0 - frag_pos;
+    vec3 ld = l1 - l0;
+    float r_ld = dot(R, ld);
+    float t = (dot(R, l0) * r_ld - dot(l0, ld)) / max(dot(ld, ld) - r_ld * r_ld, 0.0001);
+    return frag_pos + l0 + clamp(t, 0.0, 1.0) * ld;
+  }
+
+  if (light.kind == LIGHT_KIND_RECTANGLE) {
+    vec3 n = light.direction;
+    float r_n = dot(R, n);
+    float d = dot(light.position - frag_pos, n);
+    // Intersect the reflection ray with the plane of the rectangle or fall
+    // back to the closest point on the plane.
+    vec3 p = (r_n < -0.0001 && d < 0.0) ? frag_pos + R * (d / r_n) : frag_pos + n * d;
+    vec3 c_to_p = p - light.position;
+    float u = clamp(dot(c_to_p, light.axis_0) / dot(light.axis_0, light.axis_0), -1.0, 1.0);
+    float v = clamp(dot(c_to_p, light.axis_1) / dot(light.axis_1, light.axis_1), -1.0, 1.0);
+    return light.position + u * light.axis_0 + v * light.axis_1;
+  }
+
+  return light.position;
+}
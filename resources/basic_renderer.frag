@@ -22,11 +22,20 @@
 #error DEPTH_PREPASS is not defined.
 #endif
 
+#if !defined(TRANSPARENCY_MODE)
+#error TRANSPARENCY_MODE is not defined.
+#endif
+
 #include "common.glsl"
 #include "heatmap.glsl"
 #include "light_buffer.glsl"
 #include "point_light_attenuate.glsl"
 #include "pbr.glsl"
+#include "area_light.glsl"
+#include "shadow_atlas.glsl"
+#include "ibl.glsl"
+#include "camera_buffer.glsl"
+#include "cotangent_frame.glsl"
 
 #if defined(RENDER_TECHNIQUE_CLUSTERED)
 #include "cls/cluster_space_buffer.glsl"
@@ -67,16 +76,16 @@ layout(binding = EMISSIVE_SAMPLER_BINDING) uniform sampler2D emissive_sampler;
 layout(binding = AMBIENT_SAMPLER_BINDING) uniform sampler2D ambient_sampler;
 layout(binding = DIFFUSE_SAMPLER_BINDING) uniform sampler2D diffuse_sampler;
 layout(binding = SPECULAR_SAMPLER_BINDING) uniform sampler2D specular_sampler;
+layout(binding = METALLIC_ROUGHNESS_SAMPLER_BINDING) uniform sampler2D metallic_roughness_sampler;
+layout(binding = OCCLUSION_SAMPLER_BINDING) uniform sampler2D occlusion_sampler;
 // layout(binding = SHADOW_SAMPLER_BINDING) uniform samplerCubeShadow shadow_sampler;
 layout(binding = SHADOW_SAMPLER_BINDING) uniform samplerCube shadow_sampler;
 layout(binding = SHADOW_SAMPLER_BINDING_2) uniform samplerCube shadow_sampler_2;
 layout(binding = SHADOW_SAMPLER_BINDING_3) uniform samplerCube shadow_sampler_3;
 
-layout(location = CAM_POS_IN_LGT_LOC) uniform vec3 cam_pos_in_lgt;
-
 #if defined(RENDER_TECHNIQUE_CLUSTERED)
 layout(location = VIEWPORT_LOC) uniform vec4 viewport;
-layout(location = REN_CLP_TO_CLU_CAM_LOC) uniform mat4 ren_clp_to_clu_cam;
+layout(location = REN_CLP_TO_CLU_CAM_LOC) uniform mat4 ren_clp_to_clu_cam[2];
 #endif
 
 in vec3 fs_pos_in_lgt;
@@ -84,8 +93,35 @@ in vec3 fs_nor_in_lgt;
 in vec3 fs_bin_in_lgt;
 in vec3 fs_tan_in_lgt;
 in vec2 fs_pos_in_tex;
+flat in uint fs_view_index;
 
+vec3 cam_pos_in_lgt() {
+  return camera_buffer.cam_pos_in_lgt[fs_view_index].xyz;
+}
+
+#if BASIC_PASS == BASIC_PASS_TRANSPARENT && TRANSPARENCY_MODE == TRANSPARENCY_MODE_WEIGHTED_BLENDED
+layout(location = 0) out vec4 frag_accum;
+layout(location = 1) out float frag_revealage;
+#elif BASIC_PASS == BASIC_PASS_TRANSPARENT && TRANSPARENCY_MODE == TRANSPARENCY_MODE_LINKED_LIST
+#include "oit_linked_list.glsl"
+#else
 layout(location = 0) out vec4 frag_color;
+#endif
+
+void write_frag_color(vec4 color) {
+#if BASIC_PASS == BASIC_PASS_TRANSPARENT && TRANSPARENCY_MODE == TRANSPARENCY_MODE_WEIGHTED_BLENDED
+  // Depth weight from equation 7 of McGuire and Bavoil 2013, using the
+  // distance to the camera.
+  float d = distance(cam_pos_in_lgt(), fs_pos_in_lgt);
+  float w = color.a * clamp(10.0 / (1e-5 + pow(d / 5.0, 2.0) + pow(d / 200.0, 6.0)), 1e-2, 3e3);
+  frag_accum = vec4(color.rgb * color.a, color.a) * w;
+  frag_revealage = -log(1.0 - min(color.a, 0.999));
+#elif BASIC_PASS == BASIC_PASS_TRANSPARENT && TRANSPARENCY_MODE == TRANSPARENCY_MODE_LINKED_LIST
+  oit_push_fragment(color, gl_FragCoord.z);
+#else
+  frag_color = color;
+#endif
+}
 
 vec3 sample_nor_in_tan(vec2 pos_in_tex) {
   vec2 xy = texture(normal_sampler, pos_in_tex).xy * 2.0 - vec2(1.0);
@@ -96,8 +132,6 @@ vec3 sample_nor_in_tan(vec2 pos_in_tex) {
 void main() {
   vec3 frag_pos_in_lgt = fs_pos_in_lgt;
   vec3 frag_geo_nor_in_lgt = normalize(fs_nor_in_lgt);
-  vec3 frag_geo_bin_in_lgt = normalize(fs_bin_in_lgt);
-  vec3 frag_geo_tan_in_lgt = normalize(fs_tan_in_lgt);
   vec2 frag_pos_in_tex = fs_pos_in_tex;
   vec3 frag_nor_in_tan = sample_nor_in_tan(frag_pos_in_tex);
 
@@ -105,6 +139,16 @@ void main() {
   vec4 ke = texture(emissive_sampler, frag_pos_in_tex);
   vec4 kd = texture(diffuse_sampler, frag_pos_in_tex);
   vec4 ks = texture(specular_sampler, frag_pos_in_tex);
+  vec2 metallic_roughness = texture(metallic_roughness_sampler, frag_pos_in_tex).bg;
+  float metalness = metallic_roughness.x;
+  float roughness = metallic_roughness.y;
+  float occlusion = texture(occlusion_sampler, frag_pos_in_tex).r;
+
+  // Meshes without tangents have zero tangent and binormal attributes, derive
+  // the frame from the screen space derivatives instead. The derivatives
+  // require uniform control flow, so they are taken before any discard and
+  // outside of the branch that selects the frame.
+  mat3 derived_tbn = cotangent_frame(frag_geo_nor_in_lgt, frag_pos_in_lgt, frag_pos_in_tex);
 
 #if BASIC_PASS == BASIC_PASS_MASKED
   if (kd.a < 0.5) {
@@ -125,21 +169,37 @@ void main() {
   // actually somewhat transparent. Asset problem.
   kd.a *= 0.95;
 
-  mat3 tbn = mat3(frag_geo_tan_in_lgt, frag_geo_bin_in_lgt, frag_geo_nor_in_lgt);
+  bool has_tangents = dot(fs_tan_in_lgt, fs_tan_in_lgt) > 0.0 && dot(fs_bin_in_lgt, fs_bin_in_lgt) > 0.0;
+  mat3 tbn = has_tangents
+    ? mat3(normalize(fs_tan_in_lgt), normalize(fs_bin_in_lgt), frag_geo_nor_in_lgt)
+    : derived_tbn;
   vec3 frag_nor_in_lgt = normalize(tbn * frag_nor_in_tan);
-  vec3 frag_to_cam_nor = normalize(cam_pos_in_lgt - frag_pos_in_lgt);
-
-  vec3 color_accumulator = vec3(ke.xyz);
+  vec3 frag_to_cam_nor = normalize(cam_pos_in_lgt() - frag_pos_in_lgt);
+  vec3 frag_reflect_nor = reflect(-frag_to_cam_nor, frag_nor_in_lgt);
+
+  // The ambient color stands in for the environment when image based lighting
+  // is disabled. The environment textures are undefined until they have been
+  // generated so they must not be sampled at all, multiplying by a zero
+  // intensity would keep NaNs.
+  vec3 indirect;
+  if (ibl_intensity > 0.0) {
+    indirect = ibl_radiance(frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.xyz, roughness, metalness);
+  } else {
+    indirect = ka.xyz * kd.xyz;
+  }
+  vec3 color_accumulator = ke.xyz + occlusion * indirect;
 #if defined(RENDER_TECHNIQUE_NAIVE)
   for (uint i = 1; i < light_buffer.light_count.x; i += 1) {
     PointLight light = light_buffer.point_lights[i];
-    vec3 f_to_l = light.position - frag_pos_in_lgt;
+    vec3 f_to_l = light_representative_point(light, frag_pos_in_lgt, frag_reflect_nor) - frag_pos_in_lgt;
     float f_to_l_mag = length(f_to_l);
 
     color_accumulator +=
       point_light_attenuate(light.i, light.i0, light.r0, light.r1, f_to_l_mag) *
+      spot_light_cone(f_to_l/-f_to_l_mag, light.direction, light.cos_inner, light.cos_outer) *
       light.tint *
-      cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.y, ks.z);
+      light_shadow(light, frag_pos_in_lgt, frag_geo_nor_in_lgt) *
+      cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.xyz, roughness, metalness);
 
 #if !PROFILING_TIME_SENSITIVE
     atomicCounterIncrement(lighting_ops);
@@ -157,7 +217,7 @@ void main() {
   //   cook_torrance(l_to_f/(-l_to_f_mag), frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.y, ks.z);
   // }
 #elif defined(RENDER_TECHNIQUE_CLUSTERED)
-  vec3 frag_pos_in_clu_cam = from_homogeneous(ren_clp_to_clu_cam * vec4(
+  vec3 frag_pos_in_clu_cam = from_homogeneous(ren_clp_to_clu_cam[fs_view_index] * vec4(
     gl_FragCoord.xy/viewport.zw * 2.0 - 1.0,
     gl_FragCoord.z,
     1.0
@@ -202,7 +262,7 @@ void main() {
 
   if (maybe_active_cluster_index == 0) {
     // We generally shouldn't see clusters that don't have any fragments.
-    frag_color = vec4(1.0, 0.0, 1.0, 1.0);
+    write_frag_color(vec4(1.0, 0.0, 1.0, 1.0));
     return;
   }
 
@@ -243,7 +303,7 @@ void main() {
     uint light_index = light_indices[cluster_light_offset + i];
 
     PointLight light = light_buffer.point_lights[light_index];
-    vec3 f_to_l = light.position - frag_pos_in_lgt;
+    vec3 f_to_l = light_representative_point(light, frag_pos_in_lgt, frag_reflect_nor) - frag_pos_in_lgt;
     float f_to_l_mag = length(f_to_l);
 
     // if (light_index == 0) {
@@ -255,13 +315,15 @@ void main() {
     //   color_accumulator +=
     //     min(light.i, point_light_attenuate(light.i, light.i0, light.r0, light.r1, f_to_l_mag)) *
     //     light.tint *
-    //     cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, roughness, metalness);
+    //     cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.xyz, roughness, metalness);
 
     // } else {
       color_accumulator +=
         point_light_attenuate(light.i, light.i0, light.r0, light.r1, f_to_l_mag) *
+        spot_light_cone(f_to_l/-f_to_l_mag, light.direction, light.cos_inner, light.cos_outer) *
         light.tint *
-        cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.y, ks.z);
+        light_shadow(light, frag_pos_in_lgt, frag_geo_nor_in_lgt) *
+        cook_torrance(f_to_l/f_to_l_mag, frag_nor_in_lgt, frag_to_cam_nor, kd.xyz, ks.xyz, roughness, metalness);
     // }
 
 #if !PROFILING_TIME_SENSITIVE
@@ -306,8 +368,8 @@ void main() {
 #endif
 
 #if BASIC_PASS == BASIC_PASS_TRANSPARENT
-  frag_color = vec4(color_accumulator, kd.a);
+  write_frag_color(vec4(color_accumulator, kd.a));
 #else
-  frag_color = vec4(color_accumulator, 1.0);
+  write_frag_color(vec4(color_accumulator, 1.0));
 #endif
 }
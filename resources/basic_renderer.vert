@@ -3,6 +3,7 @@
 #include "common.glsl"
 #include "light_buffer.glsl"
 #include "instance_matrices_buffer.glsl"
+#include "stereo_view.glsl"
 #if defined(RENDER_TECHNIQUE_CLUSTERED)
 #include "cls/cluster_space_buffer.glsl"
 #endif
@@ -21,16 +22,20 @@ out vec3 fs_nor_in_lgt;
 out vec3 fs_bin_in_lgt;
 out vec3 fs_tan_in_lgt;
 out vec2 fs_pos_in_tex;
+flat out uint fs_view_index;
 
 void main() {
   InstanceMatrices m = instance_matrices_buffer[vs_instance_index];
 
+  uint view_index = stereo_view_index();
   vec4 pos_in_obj = to_homogeneous(vs_pos_in_obj);
-  gl_Position = m.obj_to_ren_clp * pos_in_obj;
+  gl_Position = m.obj_to_ren_clp[view_index] * pos_in_obj;
+  stereo_view_clip(view_index, gl_Position);
   fs_pos_in_lgt = mat4x3(m.obj_to_lgt) * pos_in_obj;
   fs_nor_in_lgt = normalize(mat3(m.obj_to_lgt_inv_tra) * vs_nor_in_obj);
   fs_bin_in_lgt = normalize(mat3(m.obj_to_lgt) * vs_bin_in_obj);
   fs_tan_in_lgt = normalize(mat3(m.obj_to_lgt) * vs_tan_in_obj);
   // NOTE(mickvangelderen): TOO LAZY TO CHANGE IMAGE ORIGIN.
   fs_pos_in_tex = vec2(vs_pos_in_tex.x, 1.0 - vs_pos_in_tex.y);
+  fs_view_index = view_index;
 }
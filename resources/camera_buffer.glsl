@@ -0,0 +1,8 @@
+// Matches `CameraBuffer` in `rendering.rs`, holds one camera per view.
+layout(std140, binding = CAMERA_BUFFER_BINDING) uniform CameraBuffer {
+  mat4 wld_to_cam[2];
+  mat4 cam_to_wld[2];
+  mat4 cam_to_clp[2];
+  mat4 clp_to_cam[2];
+  vec4 cam_pos_in_lgt[2];
+} camera_buffer;
@@ -23,9 +23,15 @@ layout(std140,
   float clp_to_cam_ay;
   float clp_to_cam_by;
   float clp_to_cam_az;
-  float clp_to_cam_bz; // add_d_1
+  float clp_to_cam_bz; // add_d_1 or ln(f/s)/(Z - 1)
   float _pad2;
   float _pad3;
+
+  // Buffer capacities, may grow at runtime.
+  uint max_active_clusters;
+  uint max_light_indices;
+  uint _pad4;
+  uint _pad5;
 }
 cluster_space;
 
@@ -45,6 +51,21 @@ vec3 cluster_cam_to_clp(vec3 pos_in_cam) {
   float y_cls = frac_1_neg_z_cam * (ay * pos_in_cam.y) + by;
   float z = pos_in_cam.z * az;
   float z_cls = float(cluster_space.dimensions.z) - (z < 0.0 ? (-1.0/0.0) : (log(z) * bz));
+#elif CLUSTERING_PROJECTION == CLUSTERING_PROJECTION_LOGARITHMIC
+  float frac_1_neg_z_cam = -1.0 / pos_in_cam.z;
+  float x_cls = frac_1_neg_z_cam * (ax * pos_in_cam.x) + bx;
+  float y_cls = frac_1_neg_z_cam * (ay * pos_in_cam.y) + by;
+  float z = pos_in_cam.z * az;
+  float z_max = float(cluster_space.dimensions.z);
+  float z_cls;
+  if (z < 1.0) {
+    // Linear slice between the near plane and the split.
+    float near = -cluster_space.frustum.z1;
+    float split = -1.0 / az;
+    z_cls = z_max - (-pos_in_cam.z - near) / (split - near);
+  } else {
+    z_cls = (z_max - 1.0) - log(z) * bz;
+  }
 #elif CLUSTERING_PROJECTION == CLUSTERING_PROJECTION_ORTHOGRAPHIC
   float x_cls = ax * pos_in_cam.x + bx;
   float y_cls = ay * pos_in_cam.y + by;
@@ -69,6 +90,19 @@ vec3 cluster_clp_to_cam(vec3 pos_in_clp) {
   float z_cam = az * pow(bz, float(cluster_space.dimensions.z) - pos_in_clp.z);
   float x_cam = -z_cam * (ax * pos_in_clp.x + bx);
   float y_cam = -z_cam * (ay * pos_in_clp.y + by);
+#elif CLUSTERING_PROJECTION == CLUSTERING_PROJECTION_LOGARITHMIC
+  float z_max = float(cluster_space.dimensions.z);
+  float z_cam;
+  if (pos_in_clp.z > z_max - 1.0) {
+    // Linear slice between the near plane and the split.
+    float near = -cluster_space.frustum.z1;
+    float split = -az;
+    z_cam = -(near + (z_max - pos_in_clp.z) * (split - near));
+  } else {
+    z_cam = az * exp((z_max - 1.0 - pos_in_clp.z) * bz);
+  }
+  float x_cam = -z_cam * (ax * pos_in_clp.x + bx);
+  float y_cam = -z_cam * (ay * pos_in_clp.y + by);
 #elif CLUSTERING_PROJECTION == CLUSTERING_PROJECTION_ORTHOGRAPHIC
   float x_cam = ax * pos_in_clp.x + bx;
   float y_cam = ay * pos_in_clp.y + by;
@@ -0,0 +1,20 @@
+// Conservative cone versus sphere test for lights with an outer angle of at
+// most 90 degrees. See https://bartwronski.com/2017/04/13/cull-that-cone/.
+bool cone_intersects_sphere(vec3 apex, vec3 direction, float cos_a, float range, vec3 center, float radius) {
+  vec3 v = center - apex;
+  float v_sq = dot(v, v);
+  float v1 = dot(v, direction);
+  float sin_a = sqrt(max(0.0, 1.0 - cos_a * cos_a));
+  float d = cos_a * sqrt(max(0.0, v_sq - v1 * v1)) - v1 * sin_a;
+  return !(d > radius || v1 > radius + range || v1 < -radius);
+}
+
+// Tests a spot light or one-sided area light against the bounding sphere of a
+// cluster AABB. Lights without a cone (or with a cone too wide to test) always
+// pass.
+bool cone_intersects_aabb(vec3 apex, vec4 dir_cos, float range, vec3 b0, vec3 b1) {
+  if (dir_cos.w < 0.0) {
+    return true;
+  }
+  return cone_intersects_sphere(apex, dir_cos.xyz, dir_cos.w, range, (b0 + b1) * 0.5, length(b1 - b0) * 0.5);
+}
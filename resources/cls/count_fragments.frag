@@ -54,8 +54,10 @@ layout(binding = DIFFUSE_SAMPLER_BINDING) uniform sampler2D diffuse_sampler;
 in vec2 fs_pos_in_tex;
 #endif
 
+flat in uint fs_view_index;
+
 layout(location = VIEWPORT_LOC) uniform vec4 viewport;
-layout(location = REN_CLP_TO_CLU_CAM_LOC) uniform mat4 ren_clp_to_clu_cam;
+layout(location = REN_CLP_TO_CLU_CAM_LOC) uniform mat4 ren_clp_to_clu_cam[2];
 
 // layout(location = 0) out vec4 frag_color;
 
@@ -71,7 +73,7 @@ void main() {
     discard;
   }
 #endif
-  vec3 frag_pos_in_clu_cam = from_homogeneous(ren_clp_to_clu_cam * vec4(
+  vec3 frag_pos_in_clu_cam = from_homogeneous(ren_clp_to_clu_cam[fs_view_index] * vec4(
     gl_FragCoord.xy/viewport.zw * 2.0 - 1.0,
     gl_FragCoord.z,
     1.0
@@ -1,6 +1,7 @@
 #include "../common.glsl"
 #include "../light_buffer.glsl"
 #include "../instance_matrices_buffer.glsl"
+#include "../stereo_view.glsl"
 #include "cluster_space_buffer.glsl"
 
 layout(location = VS_POS_IN_OBJ_LOC) in vec3 vs_pos_in_obj;
@@ -11,13 +12,18 @@ out vec2 fs_pos_in_tex;
 
 layout(location = VS_INSTANCE_INDEX_LOC) in uint vs_instance_index;
 
+flat out uint fs_view_index;
+
 invariant gl_Position;
 
 void main() {
   InstanceMatrices m = instance_matrices_buffer[vs_instance_index];
 
+  uint view_index = stereo_view_index();
   vec4 pos_in_obj = to_homogeneous(vs_pos_in_obj);
-  gl_Position = m.obj_to_ren_clp * pos_in_obj;
+  gl_Position = m.obj_to_ren_clp[view_index] * pos_in_obj;
+  stereo_view_clip(view_index, gl_Position);
+  fs_view_index = view_index;
 
 #if BASIC_PASS == BASIC_PASS_MASKED || BASIC_PASS == BASIC_PASS_TRANSPARENT
   fs_pos_in_tex = vec2(vs_pos_in_tex.x, 1.0 - vs_pos_in_tex.y);
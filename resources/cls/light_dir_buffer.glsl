@@ -0,0 +1,6 @@
+// Spot light directions in cluster camera space and the cosine of their outer
+// angle. Used during light counting and assignment.
+
+layout(std430, binding = LIGHT_DIR_BUFFER_BINDING) buffer LightDirBuffer {
+  vec4 light_dir[];
+};
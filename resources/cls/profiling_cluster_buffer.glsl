@@ -1,13 +1,19 @@
+#if !defined(PROFILING_HISTOGRAM_BIN_COUNT)
+  #error PROFILING_HISTOGRAM_BIN_COUNT is not defined.
+#endif
+
 struct ProfilingCluster {
   uint active_cluster_count;
   uint light_indices_count;
-  uint _pad[254];
-  uint fragments_per_cluster_hist[256];
-  uint lights_per_cluster_hist[256];
-  uint lights_per_fragment_hist[256];
+  // Number of items that did not fit in the buffers.
+  uint active_cluster_overflow;
+  uint light_indices_overflow;
+  uint _pad[252];
+  uint fragments_per_cluster_hist[PROFILING_HISTOGRAM_BIN_COUNT];
+  uint lights_per_cluster_hist[PROFILING_HISTOGRAM_BIN_COUNT];
+  uint lights_per_fragment_hist[PROFILING_HISTOGRAM_BIN_COUNT];
 };
 
 layout(std430, binding = PROFILING_CLUSTER_BUFFER_BINDING) buffer ProfilingClusterBuffer {
   ProfilingCluster profiling_cluster_buffer;
 };
-
@@ -1,5 +1,6 @@
 #include "common.glsl"
 #include "instance_matrices_buffer.glsl"
+#include "stereo_view.glsl"
 
 #if !defined(BASIC_PASS)
 #error BASIC_PASS is undefined.
@@ -18,8 +19,10 @@ invariant gl_Position;
 void main() {
   InstanceMatrices m = instance_matrices_buffer[vs_instance_index];
 
+  uint view_index = stereo_view_index();
   vec4 pos_in_obj = to_homogeneous(vs_pos_in_obj);
-  gl_Position = m.obj_to_ren_clp * pos_in_obj;
+  gl_Position = m.obj_to_ren_clp[view_index] * pos_in_obj;
+  stereo_view_clip(view_index, gl_Position);
 
 #if BASIC_PASS == BASIC_PASS_MASKED
   fs_pos_in_tex = vec2(vs_pos_in_tex.x, 1.0 - vs_pos_in_tex.y);
@@ -0,0 +1,27 @@
+// Radial density masking in 2x2 pixel quads, see `foveation_renderer.rs`.
+
+layout(location = TANGENTS_LOC) uniform vec4 foveation_tangents;
+layout(location = ANGLES_LOC) uniform vec2 foveation_angles;
+// The origin and dimensions of the view in the framebuffer.
+layout(location = VIEWPORT_LOC) uniform vec4 foveation_viewport;
+
+// Quads are aligned to the origin of the view.
+ivec2 foveation_pos(vec2 frag_coord) {
+  return ivec2(frag_coord) - ivec2(foveation_viewport.xy);
+}
+
+// 0 shades every quad, 1 every other quad and 2 one in four quads.
+uint foveation_level(ivec2 quad) {
+  vec2 pos_in_tex = (vec2(quad * 2) + 1.0) / foveation_viewport.zw;
+  vec2 tangents = mix(foveation_tangents.xz, foveation_tangents.yw, pos_in_tex);
+  float angle = atan(length(tangents));
+  return angle < foveation_angles.x ? 0 : angle < foveation_angles.y ? 1 : 2;
+}
+
+bool foveation_quad_shaded(ivec2 quad) {
+  switch (foveation_level(quad)) {
+    case 0: return true;
+    case 1: return ((quad.x + quad.y) & 1) == 0;
+    default: return ((quad.x | quad.y) & 1) == 0;
+  }
+}
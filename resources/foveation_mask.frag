@@ -0,0 +1,10 @@
+#include "foveation.glsl"
+
+void main() {
+  if (foveation_quad_shaded(foveation_pos(gl_FragCoord.xy) / 2)) {
+    discard;
+  }
+
+  // Depth is reversed so nothing passes the depth test here anymore.
+  gl_FragDepth = 1.0;
+}
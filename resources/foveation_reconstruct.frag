@@ -0,0 +1,44 @@
+#include "foveation.glsl"
+
+layout(binding = COLOR_SAMPLER_BINDING) uniform sampler2D color_sampler;
+
+layout(location = 0) out vec4 frag_color;
+
+// Averages the nearest pixels of the shaded quads that share an edge with the
+// masked quad, falling back to the quads that share a corner.
+void main() {
+  ivec2 pos = foveation_pos(gl_FragCoord.xy);
+  ivec2 quad = pos / 2;
+  if (foveation_quad_shaded(quad)) {
+    discard;
+  }
+
+  ivec2 dimensions = ivec2(foveation_viewport.zw);
+  ivec2 quad_count = (dimensions + 1) / 2;
+  ivec2 offsets[8] = ivec2[8](
+    ivec2(-1, 0), ivec2(1, 0), ivec2(0, -1), ivec2(0, 1),
+    ivec2(-1, -1), ivec2(1, -1), ivec2(-1, 1), ivec2(1, 1)
+  );
+
+  vec4 sum = vec4(0.0);
+  for (int i = 0; i < 8; i++) {
+    if (i == 4 && sum.a > 0.0) {
+      break;
+    }
+
+    ivec2 neighbour = quad + offsets[i];
+    if (any(lessThan(neighbour, ivec2(0))) || any(greaterThanEqual(neighbour, quad_count)) ||
+        !foveation_quad_shaded(neighbour)) {
+      continue;
+    }
+
+    ivec2 sample_pos = clamp(pos, neighbour * 2, min(neighbour * 2 + 1, dimensions - 1));
+    sum += vec4(texelFetch(color_sampler, ivec2(foveation_viewport.xy) + sample_pos, 0).rgb, 1.0);
+  }
+
+  if (sum.a == 0.0) {
+    discard;
+  }
+
+  frag_color = vec4(sum.rgb / sum.a, 1.0);
+}
@@ -0,0 +1,29 @@
+layout(binding = DEPTH_SAMPLER_BINDING) uniform sampler2D depth_sampler;
+
+layout(location = 0) out float frag_depth;
+
+void main() {
+  ivec2 src_size = textureSize(depth_sampler, 0);
+  ivec2 dst_size = max(src_size / 2, ivec2(1));
+  ivec2 dst_pos = ivec2(gl_FragCoord.xy);
+
+  // Each texel covers 2x2 source texels. The last row and column pick up
+  // the remainder of odd sized sources.
+  ivec2 p0 = dst_pos * 2;
+  ivec2 p1 = mix(
+    min(p0 + 1, src_size - 1),
+    src_size - 1,
+    equal(dst_pos, dst_size - 1)
+  );
+
+  // Depth is reversed so the farthest depth is the smallest.
+  float depth = 1.0;
+  for (int y = p0.y; y <= p1.y; y++) {
+    for (int x = p0.x; x <= p1.x; x++) {
+      // Pixels masked by foveation hold the nearest depth but occlude nothing.
+      float d = texelFetch(depth_sampler, ivec2(x, y), 0).r;
+      depth = min(depth, d == 1.0 ? 0.0 : d);
+    }
+  }
+  frag_depth = depth;
+}
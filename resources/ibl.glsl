@@ -0,0 +1,26 @@
+layout(binding = IBL_IRRADIANCE_SAMPLER_BINDING) uniform samplerCube ibl_irradiance_sampler;
+layout(binding = IBL_PREFILTERED_SAMPLER_BINDING) uniform samplerCube ibl_prefiltered_sampler;
+layout(binding = IBL_BRDF_LUT_SAMPLER_BINDING) uniform sampler2D ibl_brdf_lut_sampler;
+layout(location = IBL_INTENSITY_LOC) uniform float ibl_intensity;
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 F0, float roughness) {
+  return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// Radiance reflected from the environment towards `V` using the split sum
+// approximation, see "Real Shading in Unreal Engine 4" by Brian Karis.
+vec3 ibl_radiance(vec3 N, vec3 V, vec3 kd, vec3 ks, float roughness, float metalness) {
+  vec3 F0 = specular_f0(kd, ks, metalness);
+  float NdotV = max(dot(N, V), 0.0);
+  vec3 F = fresnel_schlick_roughness(NdotV, F0, roughness);
+  vec3 kD = (vec3(1.0) - F) * (1.0 - metalness);
+
+  vec3 diffuse = texture(ibl_irradiance_sampler, N).rgb * kd;
+
+  float max_lod = float(textureQueryLevels(ibl_prefiltered_sampler) - 1);
+  vec3 prefiltered = textureLod(ibl_prefiltered_sampler, reflect(-V, N), roughness * max_lod).rgb;
+  vec2 brdf = texture(ibl_brdf_lut_sampler, vec2(NdotV, roughness)).rg;
+  vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+
+  return ibl_intensity * (kD * diffuse + specular);
+}
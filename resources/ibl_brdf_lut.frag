@@ -0,0 +1,41 @@
+#include "pbr.glsl"
+#include "ibl_sampling.glsl"
+
+layout(location = SAMPLE_COUNT_LOC) uniform uint sample_count;
+
+in vec2 fs_pos_in_tex;
+
+layout(location = 0) out vec2 frag_scale_bias;
+
+float geometry_schlick_ggx_ibl(float n_dot_x, float roughness) {
+  float k = roughness * roughness / 2.0;
+  return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+// Scale and bias to F0 of the specular BRDF integrated over the hemisphere,
+// indexed by the cosine between the normal and the view direction and the
+// roughness.
+void main() {
+  float n_dot_v = max(fs_pos_in_tex.x, 1e-3);
+  float roughness = fs_pos_in_tex.y;
+
+  vec3 n = vec3(0.0, 0.0, 1.0);
+  vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+  vec2 sum = vec2(0.0);
+  for (uint i = 0u; i < sample_count; i++) {
+    vec3 h = importance_sample_ggx(hammersley(i, sample_count), n, roughness);
+    vec3 l = reflect(-v, h);
+    float n_dot_l = l.z;
+    if (n_dot_l > 0.0) {
+      float n_dot_h = max(h.z, 0.0);
+      float v_dot_h = max(dot(v, h), 0.0);
+      float g = geometry_schlick_ggx_ibl(n_dot_v, roughness) * geometry_schlick_ggx_ibl(n_dot_l, roughness);
+      float g_vis = g * v_dot_h / max(n_dot_h * n_dot_v, 1e-4);
+      float fc = pow(1.0 - v_dot_h, 5.0);
+      sum += vec2(1.0 - fc, fc) * g_vis;
+    }
+  }
+
+  frag_scale_bias = sum / float(sample_count);
+}
@@ -0,0 +1,7 @@
+layout(location = VS_POS_IN_TEX_LOC) in vec2 vs_pos_in_tex;
+
+out vec2 ge_pos_in_clp;
+
+void main() {
+  ge_pos_in_clp = vs_pos_in_tex * 2.0 - 1.0;
+}
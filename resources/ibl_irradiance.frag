@@ -0,0 +1,22 @@
+#include "pbr.glsl"
+#include "ibl_sampling.glsl"
+#include "ibl_sky.glsl"
+
+layout(location = SAMPLE_COUNT_LOC) uniform uint sample_count;
+
+in vec3 fs_dir_in_lgt;
+
+layout(location = 0) out vec4 frag_color;
+
+// Irradiance divided by PI, so that multiplying by the albedo yields the
+// Lambertian radiance.
+void main() {
+  vec3 n = normalize(fs_dir_in_lgt);
+
+  vec3 sum = vec3(0.0);
+  for (uint i = 0u; i < sample_count; i++) {
+    sum += sky_radiance(importance_sample_cosine(hammersley(i, sample_count), n));
+  }
+
+  frag_color = vec4(sum / float(sample_count), 1.0);
+}
@@ -0,0 +1,30 @@
+#include "pbr.glsl"
+#include "ibl_sampling.glsl"
+#include "ibl_sky.glsl"
+
+layout(location = SAMPLE_COUNT_LOC) uniform uint sample_count;
+layout(location = ROUGHNESS_LOC) uniform float roughness;
+
+in vec3 fs_dir_in_lgt;
+
+layout(location = 0) out vec4 frag_color;
+
+// Convolves the sky with the GGX lobe of `roughness`. The view direction is
+// assumed to equal the normal and the reflection direction.
+void main() {
+  vec3 n = normalize(fs_dir_in_lgt);
+
+  vec3 sum = vec3(0.0);
+  float weight = 0.0;
+  for (uint i = 0u; i < sample_count; i++) {
+    vec3 h = importance_sample_ggx(hammersley(i, sample_count), n, roughness);
+    vec3 l = reflect(-n, h);
+    float n_dot_l = dot(n, l);
+    if (n_dot_l > 0.0) {
+      sum += sky_radiance(l) * n_dot_l;
+      weight += n_dot_l;
+    }
+  }
+
+  frag_color = vec4(sum / max(weight, 1e-4), 1.0);
+}
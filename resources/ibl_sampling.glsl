@@ -0,0 +1,32 @@
+// Requires PI from pbr.glsl.
+
+// Low discrepancy points in [0, 1)^2, see "Hammersley Points on the
+// Hemisphere" by Holger Dammertz.
+vec2 hammersley(uint i, uint n) {
+  return vec2(float(i) / float(n), float(bitfieldReverse(i)) * 2.3283064365386963e-10);
+}
+
+// Rotates `v` from a space in which +z points along `n` into the space of `n`.
+vec3 tangent_to_lgt(vec3 v, vec3 n) {
+  vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+  vec3 t = normalize(cross(up, n));
+  vec3 b = cross(n, t);
+  return t * v.x + b * v.y + n * v.z;
+}
+
+// Half vector around `n` distributed according to the GGX normal distribution.
+vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+  float a = roughness * roughness;
+  float phi = 2.0 * PI * xi.x;
+  float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+  float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+  return normalize(tangent_to_lgt(vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n));
+}
+
+// Direction around `n` distributed proportionally to the cosine with `n`.
+vec3 importance_sample_cosine(vec2 xi, vec3 n) {
+  float phi = 2.0 * PI * xi.x;
+  float cos_theta = sqrt(1.0 - xi.y);
+  float sin_theta = sqrt(xi.y);
+  return normalize(tangent_to_lgt(vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n));
+}
@@ -0,0 +1,12 @@
+layout(location = SKY_ZENITH_COLOR_LOC) uniform vec3 sky_zenith_color;
+layout(location = SKY_HORIZON_COLOR_LOC) uniform vec3 sky_horizon_color;
+layout(location = SKY_GROUND_COLOR_LOC) uniform vec3 sky_ground_color;
+
+// Radiance arriving from direction `dir`, with +y pointing up.
+vec3 sky_radiance(vec3 dir) {
+  float y = normalize(dir).y;
+  if (y >= 0.0) {
+    return mix(sky_horizon_color, sky_zenith_color, sqrt(y));
+  }
+  return mix(sky_horizon_color, sky_ground_color, sqrt(sqrt(-y)));
+}
@@ -1,5 +1,5 @@
 struct InstanceMatrices {
-  mat4 obj_to_ren_clp; // = wld_to_ren_clp * obj_to_wld
+  mat4 obj_to_ren_clp[2]; // = wld_to_ren_clp[view_index] * obj_to_wld
   mat4 obj_to_lgt; // = obj_to_wld
   mat4 obj_to_lgt_inv_tra; // = transpose(inverse(obj_to_lgt))
 };
@@ -20,7 +20,7 @@ void main() {
   InstanceMatrices m = instance_matrices_buffer[vs_instance_index];
 
   vec4 pos_in_obj = to_homogeneous(vs_pos_in_obj);
-  gl_Position = m.obj_to_ren_clp * pos_in_obj;
+  gl_Position = m.obj_to_ren_clp[0] * pos_in_obj;
   ge_pos_in_lgt = mat4x3(m.obj_to_lgt) * pos_in_obj;
   ge_nor_in_lgt = normalize(mat3(m.obj_to_lgt_inv_tra) * vs_nor_in_obj);
   ge_bin_in_lgt = normalize(mat3(m.obj_to_lgt) * vs_bin_in_obj);
@@ -10,7 +10,9 @@ out vec3 fs_tint;
 void main() {
   PointLight light = light_buffer.point_lights[gl_InstanceID];
 
-  vec3 pos_in_wld = light.position + vs_pos_in_obj * light.r1;
+  // Matches LightBufferLight::radius.
+  float radius = light.r1 + sqrt(dot(light.axis_0, light.axis_0) + dot(light.axis_1, light.axis_1));
+  vec3 pos_in_wld = light.position + vs_pos_in_obj * radius;
   gl_Position = wld_to_clp * to_homogeneous(pos_in_wld);
 
   fs_tint = light.tint;
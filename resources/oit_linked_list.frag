@@ -0,0 +1,56 @@
+#include "oit_linked_list.glsl"
+
+// Number of fragments per pixel that are sorted and blended. When a pixel has
+// more fragments, the farthest ones are dropped.
+#define OIT_MAX_LAYERS 16
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+  vec4 colors[OIT_MAX_LAYERS];
+  float depths[OIT_MAX_LAYERS];
+  int count = 0;
+
+  // Insertion sort from far to near. Depth is reversed so nearer fragments
+  // have a larger depth.
+  uint node_ref = oit_heads[oit_pixel_index()];
+  while (node_ref != 0) {
+    OitNode node = oit_nodes[node_ref - 1];
+    node_ref = node.next;
+
+    int i;
+    if (count < OIT_MAX_LAYERS) {
+      i = count;
+      count += 1;
+    } else if (node.depth > depths[0]) {
+      for (int j = 1; j < OIT_MAX_LAYERS; j++) {
+        colors[j - 1] = colors[j];
+        depths[j - 1] = depths[j];
+      }
+      i = OIT_MAX_LAYERS - 1;
+    } else {
+      continue;
+    }
+
+    while (i > 0 && depths[i - 1] > node.depth) {
+      colors[i] = colors[i - 1];
+      depths[i] = depths[i - 1];
+      i -= 1;
+    }
+    colors[i] = vec4(unpackHalf2x16(node.color_rg), unpackHalf2x16(node.color_ba));
+    depths[i] = node.depth;
+  }
+
+  if (count == 0) {
+    discard;
+  }
+
+  vec3 color = vec3(0.0);
+  float transmittance = 1.0;
+  for (int i = 0; i < count; i++) {
+    color = mix(color, colors[i].rgb, colors[i].a);
+    transmittance *= 1.0 - colors[i].a;
+  }
+
+  frag_color = vec4(color, transmittance);
+}
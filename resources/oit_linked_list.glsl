@@ -0,0 +1,33 @@
+// Per pixel linked lists of transparent fragments, see
+// `main_resources::LinkedListOit`.
+
+struct OitNode {
+  uint color_rg;
+  uint color_ba;
+  float depth;
+  uint next;
+};
+
+layout(std430, binding = OIT_HEAD_BUFFER_BINDING) buffer OitHeadBuffer {
+  uint oit_node_count;
+  uint oit_heads[];
+};
+
+layout(std430, binding = OIT_NODE_BUFFER_BINDING) buffer OitNodeBuffer {
+  OitNode oit_nodes[];
+};
+
+layout(location = OIT_WIDTH_LOC) uniform uint oit_width;
+
+uint oit_pixel_index() {
+  return uint(gl_FragCoord.y) * oit_width + uint(gl_FragCoord.x);
+}
+
+// Fragments that do not fit in the node buffer are dropped.
+void oit_push_fragment(vec4 color, float depth) {
+  uint node_index = atomicAdd(oit_node_count, 1);
+  if (node_index < oit_nodes.length()) {
+    uint next = atomicExchange(oit_heads[oit_pixel_index()], node_index + 1);
+    oit_nodes[node_index] = OitNode(packHalf2x16(color.rg), packHalf2x16(color.ba), depth, next);
+  }
+}
@@ -0,0 +1,30 @@
+#include "native/SAMPLE_COUNT"
+
+#if SAMPLE_COUNT == 0
+layout(binding = OIT_ACCUM_SAMPLER_BINDING) uniform sampler2D accum_sampler;
+layout(binding = OIT_REVEALAGE_SAMPLER_BINDING) uniform sampler2D revealage_sampler;
+#else
+layout(binding = OIT_ACCUM_SAMPLER_BINDING) uniform sampler2DMS accum_sampler;
+layout(binding = OIT_REVEALAGE_SAMPLER_BINDING) uniform sampler2DMS revealage_sampler;
+#endif
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+  ivec2 pos = ivec2(gl_FragCoord.xy);
+#if SAMPLE_COUNT == 0
+  vec4 accum = texelFetch(accum_sampler, pos, 0);
+  float log_revealage = texelFetch(revealage_sampler, pos, 0).r;
+#else
+  vec4 accum = texelFetch(accum_sampler, pos, gl_SampleID);
+  float log_revealage = texelFetch(revealage_sampler, pos, gl_SampleID).r;
+#endif
+
+  // The revealage target holds the sum of -log(1 - alpha).
+  float revealage = exp(-log_revealage);
+  if (revealage > 0.999) {
+    discard;
+  }
+
+  frag_color = vec4(accum.rgb / max(accum.a, 1e-5), 1.0 - revealage);
+}
@@ -40,12 +40,17 @@ vec3 fresnelSchlick(float cosTheta, vec3 F0)
     return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
 }
 // ----------------------------------------------------------------------------
-vec3 cook_torrance(vec3 L, vec3 N, vec3 V, vec3 kd, float roughness, float metalness) {
+// Reflectance at normal incidence. The specular color maps [0, 1] onto the
+// range of common dielectrics [0, 0.08], so 0.5 gives the usual 0.04.
+vec3 specular_f0(vec3 kd, vec3 ks, float metalness) {
+  return mix(0.08 * ks, kd, metalness);
+}
+// ----------------------------------------------------------------------------
+vec3 cook_torrance(vec3 L, vec3 N, vec3 V, vec3 kd, vec3 ks, float roughness, float metalness) {
   // roughness *= 0.6;
   // metalness *= 2.0;
 
-  vec3 F0 = vec3(0.04);
-  F0 = mix(F0, kd, metalness);
+  vec3 F0 = specular_f0(kd, ks, metalness);
 
   // calculate per-light radiance
   vec3 H = normalize(V + L);
@@ -63,3 +68,12 @@ vec3 cook_torrance(vec3 L, vec3 N, vec3 V, vec3 kd, float roughness, float metal
 
   return (kD * kd / PI + specular) * NdotL;
 }
+// ----------------------------------------------------------------------------
+// Smooth falloff between the inner and outer cone of a spot light. Lights with
+// cos_outer <= -1 are omnidirectional.
+float spot_light_cone(vec3 l_to_f_norm, vec3 direction, float cos_inner, float cos_outer) {
+  if (cos_outer <= -1.0) {
+    return 1.0;
+  }
+  return smoothstep(cos_outer, cos_inner, dot(l_to_f_norm, direction));
+}
@@ -1,3 +1,10 @@
+#define LIGHT_KIND_POINT 0
+#define LIGHT_KIND_SPOT 1
+#define LIGHT_KIND_RECTANGLE 2
+#define LIGHT_KIND_TUBE 3
+
+#define SHADOW_INDEX_NONE 0xffffffffu
+
 struct PointLight {
   vec3 tint;
   float _pad0;
@@ -10,5 +17,21 @@ struct PointLight {
   float i0;
   float r0;
   float r1;
-};
 
+  // Spot lights. Omnidirectional lights have cos_outer = -1.
+  vec3 direction;
+  float cos_outer;
+
+  // Half extents of area lights, zero for other lights.
+  vec3 axis_0;
+  float cos_inner;
+
+  vec3 axis_1;
+  uint kind;
+
+  // Cube map index in the shadow atlas or SHADOW_INDEX_NONE.
+  uint shadow_index;
+  uint _pad1;
+  uint _pad2;
+  uint _pad3;
+};
@@ -0,0 +1,51 @@
+#include "post_exposure.glsl"
+
+layout(binding = COLOR_SAMPLER_BINDING) uniform sampler2D color_sampler;
+layout(binding = BLOOM_SAMPLER_BINDING) uniform sampler2D bloom_sampler;
+
+layout(location = BLOOM_THRESHOLD_LOC) uniform float bloom_threshold;
+
+in vec2 fs_pos_in_tex;
+
+layout(location = 0) out vec4 frag_color;
+
+void main() {
+#if BLOOM_PASS == BLOOM_PASS_PREFILTER
+  // Average the 2x2 source texels and keep what exceeds the threshold after
+  // exposure so the threshold does not depend on the scene brightness.
+  ivec2 p0 = ivec2(gl_FragCoord.xy) * 2;
+  ivec2 p1 = min(p0 + 1, textureSize(color_sampler, 0) - 1);
+  vec3 color = 0.25 * (
+    texelFetch(color_sampler, ivec2(p0.x, p0.y), 0).rgb +
+    texelFetch(color_sampler, ivec2(p1.x, p0.y), 0).rgb +
+    texelFetch(color_sampler, ivec2(p0.x, p1.y), 0).rgb +
+    texelFetch(color_sampler, ivec2(p1.x, p1.y), 0).rgb
+  ) * get_exposure();
+  float l = luminance(color);
+  frag_color = vec4(color * (max(l - bloom_threshold, 0.0) / max(l, 1e-4)), 1.0);
+#elif BLOOM_PASS == BLOOM_PASS_DOWNSAMPLE
+  // Four bilinear taps cover a 4x4 texel footprint of the larger level.
+  vec2 d = 1.0 / vec2(textureSize(bloom_sampler, 0));
+  frag_color = vec4(0.25 * (
+    textureLod(bloom_sampler, fs_pos_in_tex + vec2(-d.x, -d.y), 0.0).rgb +
+    textureLod(bloom_sampler, fs_pos_in_tex + vec2(d.x, -d.y), 0.0).rgb +
+    textureLod(bloom_sampler, fs_pos_in_tex + vec2(-d.x, d.y), 0.0).rgb +
+    textureLod(bloom_sampler, fs_pos_in_tex + vec2(d.x, d.y), 0.0).rgb
+  ), 1.0);
+#elif BLOOM_PASS == BLOOM_PASS_UPSAMPLE
+  // 3x3 tent filter over the smaller level, added to the current level.
+  vec2 d = 1.0 / vec2(textureSize(bloom_sampler, 0));
+  vec3 sum = 4.0 * textureLod(bloom_sampler, fs_pos_in_tex, 0.0).rgb;
+  sum += 2.0 * textureLod(bloom_sampler, fs_pos_in_tex + vec2(-d.x, 0.0), 0.0).rgb;
+  sum += 2.0 * textureLod(bloom_sampler, fs_pos_in_tex + vec2(d.x, 0.0), 0.0).rgb;
+  sum += 2.0 * textureLod(bloom_sampler, fs_pos_in_tex + vec2(0.0, -d.y), 0.0).rgb;
+  sum += 2.0 * textureLod(bloom_sampler, fs_pos_in_tex + vec2(0.0, d.y), 0.0).rgb;
+  sum += textureLod(bloom_sampler, fs_pos_in_tex + vec2(-d.x, -d.y), 0.0).rgb;
+  sum += textureLod(bloom_sampler, fs_pos_in_tex + vec2(d.x, -d.y), 0.0).rgb;
+  sum += textureLod(bloom_sampler, fs_pos_in_tex + vec2(-d.x, d.y), 0.0).rgb;
+  sum += textureLod(bloom_sampler, fs_pos_in_tex + vec2(d.x, d.y), 0.0).rgb;
+  frag_color = vec4(sum / 16.0, 1.0);
+#else
+#error Unknown BLOOM_PASS.
+#endif
+}
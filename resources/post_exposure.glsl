@@ -0,0 +1,17 @@
+layout(std430, binding = EXPOSURE_BUFFER_BINDING) buffer ExposureBuffer {
+  float average_luminance;
+  float auto_exposure_value;
+};
+
+layout(location = AUTO_EXPOSURE_LOC) uniform uint auto_exposure;
+layout(location = EXPOSURE_LOC) uniform float exposure_scale;
+
+float luminance(vec3 color) {
+  return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// The exposure scale is the compensation when auto exposure is enabled and
+// the exposure itself otherwise.
+float get_exposure() {
+  return auto_exposure != 0 ? auto_exposure_value * exposure_scale : exposure_scale;
+}
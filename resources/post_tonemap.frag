@@ -0,0 +1,62 @@
+#include "native/TONEMAPPER"
+#include "post_exposure.glsl"
+
+layout(binding = COLOR_SAMPLER_BINDING) uniform sampler2D color_sampler;
+layout(binding = BLOOM_SAMPLER_BINDING) uniform sampler2D bloom_sampler;
+
+layout(location = BLOOM_INTENSITY_LOC) uniform float bloom_intensity;
+
+in vec2 fs_pos_in_tex;
+
+layout(location = 0) out vec4 frag_color;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+vec3 tonemap_aces(vec3 x) {
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+vec3 tonemap_reinhard(vec3 x) {
+  return x / (1.0 + luminance(x));
+}
+
+// John Hable's filmic curve from Uncharted 2.
+vec3 hable(vec3 x) {
+  const float A = 0.15;
+  const float B = 0.50;
+  const float C = 0.10;
+  const float D = 0.20;
+  const float E = 0.02;
+  const float F = 0.30;
+  return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 tonemap_uncharted_2(vec3 x) {
+  const float exposure_bias = 2.0;
+  const float white_point = 11.2;
+  return clamp(hable(exposure_bias * x) / hable(vec3(white_point)), 0.0, 1.0);
+}
+
+void main() {
+  vec3 color = texelFetch(color_sampler, ivec2(gl_FragCoord.xy), 0).rgb * get_exposure();
+
+  if (bloom_intensity > 0.0) {
+    color += bloom_intensity * textureLod(bloom_sampler, fs_pos_in_tex, 0.0).rgb;
+  }
+
+#if defined(TONEMAPPER_ACES)
+  color = tonemap_aces(color);
+#elif defined(TONEMAPPER_REINHARD)
+  color = tonemap_reinhard(color);
+#elif defined(TONEMAPPER_UNCHARTED_2)
+  color = tonemap_uncharted_2(color);
+#else
+#error Unknown tonemapper.
+#endif
+
+  frag_color = vec4(color, 1.0);
+}
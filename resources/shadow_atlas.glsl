@@ -0,0 +1,19 @@
+layout(binding = SHADOW_ATLAS_SAMPLER_BINDING) uniform samplerCubeArray shadow_atlas_sampler;
+
+// Returns 0.0 if the fragment is occluded from the light and 1.0 otherwise.
+// Lights without a tile in the shadow atlas never cast shadows.
+float light_shadow(PointLight light, vec3 frag_pos_in_lgt, vec3 frag_geo_nor_in_lgt) {
+  if (light.shadow_index == SHADOW_INDEX_NONE) {
+    return 1.0;
+  }
+
+  vec3 l_to_f = frag_pos_in_lgt - light.position;
+  float d_l_to_f = length(l_to_f);
+  float d_l_to_f_closest = texture(shadow_atlas_sampler, vec4(l_to_f, float(light.shadow_index))).r;
+
+  // Slope scaled bias, grows with distance because texels get bigger.
+  float cos_theta = clamp(dot(l_to_f / -d_l_to_f, frag_geo_nor_in_lgt), 0.0, 1.0);
+  float bias = (0.005 + 0.02 * sqrt(1.0 - cos_theta * cos_theta)) * d_l_to_f;
+
+  return d_l_to_f <= d_l_to_f_closest + bias ? 1.0 : 0.0;
+}
@@ -0,0 +1,23 @@
+#if !defined(BASIC_PASS)
+#error BASIC_PASS is undefined.
+#endif
+
+layout(binding = DIFFUSE_SAMPLER_BINDING) uniform sampler2D diffuse_sampler;
+
+layout(location = LIGHT_POS_IN_LGT_LOC) uniform vec3 light_pos_in_lgt;
+
+in vec3 fs_pos_in_lgt;
+in vec2 fs_pos_in_tex;
+
+layout(location = 0) out float frag_distance;
+
+void main() {
+#if BASIC_PASS == BASIC_PASS_MASKED
+  vec4 kd = texture(diffuse_sampler, fs_pos_in_tex);
+  if (kd.a < 0.5) {
+    discard;
+  }
+#endif
+
+  frag_distance = length(fs_pos_in_lgt - light_pos_in_lgt);
+}
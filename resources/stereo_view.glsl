@@ -0,0 +1,13 @@
+// Instanced stereo draws every instance once per eye and places the eyes side
+// by side in a single framebuffer, see `MainResources::eyes`. Mono rendering
+// draws every instance once and only has view 0.
+
+uint stereo_view_index() {
+  return uint(gl_InstanceID) % 2u;
+}
+
+// Confines a view to its half of the framebuffer. Only has an effect while
+// GL_CLIP_DISTANCE0 is enabled.
+void stereo_view_clip(uint view_index, vec4 pos_in_ren_clp) {
+  gl_ClipDistance[0] = view_index == 0u ? -pos_in_ren_clp.x : pos_in_ren_clp.x;
+}